@@ -0,0 +1,426 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::documents;
+use crate::error::AppError;
+use crate::files::{self, FileContent};
+use crate::images::{self, ImageData};
+use crate::references;
+
+// Levels listed for a directory attachment unless given
+const DEFAULT_DIRECTORY_DEPTH: usize = 2;
+// Entries listed for a directory before the rest are only counted
+const MAX_DIRECTORY_ENTRIES: usize = 500;
+
+// Context sent along with a chat message. Relative paths are resolved
+// against the session's working dir, and lines are numbered from 1.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Attachment {
+    // A whole file, or lines start_line..=end_line of it. Documents are
+    // attached as the markdown read_document gives.
+    File {
+        path: String,
+        start_line: Option<usize>,
+        end_line: Option<usize>,
+    },
+    // The files and folders below a folder, `depth` levels down
+    Directory { path: String, depth: Option<usize> },
+    // Text selected in the editor. Offsets count UTF-16 code units into the
+    // file, as the editor does; without them the text is looked up in the
+    // file to find its lines.
+    Selection {
+        text: String,
+        path: Option<String>,
+        start: Option<usize>,
+        end: Option<usize>,
+    },
+    // A PNG, JPEG, GIF or WebP image, scaled down if it is too large
+    Image { path: String },
+    Snippet { text: String, label: Option<String> },
+    // An @-mention such as "notes.md:15-30" or "notes.md#Methods", as
+    // resolve_reference reads it
+    Reference { reference: String },
+}
+
+// An attachment read from disk and labelled, ready for the prompt
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedAttachment {
+    // e.g. "notes.md, lines 10-20"
+    pub label: String,
+    pub path: Option<String>,
+    pub text: String,
+    // Sent as an image block after the text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<ImageData>,
+}
+
+// A block of a user message in stream-json input
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    Text { text: String },
+    Image { source: ImageSource },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageSource {
+    // Always "base64"
+    #[serde(rename = "type")]
+    pub source_type: &'static str,
+    pub media_type: String,
+    pub data: String,
+}
+
+pub fn resolve(
+    attachments: &[Attachment],
+    working_dir: &str,
+    max_read_size: u64,
+) -> Result<Vec<ResolvedAttachment>, AppError> {
+    attachments
+        .iter()
+        .map(|attachment| resolve_one(attachment, Path::new(working_dir), max_read_size))
+        .collect()
+}
+
+// The message followed by its attachments, each in its own tag so Claude
+// can tell where one ends and the next begins. Images follow the text as
+// image blocks, in the order they are listed.
+pub fn render(message: &str, attachments: &[ResolvedAttachment]) -> Vec<ContentBlock> {
+    let mut blocks = vec![ContentBlock::Text {
+        text: render_text(message, attachments),
+    }];
+    for image in attachments.iter().filter_map(|a| a.image.as_ref()) {
+        blocks.push(ContentBlock::Image {
+            source: ImageSource {
+                source_type: "base64",
+                media_type: image.media_type.clone(),
+                data: image.data.clone(),
+            },
+        });
+    }
+    blocks
+}
+
+fn render_text(message: &str, attachments: &[ResolvedAttachment]) -> String {
+    if attachments.is_empty() {
+        return message.to_string();
+    }
+
+    let mut prompt = format!("{}\n\n<attachments>\n", message);
+    for attachment in attachments {
+        prompt.push_str(&format!("<attachment label=\"{}\"", escape(&attachment.label)));
+        if let Some(path) = &attachment.path {
+            prompt.push_str(&format!(" path=\"{}\"", escape(path)));
+        }
+        prompt.push_str(&format!(">\n{}\n</attachment>\n", attachment.text.trim_end_matches('\n')));
+    }
+    prompt.push_str("</attachments>");
+    prompt
+}
+
+fn resolve_one(attachment: &Attachment, working_dir: &Path, max_read_size: u64) -> Result<ResolvedAttachment, AppError> {
+    match attachment {
+        Attachment::File {
+            path,
+            start_line,
+            end_line,
+        } => {
+            let file_path = absolute(working_dir, path)?;
+            let text = read_text(&file_path, max_read_size)?;
+            let (label, text) = match (start_line, end_line) {
+                (None, None) => (file_name(&file_path), text),
+                _ => {
+                    let lines: Vec<&str> = text.lines().collect();
+                    let start = start_line.unwrap_or(1);
+                    let end = end_line.unwrap_or(lines.len()).min(lines.len());
+                    if start == 0 || start > end {
+                        return Err(AppError::invalid(format!(
+                            "Lines {}-{} are not in {}, which has {} lines",
+                            start,
+                            end_line.unwrap_or(end),
+                            path,
+                            lines.len()
+                        )));
+                    }
+                    (
+                        format!("{}, lines {}-{}", file_name(&file_path), start, end),
+                        lines[start - 1..end].join("\n"),
+                    )
+                }
+            };
+            Ok(ResolvedAttachment {
+                label,
+                path: Some(file_path.display().to_string()),
+                text,
+                image: None,
+            })
+        }
+        Attachment::Directory { path, depth } => {
+            let dir_path = absolute(working_dir, path)?;
+            if !dir_path.exists() {
+                return Err(AppError::NotFound { path: path.clone() });
+            }
+            if !dir_path.is_dir() {
+                return Err(AppError::NotADirectory { path: path.clone() });
+            }
+
+            let mut lines = Vec::new();
+            let mut skipped = 0;
+            list(&dir_path, 0, depth.unwrap_or(DEFAULT_DIRECTORY_DEPTH).max(1), &mut lines, &mut skipped)?;
+            if skipped > 0 {
+                lines.push(format!("... and {} more", skipped));
+            }
+            Ok(ResolvedAttachment {
+                label: format!("{}/ (folder listing)", file_name(&dir_path)),
+                path: Some(dir_path.display().to_string()),
+                text: lines.join("\n"),
+                image: None,
+            })
+        }
+        Attachment::Selection { text, path, start, end } => {
+            let Some(path) = path else {
+                return Ok(ResolvedAttachment {
+                    label: "Selection".to_string(),
+                    path: None,
+                    text: text.clone(),
+                    image: None,
+                });
+            };
+            let file_path = absolute(working_dir, path)?;
+            // Lines are a nicety; an unsaved or unreadable file still sends the text
+            let lines = read_text(&file_path, max_read_size)
+                .ok()
+                .and_then(|content| selection_lines(&content, text, *start, *end));
+            let label = match lines {
+                Some((first, last)) if first == last => format!("Selection from {}, line {}", file_name(&file_path), first),
+                Some((first, last)) => format!("Selection from {}, lines {}-{}", file_name(&file_path), first, last),
+                None => format!("Selection from {}", file_name(&file_path)),
+            };
+            Ok(ResolvedAttachment {
+                label,
+                path: Some(file_path.display().to_string()),
+                text: text.clone(),
+                image: None,
+            })
+        }
+        Attachment::Image { path } => {
+            let file_path = absolute(working_dir, path)?;
+            if !file_path.exists() {
+                return Err(AppError::NotFound { path: path.clone() });
+            }
+            if !file_path.is_file() {
+                return Err(AppError::NotAFile { path: path.clone() });
+            }
+            let image = images::load(&file_path)?;
+            let text = match image.original_size {
+                Some((width, height)) => format!(
+                    "Attached as an image below, scaled down from {}x{} to {}x{}",
+                    width, height, image.width, image.height
+                ),
+                None => format!("Attached as an image below, {}x{}", image.width, image.height),
+            };
+            Ok(ResolvedAttachment {
+                label: format!("Image {}", file_name(&file_path)),
+                path: Some(file_path.display().to_string()),
+                text,
+                image: Some(image),
+            })
+        }
+        Attachment::Reference { reference } => {
+            let resolved = references::resolve(reference, working_dir, max_read_size)?;
+            let label = match resolved.heading {
+                Some(heading) => format!("{} (under \"{}\")", resolved.anchor, heading),
+                None => resolved.anchor,
+            };
+            Ok(ResolvedAttachment {
+                label,
+                path: Some(resolved.path),
+                text: resolved.text,
+                image: None,
+            })
+        }
+        Attachment::Snippet { text, label } => Ok(ResolvedAttachment {
+            label: label.clone().unwrap_or_else(|| "Snippet".to_string()),
+            path: None,
+            text: text.clone(),
+            image: None,
+        }),
+    }
+}
+
+// Text of a file, or the markdown of a document
+pub fn read_text(file_path: &Path, max_read_size: u64) -> Result<String, AppError> {
+    let path = file_path.display().to_string();
+    if !file_path.exists() {
+        return Err(AppError::NotFound { path });
+    }
+    if !file_path.is_file() {
+        return Err(AppError::NotAFile { path });
+    }
+
+    if let Some(markdown) = documents::markdown(file_path)? {
+        return Ok(markdown);
+    }
+
+    let size = fs::metadata(file_path)
+        .map_err(|e| AppError::io("Failed to read file", file_path, e))?
+        .len();
+    if size > max_read_size {
+        return Err(AppError::invalid(format!(
+            "{} is too large to attach ({} bytes, the limit is {})",
+            path, size, max_read_size
+        )));
+    }
+    let bytes = fs::read(file_path).map_err(|e| AppError::io("Failed to read file", file_path, e))?;
+    match files::decode(file_path, &bytes) {
+        FileContent::Text { content, .. } => Ok(content),
+        _ => Err(AppError::invalid(format!("Cannot attach binary file as text: {}", path))),
+    }
+}
+
+// Resolve a path against the working dir. Relative paths may not climb out
+// of it with "..", while absolute ones can name any file.
+pub fn absolute(working_dir: &Path, path: &str) -> Result<PathBuf, AppError> {
+    let relative = Path::new(path);
+    if relative.is_absolute() {
+        return Ok(relative.to_path_buf());
+    }
+    let mut depth = 0usize;
+    for component in relative.components() {
+        match component {
+            Component::ParentDir => {
+                depth = depth.checked_sub(1).ok_or_else(|| AppError::OutsideWorkspace {
+                    path: path.to_string(),
+                })?;
+            }
+            Component::Normal(_) => depth += 1,
+            _ => {}
+        }
+    }
+    Ok(working_dir.join(relative))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+// Hidden entries are skipped, as in list_directory; folders come first
+fn list(dir: &Path, level: usize, depth: usize, lines: &mut Vec<String>, skipped: &mut usize) -> Result<(), AppError> {
+    let mut entries: Vec<(String, PathBuf, bool)> = fs::read_dir(dir)
+        .map_err(|e| AppError::io("Failed to read directory", dir, e))?
+        .flatten()
+        .map(|entry| {
+            let path = entry.path();
+            (entry.file_name().to_string_lossy().to_string(), path.clone(), path.is_dir())
+        })
+        .filter(|(name, _, _)| !name.starts_with('.'))
+        .collect();
+    entries.sort_by_key(|(name, _, is_dir)| (!is_dir, name.to_lowercase()));
+
+    for (name, path, is_dir) in entries {
+        if lines.len() >= MAX_DIRECTORY_ENTRIES {
+            *skipped += 1;
+            continue;
+        }
+        let indent = "  ".repeat(level);
+        if is_dir {
+            lines.push(format!("{}{}/", indent, name));
+            if level + 1 < depth {
+                // Unreadable subfolders are listed without their contents
+                let _ = list(&path, level + 1, depth, lines, skipped);
+            }
+        } else {
+            lines.push(format!("{}{}", indent, name));
+        }
+    }
+    Ok(())
+}
+
+// First and last line of a selection, from its offsets or else from where
+// its text first appears in the file
+fn selection_lines(content: &str, text: &str, start: Option<usize>, end: Option<usize>) -> Option<(usize, usize)> {
+    let (start, end) = match start {
+        Some(start) => {
+            let start = byte_offset(content, start)?;
+            let end = match end {
+                Some(end) => byte_offset(content, end)?,
+                None => start + text.len(),
+            };
+            (start, end.max(start))
+        }
+        None => {
+            let start = content.find(text).filter(|_| !text.trim().is_empty())?;
+            (start, start + text.len())
+        }
+    };
+    let line_at = |offset: usize| content[..offset].matches('\n').count() + 1;
+    // A selection ending just after a newline ends on the line before
+    let last = content.get(start..end)?.trim_end_matches('\n').len() + start;
+    Some((line_at(start), line_at(last)))
+}
+
+// Byte offset of a UTF-16 offset, or None past the end
+fn byte_offset(content: &str, utf16_offset: usize) -> Option<usize> {
+    let mut units = 0;
+    for (index, c) in content.char_indices() {
+        if units >= utf16_offset {
+            return Some(index);
+        }
+        units += c.len_utf16();
+    }
+    (units >= utf16_offset).then_some(content.len())
+}
+
+fn escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('"', "&quot;").replace('<', "&lt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_paths_stay_in_the_working_dir() {
+        let dir = Path::new("/work/project");
+        assert_eq!(absolute(dir, "notes.md").unwrap(), dir.join("notes.md"));
+        assert_eq!(absolute(dir, "a/../b.md").unwrap(), dir.join("a/../b.md"));
+        assert_eq!(absolute(dir, "/etc/hosts").unwrap(), Path::new("/etc/hosts"));
+        assert!(matches!(
+            absolute(dir, "../other/secret.md"),
+            Err(AppError::OutsideWorkspace { .. })
+        ));
+        assert!(matches!(
+            absolute(dir, "a/../../b.md"),
+            Err(AppError::OutsideWorkspace { .. })
+        ));
+    }
+
+    #[test]
+    fn selection_offsets_count_utf16_units() {
+        // Each emoji is two UTF-16 units and four bytes
+        let content = "\u{1F600}\u{1F600}\u{1F600}\u{1F600}\nsecond \u{1F680} line\nthird\n";
+        // Line 2 starts at unit 9, line 3 at unit 24
+        assert_eq!(selection_lines(content, "second", Some(9), Some(15)), Some((2, 2)));
+        assert_eq!(selection_lines(content, "", Some(16), Some(29)), Some((2, 3)));
+        // Ending just after a newline stays on the line before
+        assert_eq!(selection_lines(content, "", Some(9), Some(24)), Some((2, 2)));
+        assert_eq!(selection_lines(content, "", Some(0), Some(30)), Some((1, 3)));
+        // Without an end the selected text gives the length
+        assert_eq!(selection_lines(content, "\u{1F680} line\nthird", Some(16), None), Some((2, 3)));
+        // Past the end of the file
+        assert_eq!(selection_lines(content, "", Some(31), None), None);
+        assert_eq!(selection_lines(content, "", Some(0), Some(31)), None);
+    }
+
+    #[test]
+    fn selections_without_offsets_are_found_in_the_file() {
+        let content = "one\ntwo\nthree\ntwo\n";
+        assert_eq!(selection_lines(content, "two\nthree", None, None), Some((2, 3)));
+        assert_eq!(selection_lines(content, "missing", None, None), None);
+        assert_eq!(selection_lines(content, "\n", None, None), None);
+    }
+}
@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+
+use crate::attachments::ResolvedAttachment;
+
+// Characters per token in English prose and code, near enough for a warning
+const CHARS_PER_TOKEN: usize = 4;
+// Claude's window unless the model says otherwise
+const DEFAULT_CONTEXT_WINDOW: usize = 200_000;
+const LONG_CONTEXT_WINDOW: usize = 1_000_000;
+// Kept free for Claude Code's own prompt, tool output and the answer
+const RESERVED_TOKENS: usize = 30_000;
+// Attachments are never cut below this
+const MIN_ATTACHMENT_TOKENS: usize = 200;
+// Room for the note and label suffix a cut-down attachment gets
+const FIT_NOTE_TOKENS: usize = 24;
+
+// How to make oversized attachments fit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fit {
+    // Keep the start of the text
+    Truncate,
+    // Keep the headings and the first line under each, which summarizes a
+    // document without asking Claude; text without headings is truncated
+    Outline,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextEstimate {
+    pub message_tokens: usize,
+    pub attachments: Vec<AttachmentEstimate>,
+    // Message and attachments, after fitting
+    pub total_tokens: usize,
+    // Taken by the conversation so far, from the last answer's usage
+    pub context_used: usize,
+    pub context_window: usize,
+    pub model: Option<String>,
+    // Whether the message fits in what is left of the window
+    pub fits: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AttachmentEstimate {
+    pub label: String,
+    pub path: Option<String>,
+    pub tokens: usize,
+    // Set when `fit` cut the attachment down, with its size before
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_tokens: Option<usize>,
+}
+
+fn estimate_text(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+// Claude's own rule of thumb for images: width * height / 750
+fn estimate_attachment(attachment: &ResolvedAttachment) -> usize {
+    let image = attachment
+        .image
+        .as_ref()
+        .map_or(0, |image| (image.width as usize * image.height as usize).div_ceil(750));
+    estimate_text(&attachment.label) + estimate_text(&attachment.text) + image
+}
+
+fn context_window(model: Option<&str>) -> usize {
+    match model {
+        Some(model) if model.to_ascii_lowercase().contains("[1m]") => LONG_CONTEXT_WINDOW,
+        _ => DEFAULT_CONTEXT_WINDOW,
+    }
+}
+
+// Estimate the message and its attachments against what is left of the
+// window. With `fit`, attachments too large for their share are cut down
+// in place, so the estimate describes what would be sent.
+pub fn estimate(
+    message: &str,
+    attachments: &mut [ResolvedAttachment],
+    model: Option<&str>,
+    context_used: usize,
+    fit: Option<Fit>,
+) -> ContextEstimate {
+    let context_window = context_window(model);
+    let message_tokens = estimate_text(message);
+    let available = context_window.saturating_sub(context_used + RESERVED_TOKENS + message_tokens);
+
+    let original: Vec<usize> = attachments.iter().map(estimate_attachment).collect();
+    if let Some(fit) = fit {
+        if original.iter().sum::<usize>() > available {
+            let shares = shares(&original, available);
+            for ((attachment, share), tokens) in attachments.iter_mut().zip(shares).zip(&original) {
+                // Images are already as small as they get
+                if *tokens > share && attachment.image.is_none() {
+                    shrink(attachment, share.max(MIN_ATTACHMENT_TOKENS), fit);
+                }
+            }
+        }
+    }
+
+    let estimates: Vec<AttachmentEstimate> = attachments
+        .iter()
+        .zip(original)
+        .map(|(attachment, original)| {
+            let tokens = estimate_attachment(attachment);
+            AttachmentEstimate {
+                label: attachment.label.clone(),
+                path: attachment.path.clone(),
+                tokens,
+                original_tokens: (tokens < original).then_some(original),
+            }
+        })
+        .collect();
+    let total_tokens = message_tokens + estimates.iter().map(|a| a.tokens).sum::<usize>();
+
+    ContextEstimate {
+        message_tokens,
+        attachments: estimates,
+        total_tokens,
+        context_used,
+        context_window,
+        model: model.map(str::to_string),
+        fits: context_used + RESERVED_TOKENS + total_tokens <= context_window,
+    }
+}
+
+// Split `available` so small attachments keep all of theirs and the large
+// ones share the rest equally
+fn shares(tokens: &[usize], available: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..tokens.len()).collect();
+    order.sort_by_key(|&i| tokens[i]);
+
+    let mut shares = vec![0; tokens.len()];
+    let mut left = available;
+    for (position, &i) in order.iter().enumerate() {
+        let share = left / (order.len() - position);
+        shares[i] = tokens[i].min(share);
+        left -= shares[i];
+    }
+    shares
+}
+
+// Cut the attachment down so that it, label included, stays within `budget`
+fn shrink(attachment: &mut ResolvedAttachment, budget: usize, fit: Fit) {
+    let budget = budget.saturating_sub(estimate_text(&attachment.label) + FIT_NOTE_TOKENS);
+    let total_lines = attachment.text.lines().count();
+    if fit == Fit::Outline {
+        let outline = outline(&attachment.text);
+        if !outline.is_empty() && estimate_text(&outline) <= budget {
+            attachment.text = format!("{}\n[Outline only: headings and their first lines, from {} lines]", outline, total_lines);
+            attachment.label.push_str(" (outline)");
+            return;
+        }
+    }
+
+    let max_chars = budget * CHARS_PER_TOKEN;
+    let mut kept = String::new();
+    let mut kept_chars = 0;
+    let mut kept_lines = 0;
+    for line in attachment.text.lines() {
+        kept_chars += line.chars().count() + 1;
+        if kept_chars > max_chars {
+            break;
+        }
+        kept.push_str(line);
+        kept.push('\n');
+        kept_lines += 1;
+    }
+    // A first line longer than the budget is cut mid-line
+    if kept_lines == 0 {
+        kept = attachment.text.chars().take(max_chars).collect();
+        kept.push('\n');
+    }
+    attachment.text = format!("{}[Truncated: first {} of {} lines]", kept, kept_lines, total_lines);
+    attachment.label.push_str(" (truncated)");
+}
+
+// Markdown headings, each followed by the first non-empty line under it
+fn outline(text: &str) -> String {
+    let mut lines = Vec::new();
+    let mut want_first_line = false;
+    for line in text.lines() {
+        if line.starts_with('#') {
+            lines.push(line);
+            want_first_line = true;
+        } else if want_first_line && !line.trim().is_empty() {
+            lines.push(line);
+            want_first_line = false;
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attachment(text: &str) -> ResolvedAttachment {
+        ResolvedAttachment {
+            label: "notes.md".to_string(),
+            path: None,
+            text: text.to_string(),
+            image: None,
+        }
+    }
+
+    #[test]
+    fn shares_keep_small_attachments_whole() {
+        assert_eq!(shares(&[100, 1000, 1000], 900), [100, 400, 400]);
+        assert_eq!(shares(&[1000, 100], 600), [500, 100]);
+    }
+
+    #[test]
+    fn shares_are_the_sizes_when_everything_fits() {
+        assert_eq!(shares(&[10, 20, 30], 1000), [10, 20, 30]);
+        assert_eq!(shares(&[], 1000), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn shares_never_exceed_what_is_available() {
+        let tokens = [5000, 1, 3000, 250, 9000];
+        for available in [0, 7, 1000, 4321] {
+            assert!(shares(&tokens, available).iter().sum::<usize>() <= available);
+        }
+    }
+
+    #[test]
+    fn estimates_against_what_is_left_of_the_window() {
+        let mut attachments = vec![attachment(&"word ".repeat(1000))];
+        let empty = estimate("hello", &mut attachments, None, 0, None);
+        assert_eq!(empty.context_window, DEFAULT_CONTEXT_WINDOW);
+        assert_eq!(empty.message_tokens, 2);
+        assert!(empty.fits);
+
+        let used = DEFAULT_CONTEXT_WINDOW - RESERVED_TOKENS;
+        assert!(!estimate("hello", &mut attachments, None, used, None).fits);
+        assert!(estimate("hello", &mut attachments, Some("sonnet[1m]"), used, None).fits);
+    }
+
+    #[test]
+    fn truncates_attachments_to_fit() {
+        let text: String = (1..=20_000).map(|i| format!("line {}\n", i)).collect();
+        let mut attachments = vec![attachment(&text)];
+        let used = DEFAULT_CONTEXT_WINDOW - RESERVED_TOKENS - 1000;
+        let estimate = estimate("hello", &mut attachments, None, used, Some(Fit::Truncate));
+
+        assert!(estimate.fits);
+        assert!(estimate.attachments[0].original_tokens.is_some());
+        assert!(attachments[0].text.starts_with("line 1\nline 2\n"));
+        assert!(attachments[0].text.ends_with("of 20000 lines]"));
+        assert!(attachments[0].label.ends_with("(truncated)"));
+    }
+
+    #[test]
+    fn outlines_documents_with_headings() {
+        let mut text = String::new();
+        for section in 1..=5 {
+            text.push_str(&format!("# Section {}\n\nFirst line of {}.\n", section, section));
+            text.push_str(&"More text that the outline leaves out.\n".repeat(2000));
+        }
+        let mut attachments = vec![attachment(&text)];
+        let used = DEFAULT_CONTEXT_WINDOW - RESERVED_TOKENS - 1000;
+        estimate("hello", &mut attachments, None, used, Some(Fit::Outline));
+
+        assert!(attachments[0].text.starts_with("# Section 1\nFirst line of 1.\n# Section 2\n"));
+        assert!(!attachments[0].text.contains("More text"));
+        assert!(attachments[0].label.ends_with("(outline)"));
+    }
+}
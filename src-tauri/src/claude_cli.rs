@@ -0,0 +1,541 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+use crate::error::AppError;
+
+// File in the app data dir that holds the Claude CLI settings
+pub const SETTINGS_FILE: &str = "claude-settings.json";
+
+// Oldest CLI release whose --input-format stream-json works with our sessions
+pub const MIN_VERSION: Version = Version(1, 0, 0);
+
+// A login shell that hangs in its rc files must not hang the app
+const SHELL_TIMEOUT: Duration = Duration::from_secs(5);
+const VERSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(pub u32, pub u32, pub u32);
+
+impl Version {
+    // First x.y.z in the text, e.g. "1.0.43 (Claude Code)"
+    pub fn parse(text: &str) -> Option<Self> {
+        text.split_whitespace().find_map(|word| {
+            let mut parts = word.trim_start_matches('v').split('.').map(|part| {
+                let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+                digits.parse::<u32>().ok()
+            });
+            let major = parts.next()??;
+            let minor = parts.next()??;
+            let patch = parts.next().flatten().unwrap_or(0);
+            Some(Version(major, minor, patch))
+        })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct ClaudeSettings {
+    // Explicit binary, used instead of searching when set
+    #[serde(default)]
+    pub binary_path: Option<String>,
+}
+
+// Where the binary was found
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BinarySource {
+    Settings,
+    Path,
+    LoginShell,
+    KnownLocation,
+}
+
+// Best guess from the credentials the CLI keeps; a session can still be
+// refused if they have expired
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthStatus {
+    ApiKey,
+    Subscription,
+    CloudProvider,
+    NotLoggedIn,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClaudeStatus {
+    // Found, runs and is recent enough for chat sessions
+    pub available: bool,
+    pub path: Option<String>,
+    pub source: Option<BinarySource>,
+    pub version: Option<String>,
+    pub min_version: String,
+    pub supported: bool,
+    pub auth: AuthStatus,
+    // What the user needs to fix, if anything
+    pub problem: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Binary {
+    path: PathBuf,
+    source: BinarySource,
+    // PATH for the child, so a node-based CLI finds node when the app was
+    // started from the dock with a minimal environment
+    search_path: Option<OsString>,
+    // None when `--version` failed
+    version: Option<Version>,
+}
+
+pub struct ClaudeCli {
+    settings: ClaudeSettings,
+    settings_path: Option<PathBuf>,
+    // Found on first use and kept until a refresh
+    binary: Option<Binary>,
+    // PATH from the user's login shell, looked up once per refresh
+    login_path: Option<Option<OsString>>,
+}
+
+pub struct ClaudeCliState(pub Arc<Mutex<ClaudeCli>>);
+
+impl ClaudeCli {
+    pub fn new() -> Self {
+        Self {
+            settings: ClaudeSettings::default(),
+            settings_path: None,
+            binary: None,
+            login_path: None,
+        }
+    }
+
+    fn refresh(&mut self) {
+        self.binary = None;
+        self.login_path = None;
+    }
+
+    fn save_settings(&self) -> Result<(), String> {
+        let Some(path) = &self.settings_path else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(&self.settings)
+            .map_err(|e| format!("Failed to serialize Claude settings: {}", e))?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+        }
+
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, json).map_err(|e| format!("Failed to write Claude settings: {}", e))?;
+        fs::rename(&tmp_path, path).map_err(|e| format!("Failed to write Claude settings: {}", e))
+    }
+}
+
+impl Default for ClaudeCli {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn load_settings(state: &Arc<Mutex<ClaudeCli>>, app_data_dir: &Path) {
+    let path = app_data_dir.join(SETTINGS_FILE);
+    let Ok(mut cli) = state.lock() else { return };
+
+    if path.exists() {
+        match fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read Claude settings: {}", e))
+            .and_then(|content| {
+                serde_json::from_str(&content).map_err(|e| format!("Failed to parse Claude settings: {}", e))
+            }) {
+            Ok(settings) => cli.settings = settings,
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+    cli.settings_path = Some(path);
+    cli.refresh();
+}
+
+// The binary found earlier, or else search for it. Searching runs the login
+// shell and `claude --version`, which can take seconds, so the state is
+// only locked to read the settings and to keep what was found.
+fn find_binary(state: &Arc<Mutex<ClaudeCli>>) -> Result<Option<Binary>, AppError> {
+    let (binary_path, login_path) = {
+        let cli = state.lock().map_err(|e| e.to_string())?;
+        if let Some(binary) = &cli.binary {
+            return Ok(Some(binary.clone()));
+        }
+        (cli.settings.binary_path.clone(), cli.login_path.clone())
+    };
+
+    let login_path = login_path.unwrap_or_else(login_shell_path);
+    let binary = discover(binary_path.as_deref(), login_path.as_deref());
+
+    let mut cli = state.lock().map_err(|e| e.to_string())?;
+    // The path was changed while searching; the next use searches again
+    if cli.settings.binary_path == binary_path {
+        cli.login_path = Some(login_path);
+        cli.binary = binary.clone();
+    }
+    Ok(binary)
+}
+
+// Look in the configured path, then PATH, the login shell's PATH and
+// the places installers put the binary
+fn discover(binary_path: Option<&str>, login_path: Option<&OsStr>) -> Option<Binary> {
+    let (path, source) = match binary_path {
+        Some(path) => (Some(PathBuf::from(path)).filter(|p| p.is_file())?, BinarySource::Settings),
+        None => env::var_os("PATH")
+            .and_then(|path| find_in(&path))
+            .map(|p| (p, BinarySource::Path))
+            .or_else(|| login_path.and_then(find_in).map(|p| (p, BinarySource::LoginShell)))
+            .or_else(|| known_locations().into_iter().find(|p| p.is_file()).map(|p| (p, BinarySource::KnownLocation)))?,
+    };
+
+    let search_path = search_path(&path, login_path);
+    let mut version_cmd = Command::new(&path);
+    version_cmd.arg("--version");
+    if let Some(search_path) = &search_path {
+        version_cmd.env("PATH", search_path);
+    }
+    let version = run_with_timeout(version_cmd, VERSION_TIMEOUT)
+        .filter(|output| output.status.success())
+        .and_then(|output| Version::parse(&String::from_utf8_lossy(&output.stdout)));
+
+    Some(Binary {
+        path,
+        source,
+        search_path,
+        version,
+    })
+}
+
+fn status(state: &Arc<Mutex<ClaudeCli>>) -> Result<ClaudeStatus, AppError> {
+    let configured = state.lock().map_err(|e| e.to_string())?.settings.binary_path.clone();
+    let mut status = ClaudeStatus {
+        available: false,
+        path: None,
+        source: None,
+        version: None,
+        min_version: MIN_VERSION.to_string(),
+        supported: false,
+        auth: auth_status(),
+        problem: None,
+    };
+
+    let Some(binary) = find_binary(state)? else {
+        status.problem = Some(match configured {
+            Some(path) => format!("No Claude CLI at the configured path: {}", path),
+            None => "Claude CLI not found. Install it with `npm install -g @anthropic-ai/claude-code` \
+                     or set its path in settings."
+                .to_string(),
+        });
+        return Ok(status);
+    };
+
+    status.path = Some(binary.path.to_string_lossy().to_string());
+    status.source = Some(binary.source);
+    status.version = binary.version.map(|v| v.to_string());
+    match binary.version {
+        None => {
+            status.problem = Some(format!("Failed to run {} --version", binary.path.display()));
+        }
+        Some(version) if version < MIN_VERSION => {
+            status.problem = Some(format!(
+                "Claude CLI {} is too old, {} or newer is needed. Update it with `claude update`.",
+                version, MIN_VERSION
+            ));
+        }
+        Some(_) => {
+            status.supported = true;
+            status.available = true;
+            if status.auth == AuthStatus::NotLoggedIn {
+                status.problem = Some("Not logged in. Run `claude` in a terminal and log in.".to_string());
+            }
+        }
+    }
+    Ok(status)
+}
+
+// Command for the Claude CLI binary, refusing versions too old for our sessions
+pub fn command(state: &Arc<Mutex<ClaudeCli>>) -> Result<Command, AppError> {
+    let binary = find_binary(state)?.ok_or(AppError::ClaudeNotInstalled)?;
+
+    if let Some(version) = binary.version.filter(|v| *v < MIN_VERSION) {
+        return Err(AppError::ClaudeUnsupported {
+            version: version.to_string(),
+        });
+    }
+
+    let mut cmd = Command::new(&binary.path);
+    if let Some(search_path) = &binary.search_path {
+        cmd.env("PATH", search_path);
+    }
+    Ok(cmd)
+}
+
+// Whether a chat session can be started, using the binary found earlier
+pub fn is_available(state: &Arc<Mutex<ClaudeCli>>) -> Result<bool, AppError> {
+    Ok(status(state)?.available)
+}
+
+// Search for the Claude CLI again and report where it is, its version and
+// whether it is logged in
+#[tauri::command]
+pub async fn get_claude_status(state: State<'_, ClaudeCliState>) -> Result<ClaudeStatus, AppError> {
+    state.0.lock().map_err(|e| e.to_string())?.refresh();
+    status(&state.0)
+}
+
+// Use this binary instead of searching for one; None goes back to searching
+#[tauri::command]
+pub async fn set_claude_path(
+    path: Option<String>,
+    state: State<'_, ClaudeCliState>,
+) -> Result<ClaudeStatus, AppError> {
+    let path = path.filter(|p| !p.trim().is_empty());
+    if let Some(path) = &path {
+        let binary = Path::new(path);
+        if !binary.exists() {
+            return Err(AppError::NotFound { path: path.clone() });
+        }
+        if !binary.is_file() {
+            return Err(AppError::NotAFile { path: path.clone() });
+        }
+    }
+
+    {
+        let mut cli = state.0.lock().map_err(|e| e.to_string())?;
+        cli.settings.binary_path = path;
+        cli.save_settings()?;
+        cli.refresh();
+    }
+    status(&state.0)
+}
+
+fn executable_names() -> &'static [&'static str] {
+    if cfg!(windows) {
+        &["claude.exe", "claude.cmd"]
+    } else {
+        &["claude"]
+    }
+}
+
+fn find_in(search_path: &OsStr) -> Option<PathBuf> {
+    env::split_paths(search_path)
+        .flat_map(|dir| executable_names().iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+// Install locations of the native installer, npm, bun, volta, nvm and Homebrew
+fn known_locations() -> Vec<PathBuf> {
+    let home = home_dir();
+    let mut dirs: Vec<PathBuf> = Vec::new();
+
+    if let Some(home) = &home {
+        dirs.push(home.join(".claude").join("local"));
+        dirs.push(home.join(".local").join("bin"));
+        dirs.push(home.join(".npm-global").join("bin"));
+        dirs.push(home.join(".bun").join("bin"));
+        dirs.push(home.join(".volta").join("bin"));
+
+        // Newest node version first
+        if let Ok(entries) = fs::read_dir(home.join(".nvm").join("versions").join("node")) {
+            let mut versions: Vec<(Option<Version>, PathBuf)> = entries
+                .flatten()
+                .map(|e| (Version::parse(&e.file_name().to_string_lossy()), e.path().join("bin")))
+                .collect();
+            versions.sort_by_key(|(version, _)| std::cmp::Reverse(*version));
+            dirs.extend(versions.into_iter().map(|(_, dir)| dir));
+        }
+    }
+
+    if cfg!(windows) {
+        if let Some(app_data) = env::var_os("APPDATA") {
+            dirs.push(PathBuf::from(app_data).join("npm"));
+        }
+    } else {
+        dirs.push(PathBuf::from("/opt/homebrew/bin"));
+        dirs.push(PathBuf::from("/usr/local/bin"));
+        dirs.push(PathBuf::from("/usr/bin"));
+    }
+
+    dirs.iter()
+        .flat_map(|dir| executable_names().iter().map(move |name| dir.join(name)))
+        .collect()
+}
+
+// PATH as the user's terminal sees it. Apps started from the dock or a
+// desktop launcher do not read shell rc files, so theirs is often minimal.
+fn login_shell_path() -> Option<OsString> {
+    if cfg!(windows) {
+        return None;
+    }
+    let shell = env::var_os("SHELL").unwrap_or_else(|| OsString::from("/bin/sh"));
+
+    // `env` prints PATH colon-separated in every shell, fish included
+    let mut cmd = Command::new(shell);
+    cmd.args(["-i", "-l", "-c", "/usr/bin/env"]);
+    let output = run_with_timeout(cmd, SHELL_TIMEOUT)?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.strip_prefix("PATH="))
+        .next_back()
+        .map(OsString::from)
+}
+
+// The binary's own directory, then the login shell's PATH, then ours
+fn search_path(binary: &Path, login_path: Option<&OsStr>) -> Option<OsString> {
+    let mut dirs: Vec<PathBuf> = binary.parent().map(Path::to_path_buf).into_iter().collect();
+    if let Some(login_path) = login_path {
+        dirs.extend(env::split_paths(login_path));
+    }
+    if let Some(path) = env::var_os("PATH") {
+        dirs.extend(env::split_paths(&path));
+    }
+    env::join_paths(dirs).ok()
+}
+
+// Run to completion and collect its output, killing the process after
+// `timeout`. The pipes are read while waiting, so a process writing more
+// than a pipe buffer holds does not block until it is killed.
+fn run_with_timeout(mut cmd: Command, timeout: Duration) -> Option<Output> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(20)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    };
+
+    // Something the process started can keep a pipe open after it exits
+    let collect = |pipe: Receiver<Vec<u8>>| {
+        pipe.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            .unwrap_or_default()
+    };
+    Some(Output {
+        status,
+        stdout: collect(stdout),
+        stderr: collect(stderr),
+    })
+}
+
+// Read a pipe to its end on its own thread
+fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> Receiver<Vec<u8>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        let _ = tx.send(buffer);
+    });
+    rx
+}
+
+fn auth_status() -> AuthStatus {
+    let env_set = |name: &str| env::var_os(name).is_some_and(|v| !v.is_empty());
+
+    if env_set("CLAUDE_CODE_USE_BEDROCK") || env_set("CLAUDE_CODE_USE_VERTEX") {
+        return AuthStatus::CloudProvider;
+    }
+    if env_set("ANTHROPIC_API_KEY") || env_set("ANTHROPIC_AUTH_TOKEN") {
+        return AuthStatus::ApiKey;
+    }
+    if env_set("CLAUDE_CODE_OAUTH_TOKEN") {
+        return AuthStatus::Subscription;
+    }
+
+    let config_dir = env::var_os("CLAUDE_CONFIG_DIR").map(PathBuf::from);
+    let Some(claude_dir) = config_dir.clone().or_else(|| home_dir().map(|home| home.join(".claude"))) else {
+        return AuthStatus::NotLoggedIn;
+    };
+
+    // OAuth credentials are a file, or a keychain item on macOS
+    if claude_dir.join(".credentials.json").is_file() {
+        return AuthStatus::Subscription;
+    }
+    if cfg!(target_os = "macos") {
+        let mut cmd = Command::new("security");
+        cmd.args(["find-generic-password", "-s", "Claude Code-credentials"]);
+        if run_with_timeout(cmd, SHELL_TIMEOUT).is_some_and(|output| output.status.success()) {
+            return AuthStatus::Subscription;
+        }
+    }
+
+    // Global config, which records a console API key or the logged in account
+    let config_file = match config_dir {
+        Some(dir) => dir.join(".claude.json"),
+        None => match home_dir() {
+            Some(home) => home.join(".claude.json"),
+            None => return AuthStatus::NotLoggedIn,
+        },
+    };
+    let config = fs::read_to_string(config_file)
+        .ok()
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok());
+    match config {
+        Some(config) if config.get("primaryApiKey").is_some_and(|v| !v.is_null()) => AuthStatus::ApiKey,
+        Some(config) if config.get("oauthAccount").is_some_and(|v| !v.is_null()) => AuthStatus::Subscription,
+        _ => AuthStatus::NotLoggedIn,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_version_output() {
+        assert_eq!(Version::parse("1.0.72 (Claude Code)"), Some(Version(1, 0, 72)));
+        assert_eq!(Version::parse("2.1.0-beta"), Some(Version(2, 1, 0)));
+        assert_eq!(Version::parse("claude v1.2"), Some(Version(1, 2, 0)));
+        assert_eq!(Version::parse("command not found: claude"), None);
+        assert_eq!(Version::parse(""), None);
+        assert_eq!(Version::parse("version .. 3."), None);
+    }
+
+    #[test]
+    fn compares_against_the_minimum() {
+        assert!(Version(0, 2, 125) < MIN_VERSION);
+        assert!(Version(1, 0, 0) >= MIN_VERSION);
+        assert!(Version(1, 0, 72) < Version(1, 1, 0));
+        assert!(Version(1, 10, 0) > Version(1, 9, 99));
+        assert_eq!(Version(1, 0, 72).to_string(), "1.0.72");
+    }
+}
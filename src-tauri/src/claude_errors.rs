@@ -0,0 +1,206 @@
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde::Serialize;
+
+// Failures of the Claude CLI the user can do something about
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    NotLoggedIn,
+    RateLimited,
+    Overloaded,
+    ContextTooLong,
+    Network,
+    InvalidFlag,
+}
+
+impl ErrorKind {
+    // Whether running Claude again can succeed without the user acting first
+    pub fn retryable(self) -> bool {
+        matches!(self, ErrorKind::Overloaded | ErrorKind::Network)
+    }
+}
+
+// Sent flattened into error events as error_kind, hint and reset_at
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorInfo {
+    pub error_kind: ErrorKind,
+    pub hint: String,
+    // When a rate limit lifts, RFC 3339 if known exactly, else as Claude said it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reset_at: Option<String>,
+}
+
+// Patterns are matched against the lowercased message, first match wins
+const PATTERNS: &[(ErrorKind, &[&str])] = &[
+    (
+        ErrorKind::NotLoggedIn,
+        &[
+            "invalid api key",
+            "please run /login",
+            "not logged in",
+            "authentication_error",
+            "oauth token has expired",
+            "api error: 401",
+        ],
+    ),
+    (
+        ErrorKind::RateLimited,
+        &["limit reached", "rate limit", "rate_limit_error", "api error: 429"],
+    ),
+    (
+        ErrorKind::Overloaded,
+        &["overloaded", "api error: 529"],
+    ),
+    (
+        ErrorKind::ContextTooLong,
+        &[
+            "prompt is too long",
+            "context length",
+            "context window",
+            "too many tokens",
+            "exceed context limit",
+        ],
+    ),
+    (
+        ErrorKind::Network,
+        &[
+            "econnrefused",
+            "econnreset",
+            "enotfound",
+            "etimedout",
+            "eai_again",
+            "fetch failed",
+            "network error",
+            "connection error",
+            "unable to connect",
+        ],
+    ),
+    (
+        ErrorKind::InvalidFlag,
+        &["unknown option", "unknown command", "unexpected argument", "is invalid. allowed choices", "requires --verbose"],
+    ),
+];
+
+// Recognise a failure in stderr output or a result message
+pub fn classify(message: &str) -> Option<ErrorInfo> {
+    let lower = message.to_ascii_lowercase();
+    let error_kind = PATTERNS
+        .iter()
+        .find(|(_, patterns)| patterns.iter().any(|p| lower.contains(p)))
+        .map(|(kind, _)| *kind)?;
+
+    let reset_at = match error_kind {
+        ErrorKind::RateLimited => reset_time(message),
+        _ => None,
+    };
+    let hint = match error_kind {
+        ErrorKind::NotLoggedIn => {
+            "Claude is not logged in. Run `claude` in a terminal and log in, or set ANTHROPIC_API_KEY.".to_string()
+        }
+        ErrorKind::RateLimited => match &reset_at {
+            Some(reset_at) => format!("Rate limit reached. Try again after {}.", reset_at),
+            None => "Rate limited. Wait a minute and try again.".to_string(),
+        },
+        ErrorKind::Overloaded => "Anthropic's servers are overloaded. Try again in a few moments.".to_string(),
+        ErrorKind::ContextTooLong => {
+            "The conversation is too long for the model. Start a new chat or attach less context.".to_string()
+        }
+        ErrorKind::Network => {
+            "Could not reach Anthropic. Check your internet connection and proxy settings.".to_string()
+        }
+        ErrorKind::InvalidFlag => {
+            "The Claude CLI rejected an option it was started with. Update it with `claude update`.".to_string()
+        }
+    };
+
+    Some(ErrorInfo {
+        error_kind,
+        hint,
+        reset_at,
+    })
+}
+
+// "Claude AI usage limit reached|1760000000", "... resets 5pm (Europe/Paris)"
+// or "retry after 30 seconds"
+fn reset_time(message: &str) -> Option<String> {
+    if let Some((_, rest)) = message.split_once("limit reached|") {
+        let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+        if let Some(time) = digits.parse().ok().and_then(|secs| DateTime::<Utc>::from_timestamp(secs, 0)) {
+            return Some(time.to_rfc3339_opts(SecondsFormat::Secs, true));
+        }
+    }
+
+    // ASCII lowercasing keeps byte offsets valid in `message`
+    let lower = message.to_ascii_lowercase();
+    for marker in ["resets at ", "reset at ", "resets "] {
+        if let Some(start) = lower.find(marker) {
+            let rest = &message[start + marker.len()..];
+            let end = rest.find(['\n', '|']).unwrap_or(rest.len());
+            let when = rest[..end].trim().trim_end_matches('.');
+            if !when.is_empty() {
+                return Some(when.to_string());
+            }
+        }
+    }
+
+    let start = lower.find("retry after ").or_else(|| lower.find("retry-after: "))?;
+    let rest = &lower[start..];
+    let digits: String = rest
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(char::is_ascii_digit)
+        .collect();
+    let seconds: i64 = digits.parse().ok()?;
+    Some((Utc::now() + Duration::seconds(seconds)).to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(message: &str) -> Option<ErrorKind> {
+        classify(message).map(|info| info.error_kind)
+    }
+
+    #[test]
+    fn recognises_each_kind() {
+        assert_eq!(kind("Invalid API key · Please run /login"), Some(ErrorKind::NotLoggedIn));
+        assert_eq!(kind("API Error: 401 {\"type\":\"error\"}"), Some(ErrorKind::NotLoggedIn));
+        assert_eq!(kind("API Error: 429 rate_limit_error"), Some(ErrorKind::RateLimited));
+        assert_eq!(kind("API Error: 529 {\"type\":\"overloaded_error\"}"), Some(ErrorKind::Overloaded));
+        assert_eq!(kind("Prompt is too long"), Some(ErrorKind::ContextTooLong));
+        assert_eq!(kind("Error: connect ECONNREFUSED 127.0.0.1:443"), Some(ErrorKind::Network));
+        assert_eq!(kind("error: unknown option '--foo'"), Some(ErrorKind::InvalidFlag));
+    }
+
+    #[test]
+    fn unknown_failures_are_not_classified() {
+        assert!(classify("Something else went wrong").is_none());
+        assert!(classify("").is_none());
+    }
+
+    #[test]
+    fn only_transient_failures_are_retryable() {
+        assert!(ErrorKind::Overloaded.retryable());
+        assert!(ErrorKind::Network.retryable());
+        assert!(!ErrorKind::RateLimited.retryable());
+        assert!(!ErrorKind::NotLoggedIn.retryable());
+    }
+
+    #[test]
+    fn reads_the_rate_limit_reset_time() {
+        let info = classify("Claude AI usage limit reached|1760000000").unwrap();
+        assert_eq!(info.error_kind, ErrorKind::RateLimited);
+        assert_eq!(info.reset_at.as_deref(), Some("2025-10-09T08:53:20Z"));
+        assert!(info.hint.contains("2025-10-09T08:53:20Z"));
+
+        let info = classify("5-hour limit reached ∙ resets 5pm (Europe/Paris)").unwrap();
+        assert_eq!(info.reset_at.as_deref(), Some("5pm (Europe/Paris)"));
+
+        let info = classify("Rate limit exceeded, retry after 30 seconds").unwrap();
+        assert!(info.reset_at.is_some());
+
+        let info = classify("Rate limit exceeded").unwrap();
+        assert!(info.reset_at.is_none());
+    }
+}
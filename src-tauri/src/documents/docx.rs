@@ -0,0 +1,792 @@
+use roxmltree::Node;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::{markdown_table, parse_xml, resolve_target, word_count, PackageReader};
+
+pub const W: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+const R: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DocxContent {
+    pub markdown: String,
+    pub paragraphs: Vec<DocxParagraph>,
+    pub comments: Vec<DocxComment>,
+    pub tracked_changes: Vec<TrackedChange>,
+    pub word_count: usize,
+}
+
+// Paragraphs are numbered from 1 in reading order, counting those inside
+// table cells and empty ones too
+#[derive(Debug, Clone, Serialize)]
+pub struct DocxParagraph {
+    pub index: usize,
+    pub style: Option<String>,
+    pub heading_level: Option<u8>,
+    // Current text, without deleted revisions
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DocxComment {
+    pub id: String,
+    pub author: String,
+    pub date: Option<String>,
+    pub text: String,
+    // The commented text
+    pub anchor: String,
+    pub paragraph: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Insertion,
+    Deletion,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackedChange {
+    pub id: String,
+    pub kind: ChangeKind,
+    pub author: String,
+    pub date: Option<String>,
+    pub text: String,
+    pub paragraph: usize,
+}
+
+// Extract the body of a .docx as markdown. Tracked changes are flagged
+// with CriticMarkup ({++inserted++}, {--deleted--}) and comments appear
+// inline as {>>author: text<<}.
+pub fn read_docx(path: &Path) -> Result<DocxContent, String> {
+    let mut package = PackageReader::open(path)?;
+    let xml = package
+        .part("word/document.xml")?
+        .ok_or_else(|| "Not a Word document: word/document.xml is missing".to_string())?;
+    let links = package.relationships("word/document.xml")?;
+    let styles = match package.part("word/styles.xml")? {
+        Some(xml) => Styles::parse(&parse_xml(&xml, "word/styles.xml")?),
+        None => Styles::default(),
+    };
+    let numbering = match package.part("word/numbering.xml")? {
+        Some(xml) => list_formats(&parse_xml(&xml, "word/numbering.xml")?),
+        None => HashMap::new(),
+    };
+    let comments = match package.part("word/comments.xml")? {
+        Some(xml) => comment_bodies(&parse_xml(&xml, "word/comments.xml")?),
+        None => Vec::new(),
+    };
+
+    let doc = parse_xml(&xml, "word/document.xml")?;
+    let body = doc
+        .root_element()
+        .children()
+        .find(|n| is(*n, "body"))
+        .ok_or_else(|| "Failed to parse word/document.xml: no document body".to_string())?;
+
+    let mut reader = Reader {
+        styles,
+        numbering,
+        links,
+        comments: &comments,
+        counters: HashMap::new(),
+        open_comments: Vec::new(),
+        anchors: HashMap::new(),
+        comment_paragraphs: HashMap::new(),
+        paragraph: 0,
+        last_was_list: false,
+        paragraphs: Vec::new(),
+        tracked_changes: Vec::new(),
+    };
+    let mut markdown = String::new();
+    reader.blocks(body, &mut markdown);
+    if !markdown.is_empty() {
+        markdown.push('\n');
+    }
+
+    // Comments in the order they appear in the text; unanchored ones last
+    let mut ordered: Vec<&CommentBody> = comments.iter().collect();
+    ordered.sort_by_key(|c| reader.comment_paragraphs.get(&c.id).copied().unwrap_or(usize::MAX));
+    let comments = ordered
+        .into_iter()
+        .map(|c| DocxComment {
+            id: c.id.clone(),
+            author: c.author.clone(),
+            date: c.date.clone(),
+            text: c.text.clone(),
+            anchor: reader.anchors.get(&c.id).cloned().unwrap_or_default(),
+            paragraph: reader.comment_paragraphs.get(&c.id).copied(),
+        })
+        .collect();
+
+    let word_count = reader.paragraphs.iter().map(|p| word_count(&p.text)).sum();
+    Ok(DocxContent {
+        markdown,
+        paragraphs: reader.paragraphs,
+        comments,
+        tracked_changes: reader.tracked_changes,
+        word_count,
+    })
+}
+
+pub fn is(node: Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name && node.tag_name().namespace() == Some(W)
+}
+
+pub fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| is(*n, name))
+}
+
+pub fn val<'a>(node: Node<'a, '_>) -> Option<&'a str> {
+    node.attribute((W, "val"))
+}
+
+// Paragraphs in reading order, descending into tables and content controls
+pub fn paragraphs<'a, 'input>(container: Node<'a, 'input>) -> Vec<Node<'a, 'input>> {
+    let mut out = Vec::new();
+    for node in container.children() {
+        if is(node, "p") {
+            out.push(node);
+        } else if ["tbl", "tr", "tc", "sdt", "sdtContent", "customXml"].iter().any(|name| is(node, name)) {
+            out.extend(paragraphs(node));
+        }
+    }
+    out
+}
+
+// On/off run properties such as <w:b/> are on unless w:val says otherwise
+fn toggle(properties: Option<Node>, name: &str) -> bool {
+    properties
+        .and_then(|p| child(p, name))
+        .is_some_and(|n| !matches!(val(n), Some("0" | "false" | "off")))
+}
+
+#[derive(Default)]
+pub struct Styles {
+    headings: HashMap<String, u8>,
+    // Lowercase style name -> style id
+    ids: HashMap<String, String>,
+    // Style id -> numbering (numId, level) for styles like "List Bullet"
+    numbering: HashMap<String, (String, u8)>,
+}
+
+impl Styles {
+    pub fn parse(doc: &roxmltree::Document) -> Self {
+        struct Style {
+            name: String,
+            outline: Option<u8>,
+            based_on: Option<String>,
+            numbering: Option<(String, u8)>,
+        }
+
+        let mut styles = HashMap::new();
+        for node in doc.descendants().filter(|n| is(*n, "style")) {
+            if node.attribute((W, "type")) != Some("paragraph") {
+                continue;
+            }
+            let Some(id) = node.attribute((W, "styleId")) else {
+                continue;
+            };
+            let properties = child(node, "pPr");
+            styles.insert(
+                id.to_string(),
+                Style {
+                    name: child(node, "name").and_then(val).unwrap_or(id).to_lowercase(),
+                    outline: properties
+                        .and_then(|p| child(p, "outlineLvl"))
+                        .and_then(val)
+                        .and_then(|v| v.parse().ok()),
+                    based_on: child(node, "basedOn").and_then(val).map(str::to_string),
+                    numbering: properties.and_then(numbering_properties),
+                },
+            );
+        }
+
+        let heading_level = |id: &str| -> Option<u8> {
+            let mut current = styles.get(id);
+            // basedOn chains are short; the cap guards against cycles
+            for _ in 0..10 {
+                let style = current?;
+                if style.name == "title" {
+                    return Some(1);
+                }
+                if let Some(level) = style.name.strip_prefix("heading ").and_then(|l| l.parse().ok()) {
+                    return Some(level);
+                }
+                if let Some(outline) = style.outline.filter(|&o| o < 9) {
+                    return Some(outline + 1);
+                }
+                current = styles.get(style.based_on.as_deref()?);
+            }
+            None
+        };
+
+        Self {
+            headings: styles
+                .keys()
+                .filter_map(|id| Some((id.clone(), heading_level(id)?)))
+                .collect(),
+            ids: styles.iter().map(|(id, style)| (style.name.clone(), id.clone())).collect(),
+            numbering: styles
+                .iter()
+                .filter_map(|(id, style)| Some((id.clone(), style.numbering.clone()?)))
+                .collect(),
+        }
+    }
+
+    // Heading level from the paragraph's outline level or its style
+    pub fn heading_level(&self, paragraph: Node) -> Option<u8> {
+        let properties = child(paragraph, "pPr");
+        let outline = properties
+            .and_then(|p| child(p, "outlineLvl"))
+            .and_then(val)
+            .and_then(|v| v.parse::<u8>().ok())
+            .filter(|&o| o < 9);
+        outline.map(|o| o + 1).or_else(|| {
+            let style = properties.and_then(|p| child(p, "pStyle")).and_then(val)?;
+            self.headings.get(style).copied()
+        })
+    }
+
+    // Style id for a style id or display name, e.g. "heading 1" -> "Heading1"
+    pub fn id(&self, name: &str) -> Option<&str> {
+        self.ids
+            .values()
+            .find(|id| *id == name)
+            .or_else(|| self.ids.get(&name.to_lowercase()))
+            .map(String::as_str)
+    }
+}
+
+// (numId, level) from <w:numPr>. numId 0 switches numbering off.
+fn numbering_properties(properties: Node) -> Option<(String, u8)> {
+    let numbering = child(properties, "numPr")?;
+    let id = child(numbering, "numId").and_then(val)?;
+    if id == "0" {
+        return None;
+    }
+    let level = child(numbering, "ilvl").and_then(val).and_then(|v| v.parse().ok()).unwrap_or(0);
+    Some((id.to_string(), level))
+}
+
+// numId -> level -> whether the list is numbered (as opposed to bulleted)
+fn list_formats(doc: &roxmltree::Document) -> HashMap<String, HashMap<u8, bool>> {
+    let mut abstract_formats: HashMap<&str, HashMap<u8, bool>> = HashMap::new();
+    for node in doc.descendants().filter(|n| is(*n, "abstractNum")) {
+        let Some(id) = node.attribute((W, "abstractNumId")) else {
+            continue;
+        };
+        let levels = node
+            .children()
+            .filter(|n| is(*n, "lvl"))
+            .filter_map(|level| {
+                let index = level.attribute((W, "ilvl"))?.parse().ok()?;
+                let format = child(level, "numFmt").and_then(val).unwrap_or("bullet");
+                Some((index, !matches!(format, "bullet" | "none")))
+            })
+            .collect();
+        abstract_formats.insert(id, levels);
+    }
+
+    doc.descendants()
+        .filter(|n| is(*n, "num"))
+        .filter_map(|num| {
+            let id = num.attribute((W, "numId"))?;
+            let abstract_id = child(num, "abstractNumId").and_then(val)?;
+            Some((id.to_string(), abstract_formats.get(abstract_id)?.clone()))
+        })
+        .collect()
+}
+
+struct CommentBody {
+    id: String,
+    author: String,
+    date: Option<String>,
+    text: String,
+}
+
+fn comment_bodies(doc: &roxmltree::Document) -> Vec<CommentBody> {
+    doc.descendants()
+        .filter(|n| is(*n, "comment"))
+        .filter_map(|comment| {
+            let text = paragraphs(comment)
+                .into_iter()
+                .map(|p| {
+                    p.descendants()
+                        .filter(|n| is(*n, "t"))
+                        .filter_map(|n| n.text())
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            Some(CommentBody {
+                id: comment.attribute((W, "id"))?.to_string(),
+                author: comment.attribute((W, "author")).unwrap_or("Unknown").to_string(),
+                date: comment.attribute((W, "date")).map(str::to_string),
+                text,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Format {
+    bold: bool,
+    italic: bool,
+    strike: bool,
+    link: Option<String>,
+    change: Option<ChangeKind>,
+}
+
+struct Run {
+    text: String,
+    format: Format,
+    // Markdown emitted as is (images, comments)
+    raw: bool,
+}
+
+enum ParagraphKind {
+    Plain,
+    Heading(u8),
+    ListItem { level: u8, number: Option<u64> },
+}
+
+struct Reader<'c> {
+    styles: Styles,
+    numbering: HashMap<String, HashMap<u8, bool>>,
+    links: HashMap<String, String>,
+    comments: &'c [CommentBody],
+    counters: HashMap<(String, u8), u64>,
+    open_comments: Vec<String>,
+    anchors: HashMap<String, String>,
+    comment_paragraphs: HashMap<String, usize>,
+    paragraph: usize,
+    last_was_list: bool,
+    paragraphs: Vec<DocxParagraph>,
+    tracked_changes: Vec<TrackedChange>,
+}
+
+impl Reader<'_> {
+    fn blocks(&mut self, container: Node, out: &mut String) {
+        for node in container.children() {
+            if is(node, "p") {
+                let (kind, text) = self.paragraph(node);
+                if text.trim().is_empty() {
+                    continue;
+                }
+                let is_list = matches!(kind, ParagraphKind::ListItem { .. });
+                let block = match kind {
+                    ParagraphKind::Plain => text,
+                    ParagraphKind::Heading(level) => {
+                        format!("{} {}", "#".repeat(level.min(6) as usize), text.replace('\n', " "))
+                    }
+                    ParagraphKind::ListItem { level, number } => {
+                        let marker = match number {
+                            Some(n) => format!("{}. ", n),
+                            None => "- ".to_string(),
+                        };
+                        let indent = "  ".repeat(level as usize);
+                        let continuation = format!("\n{}{}", indent, " ".repeat(marker.len()));
+                        format!("{}{}{}", indent, marker, text.replace('\n', &continuation))
+                    }
+                };
+                self.push_block(out, &block, is_list);
+            } else if is(node, "tbl") {
+                let table = self.table(node);
+                if !table.is_empty() {
+                    self.push_block(out, &table, false);
+                }
+            } else if is(node, "sdt") {
+                if let Some(content) = child(node, "sdtContent") {
+                    self.blocks(content, out);
+                }
+            } else if is(node, "customXml") {
+                self.blocks(node, out);
+            }
+        }
+    }
+
+    // Blocks are separated by blank lines, except consecutive list items
+    fn push_block(&mut self, out: &mut String, block: &str, is_list: bool) {
+        if !out.is_empty() {
+            out.push_str(if is_list && self.last_was_list { "\n" } else { "\n\n" });
+        }
+        out.push_str(block);
+        self.last_was_list = is_list;
+    }
+
+    fn paragraph(&mut self, paragraph: Node) -> (ParagraphKind, String) {
+        self.paragraph += 1;
+        let properties = child(paragraph, "pPr");
+        let style = properties.and_then(|p| child(p, "pStyle")).and_then(val);
+
+        let heading_level = self.styles.heading_level(paragraph);
+        let list = properties
+            .and_then(numbering_properties)
+            .or_else(|| style.and_then(|s| self.styles.numbering.get(s).cloned()));
+
+        let mut runs = Vec::new();
+        self.inline(paragraph, &Format::default(), &mut runs);
+        let text: String = runs
+            .iter()
+            .filter(|r| !r.raw && r.format.change != Some(ChangeKind::Deletion))
+            .map(|r| r.text.as_str())
+            .collect();
+        self.paragraphs.push(DocxParagraph {
+            index: self.paragraph,
+            style: style.map(str::to_string),
+            heading_level,
+            text,
+        });
+
+        let kind = match (heading_level, list) {
+            (Some(level), _) => ParagraphKind::Heading(level),
+            (None, Some((id, level))) => {
+                let ordered = self
+                    .numbering
+                    .get(&id)
+                    .and_then(|levels| levels.get(&level))
+                    .copied()
+                    .unwrap_or(false);
+                // A shallower item restarts the numbering of deeper levels
+                self.counters.retain(|(list, l), _| list != &id || *l <= level);
+                let counter = self.counters.entry((id, level)).or_insert(0);
+                *counter += 1;
+                ParagraphKind::ListItem {
+                    level,
+                    number: ordered.then_some(*counter),
+                }
+            }
+            (None, None) => ParagraphKind::Plain,
+        };
+        (kind, render(&runs))
+    }
+
+    fn inline(&mut self, node: Node, format: &Format, runs: &mut Vec<Run>) {
+        for item in node.children().filter(|n| n.tag_name().namespace() == Some(W)) {
+            match item.tag_name().name() {
+                "r" => self.run(item, format, runs),
+                "hyperlink" => {
+                    let link = match (item.attribute((R, "id")), item.attribute((W, "anchor"))) {
+                        (Some(id), _) => self.links.get(id).cloned(),
+                        (None, Some(anchor)) => Some(format!("#{}", anchor)),
+                        (None, None) => None,
+                    };
+                    let format = Format {
+                        link: link.or_else(|| format.link.clone()),
+                        ..format.clone()
+                    };
+                    self.inline(item, &format, runs);
+                }
+                name @ ("ins" | "moveTo" | "del" | "moveFrom") => {
+                    let kind = if matches!(name, "ins" | "moveTo") {
+                        ChangeKind::Insertion
+                    } else {
+                        ChangeKind::Deletion
+                    };
+                    let text: String = item
+                        .descendants()
+                        .filter(|n| is(*n, "t") || is(*n, "delText"))
+                        .filter_map(|n| n.text())
+                        .collect();
+                    self.tracked_changes.push(TrackedChange {
+                        id: item.attribute((W, "id")).unwrap_or_default().to_string(),
+                        kind,
+                        author: item.attribute((W, "author")).unwrap_or("Unknown").to_string(),
+                        date: item.attribute((W, "date")).map(str::to_string),
+                        text,
+                        paragraph: self.paragraph,
+                    });
+                    let format = Format {
+                        change: Some(kind),
+                        ..format.clone()
+                    };
+                    self.inline(item, &format, runs);
+                }
+                "commentRangeStart" => {
+                    if let Some(id) = item.attribute((W, "id")) {
+                        self.open_comments.push(id.to_string());
+                        self.comment_paragraphs.entry(id.to_string()).or_insert(self.paragraph);
+                    }
+                }
+                "commentRangeEnd" => {
+                    if let Some(id) = item.attribute((W, "id")) {
+                        self.open_comments.retain(|open| open != id);
+                    }
+                }
+                "smartTag" | "sdt" | "sdtContent" | "customXml" | "fldSimple" | "bdo" | "dir" => {
+                    self.inline(item, format, runs)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn run(&mut self, run: Node, format: &Format, runs: &mut Vec<Run>) {
+        let properties = child(run, "rPr");
+        let format = Format {
+            bold: format.bold || toggle(properties, "b"),
+            italic: format.italic || toggle(properties, "i"),
+            strike: format.strike || toggle(properties, "strike") || toggle(properties, "dstrike"),
+            ..format.clone()
+        };
+
+        for part in run.children().filter(|n| n.tag_name().namespace() == Some(W)) {
+            let text = match part.tag_name().name() {
+                "t" | "delText" => part.text().unwrap_or_default(),
+                "tab" => "\t",
+                "br" | "cr" => "\n",
+                "noBreakHyphen" => "-",
+                "drawing" | "pict" => {
+                    runs.push(Run {
+                        text: self.image(part),
+                        format: format.clone(),
+                        raw: true,
+                    });
+                    continue;
+                }
+                "commentReference" => {
+                    let Some(id) = part.attribute((W, "id")) else {
+                        continue;
+                    };
+                    self.comment_paragraphs.entry(id.to_string()).or_insert(self.paragraph);
+                    if let Some(comment) = self.comments.iter().find(|c| c.id == id) {
+                        runs.push(Run {
+                            text: format!("{{>>{}: {}<<}}", comment.author, comment.text.replace('\n', " ")),
+                            format: format.clone(),
+                            raw: true,
+                        });
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            for id in &self.open_comments {
+                self.anchors.entry(id.clone()).or_default().push_str(text);
+            }
+            runs.push(Run {
+                text: text.to_string(),
+                format: format.clone(),
+                raw: false,
+            });
+        }
+    }
+
+    // ![description](word/media/image1.png), with the path inside the package
+    fn image(&self, drawing: Node) -> String {
+        let description = drawing
+            .descendants()
+            .find(|n| n.tag_name().name() == "docPr")
+            .and_then(|n| n.attribute("descr").or(n.attribute("name")))
+            .unwrap_or("image");
+        let target = drawing
+            .descendants()
+            .find_map(|n| n.attribute((R, "embed")).or(n.attribute((R, "id"))))
+            .and_then(|id| self.links.get(id))
+            .map(|target| resolve_target("word/document.xml", target))
+            .unwrap_or_default();
+        format!("![{}]({})", description, target)
+    }
+
+    fn table(&mut self, table: Node) -> String {
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        for row in table.children().filter(|n| is(*n, "tr")) {
+            let mut cells = Vec::new();
+            for cell in row.children().filter(|n| is(*n, "tc")) {
+                let text = paragraphs(cell)
+                    .into_iter()
+                    .map(|p| self.paragraph(p).1)
+                    .filter(|t| !t.trim().is_empty())
+                    .collect::<Vec<_>>()
+                    .join("<br>");
+                cells.push(text);
+
+                // Merged cells keep the grid aligned
+                let span = child(cell, "tcPr")
+                    .and_then(|p| child(p, "gridSpan"))
+                    .and_then(val)
+                    .and_then(|v| v.parse::<usize>().ok())
+                    .unwrap_or(1);
+                cells.extend(std::iter::repeat_n(String::new(), span.saturating_sub(1)));
+            }
+            rows.push(cells);
+        }
+
+        markdown_table(&rows)
+    }
+}
+
+fn render(runs: &[Run]) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < runs.len() {
+        if runs[i].raw {
+            out.push_str(&runs[i].text);
+            i += 1;
+            continue;
+        }
+        // Merge neighbouring runs with the same formatting
+        let format = &runs[i].format;
+        let mut text = String::new();
+        while i < runs.len() && !runs[i].raw && runs[i].format == *format {
+            text.push_str(&runs[i].text);
+            i += 1;
+        }
+        out.push_str(&decorate(&text, format));
+    }
+    out
+}
+
+// Markdown emphasis can't start or end with whitespace, so it stays outside
+fn decorate(text: &str, format: &Format) -> String {
+    let core = text.trim();
+    if core.is_empty() {
+        return text.to_string();
+    }
+    let lead = &text[..text.len() - text.trim_start().len()];
+    let trail = &text[text.trim_end().len()..];
+
+    let mut out = core.to_string();
+    if format.strike {
+        out = format!("~~{}~~", out);
+    }
+    out = match (format.bold, format.italic) {
+        (true, true) => format!("***{}***", out),
+        (true, false) => format!("**{}**", out),
+        (false, true) => format!("*{}*", out),
+        (false, false) => out,
+    };
+    if let Some(link) = &format.link {
+        out = format!("[{}]({})", out, link);
+    }
+    out = match format.change {
+        Some(ChangeKind::Insertion) => format!("{{++{}++}}", out),
+        Some(ChangeKind::Deletion) => format!("{{--{}--}}", out),
+        None => out,
+    };
+    format!("{}{}{}", lead, out, trail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents::docx_edit::{edit_docx, DocxEdit};
+    use crate::export::docx::markdown_to_docx;
+    use crate::export::ooxml::Package;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("clause-read-{}-{}.docx", std::process::id(), name))
+    }
+
+    // A package holding just a document body and, optionally, comments
+    fn read_parts(name: &str, body: &str, comments: Option<&str>) -> DocxContent {
+        let mut package = Package::new();
+        let document = format!(r#"<w:document xmlns:w="{}"><w:body>{}</w:body></w:document>"#, W, body);
+        package.add("word/document.xml", document.as_bytes()).unwrap();
+        if let Some(comments) = comments {
+            let comments = format!(r#"<w:comments xmlns:w="{}">{}</w:comments>"#, W, comments);
+            package.add("word/comments.xml", comments.as_bytes()).unwrap();
+        }
+        let path = temp_path(name);
+        fs::write(&path, package.finish().unwrap()).unwrap();
+        let content = read_docx(&path);
+        fs::remove_file(&path).unwrap();
+        content.unwrap()
+    }
+
+    #[test]
+    fn tracked_changes_become_critic_markup() {
+        let path = temp_path("tracked");
+        fs::write(&path, markdown_to_docx("# Plan\n\nThe old plan stays.\n\nLast.\n", Path::new("."), "Plan").unwrap()).unwrap();
+        let edits = [
+            DocxEdit::ReplaceText {
+                paragraph: 2,
+                find: Some("old".to_string()),
+                text: "new".to_string(),
+            },
+            DocxEdit::InsertParagraph {
+                after: 2,
+                text: "Added.".to_string(),
+                style: None,
+            },
+        ];
+        edit_docx(&path, &edits, true).unwrap();
+        let content = read_docx(&path);
+        fs::remove_file(&path).unwrap();
+        let content = content.unwrap();
+
+        assert_eq!(
+            content.markdown,
+            "# Plan\n\nThe {--old--}{++new++} plan stays.\n\n{++Added.++}\n\nLast.\n"
+        );
+        let changes: Vec<(ChangeKind, &str, usize)> = content
+            .tracked_changes
+            .iter()
+            .map(|c| (c.kind, c.text.as_str(), c.paragraph))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (ChangeKind::Deletion, "old", 2),
+                (ChangeKind::Insertion, "new", 2),
+                (ChangeKind::Insertion, "Added.", 3),
+            ]
+        );
+        // Paragraph text is the current text, without deletions
+        assert_eq!(content.paragraphs[1].text, "The new plan stays.");
+        assert_eq!(content.word_count, 7);
+    }
+
+    #[test]
+    fn comments_are_anchored_to_their_text() {
+        let body = concat!(
+            r#"<w:p><w:r><w:t>Intro.</w:t></w:r></w:p>"#,
+            r#"<w:p><w:r><w:t xml:space="preserve">This is the </w:t></w:r><w:commentRangeStart w:id="7"/>"#,
+            r#"<w:r><w:rPr><w:b/></w:rPr><w:t>important</w:t></w:r><w:r><w:t xml:space="preserve"> part</w:t></w:r>"#,
+            r#"<w:commentRangeEnd w:id="7"/><w:r><w:commentReference w:id="7"/></w:r><w:r><w:t>.</w:t></w:r></w:p>"#,
+        );
+        let comments = concat!(
+            r#"<w:comment w:id="3" w:author="Bo"><w:p><w:r><w:t>Unanchored</w:t></w:r></w:p></w:comment>"#,
+            r#"<w:comment w:id="7" w:author="Ann" w:date="2026-01-02T03:04:05Z"><w:p><w:r><w:t>Check</w:t></w:r></w:p><w:p><w:r><w:t>this</w:t></w:r></w:p></w:comment>"#,
+        );
+        let content = read_parts("comments", body, Some(comments));
+
+        assert_eq!(
+            content.markdown,
+            "Intro.\n\nThis is the **important** part{>>Ann: Check this<<}.\n"
+        );
+        let anchored = &content.comments[0];
+        assert_eq!(anchored.id, "7");
+        assert_eq!(anchored.anchor, "important part");
+        assert_eq!(anchored.paragraph, Some(2));
+        assert_eq!(anchored.date.as_deref(), Some("2026-01-02T03:04:05Z"));
+        // Comments without an anchor come last
+        assert_eq!(content.comments[1].id, "3");
+        assert_eq!(content.comments[1].paragraph, None);
+    }
+
+    #[test]
+    fn merged_cells_keep_the_grid() {
+        let cell = |text: &str, span: usize| {
+            let properties = if span > 1 {
+                format!(r#"<w:tcPr><w:gridSpan w:val="{}"/></w:tcPr>"#, span)
+            } else {
+                String::new()
+            };
+            format!(r#"<w:tc>{}<w:p><w:r><w:t>{}</w:t></w:r></w:p></w:tc>"#, properties, text)
+        };
+        let body = format!(
+            "<w:tbl><w:tr>{}{}{}</w:tr><w:tr>{}{}</w:tr></w:tbl>",
+            cell("A", 1),
+            cell("B", 1),
+            cell("C", 1),
+            cell("Wide", 2),
+            cell("c", 1)
+        );
+        let content = read_parts("grid", &body, None);
+        assert_eq!(content.markdown, "| A | B | C |\n| --- | --- | --- |\n| Wide |  | c |\n");
+    }
+}
@@ -0,0 +1,833 @@
+use chrono::{SecondsFormat, Utc};
+use roxmltree::Node;
+use serde::Deserialize;
+use std::ops::Range;
+use std::path::Path;
+
+use super::docx::{child, is, paragraphs, Styles, W};
+use super::{parse_xml, PackageReader};
+use crate::export::ooxml::xml_escape;
+
+// Edits splice new markup into word/document.xml and leave everything else
+// byte for byte as it was. New markup uses the w: prefix, as Word does.
+
+// Author of revision marks when edits are tracked
+pub const REVISION_AUTHOR: &str = "Claude";
+
+// An editing operation on a .docx. Paragraphs are numbered from 1 as in
+// read_document.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum DocxEdit {
+    // Replace the first occurrence of `find` in the paragraph, or the whole
+    // paragraph text when `find` is not given. Tabs and line breaks are
+    // matched as "\t" and "\n", as read_document shows them.
+    ReplaceText {
+        paragraph: usize,
+        find: Option<String>,
+        text: String,
+    },
+    // Insert a paragraph after paragraph `after`; 0 inserts at the start
+    InsertParagraph {
+        after: usize,
+        text: String,
+        style: Option<String>,
+    },
+    // Level 0 turns a heading back into body text
+    SetHeading { paragraph: usize, level: u8 },
+    // Accept or reject the tracked change with the given id, or all of them
+    AcceptChanges { id: Option<String> },
+    RejectChanges { id: Option<String> },
+}
+
+// Apply the edits in order, each seeing the result of the previous one,
+// and write the file once all of them succeeded. With `track_changes` the
+// edits are written as revisions that can be accepted or rejected in Word.
+pub fn edit_docx(path: &Path, edits: &[DocxEdit], track_changes: bool) -> Result<(), String> {
+    let mut package = PackageReader::open(path)?;
+    let mut document = package
+        .part("word/document.xml")?
+        .ok_or_else(|| "Not a Word document: word/document.xml is missing".to_string())?;
+    let original_styles = package.part("word/styles.xml")?;
+    let mut styles = original_styles.clone();
+    let mut revision = track_changes.then(|| Revision::new(REVISION_AUTHOR));
+
+    for edit in edits {
+        document = apply(&document, &mut styles, edit, revision.as_mut())?;
+    }
+
+    let mut parts = vec![("word/document.xml", document.as_str())];
+    if styles != original_styles {
+        if let Some(styles) = styles.as_deref() {
+            parts.push(("word/styles.xml", styles));
+        }
+    }
+    package.rewrite(path, &parts)
+}
+
+struct Splice {
+    range: Range<usize>,
+    text: String,
+}
+
+// Author, date and ids for revision marks
+struct Revision {
+    author: String,
+    date: String,
+    next_id: u64,
+}
+
+impl Revision {
+    fn new(author: &str) -> Self {
+        Self {
+            author: author.to_string(),
+            date: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            next_id: 0,
+        }
+    }
+
+    // Ids must not clash with those of existing revisions and comments
+    fn skip_ids_in(&mut self, doc: &roxmltree::Document) {
+        let highest = doc
+            .descendants()
+            .filter_map(|n| n.attribute((W, "id"))?.parse::<u64>().ok())
+            .max();
+        if let Some(highest) = highest {
+            self.next_id = self.next_id.max(highest + 1);
+        }
+    }
+
+    // Attributes for a new revision mark
+    fn attributes(&mut self) -> String {
+        let id = self.next_id;
+        self.next_id += 1;
+        format!(r#"w:id="{}" w:author="{}" w:date="{}""#, id, xml_escape(&self.author), self.date)
+    }
+}
+
+fn apply(
+    xml: &str,
+    styles_xml: &mut Option<String>,
+    edit: &DocxEdit,
+    mut revision: Option<&mut Revision>,
+) -> Result<String, String> {
+    let styles = match styles_xml.as_deref() {
+        Some(styles) => Styles::parse(&parse_xml(styles, "word/styles.xml")?),
+        None => Styles::default(),
+    };
+
+    let splices = {
+        let doc = parse_xml(xml, "word/document.xml")?;
+        let body = doc
+            .root_element()
+            .children()
+            .find(|n| is(*n, "body"))
+            .ok_or_else(|| "Failed to parse word/document.xml: no document body".to_string())?;
+        if let Some(revision) = revision.as_deref_mut() {
+            revision.skip_ids_in(&doc);
+        }
+        let paragraphs = paragraphs(body);
+        let paragraph = |number: usize| {
+            number
+                .checked_sub(1)
+                .and_then(|i| paragraphs.get(i))
+                .copied()
+                .ok_or_else(|| {
+                    format!("Paragraph {} does not exist; the document has {} paragraphs", number, paragraphs.len())
+                })
+        };
+
+        match edit {
+            // Untracked, a whole paragraph is simply written anew
+            DocxEdit::ReplaceText {
+                paragraph: number,
+                find: None,
+                text,
+            } if revision.is_none() => vec![replace_paragraph(xml, paragraph(*number)?, text)],
+            DocxEdit::ReplaceText {
+                paragraph: number,
+                find,
+                text,
+            } => replace_in_paragraph(xml, paragraph(*number)?, *number, find.as_deref(), text, revision)?,
+            DocxEdit::InsertParagraph { after, text, style } => {
+                let (position, previous) = if *after == 0 {
+                    let start = start_tag_end(xml, body)
+                        .ok_or_else(|| "Failed to parse word/document.xml: empty document body".to_string())?;
+                    (start, None)
+                } else {
+                    let previous = paragraph(*after)?;
+                    (previous.range().end, Some(previous))
+                };
+
+                // A named style wins; otherwise body text continues the
+                // formatting of the paragraph before, unless that is a heading
+                let (mut properties, run_properties) = match (style, previous) {
+                    (Some(style), _) => {
+                        let id = styles
+                            .id(style)
+                            .ok_or_else(|| format!("Unknown paragraph style: {}", style))?;
+                        (format!(r#"<w:pStyle w:val="{}"/>"#, xml_escape(id)), "")
+                    }
+                    (None, Some(previous)) if styles.heading_level(previous).is_none() => {
+                        (copy_properties(xml, previous), first_run_properties(xml, previous))
+                    }
+                    _ => (String::new(), ""),
+                };
+                let mut run = format!("<w:r>{}{}</w:r>", run_properties, text_elements(text));
+                // Both the text and the paragraph mark are marked as inserted
+                if let Some(revision) = revision {
+                    properties.push_str(&format!("<w:rPr><w:ins {}/></w:rPr>", revision.attributes()));
+                    run = format!("<w:ins {}>{}</w:ins>", revision.attributes(), run);
+                }
+                if !properties.is_empty() {
+                    properties = format!("<w:pPr>{}</w:pPr>", properties);
+                }
+                vec![Splice {
+                    range: position..position,
+                    text: format!("<w:p>{}{}</w:p>", properties, run),
+                }]
+            }
+            DocxEdit::SetHeading {
+                paragraph: number,
+                level,
+            } => {
+                if *level > 9 {
+                    return Err("Heading level must be between 0 and 9".to_string());
+                }
+                let style = if *level == 0 {
+                    None
+                } else {
+                    Some(heading_style(&styles, styles_xml, *level))
+                };
+                set_paragraph_style(xml, paragraph(*number)?, style.as_deref(), revision)
+            }
+            DocxEdit::AcceptChanges { id } => return resolve_changes(xml, true, id.as_deref()),
+            DocxEdit::RejectChanges { id } => return resolve_changes(xml, false, id.as_deref()),
+        }
+    };
+
+    Ok(splice(xml, splices))
+}
+
+fn splice(xml: &str, mut splices: Vec<Splice>) -> String {
+    splices.sort_by_key(|s| s.range.start);
+    let mut out = String::with_capacity(xml.len());
+    let mut position = 0;
+    for splice in splices {
+        out.push_str(&xml[position..splice.range.start]);
+        out.push_str(&splice.text);
+        position = splice.range.end;
+    }
+    out.push_str(&xml[position..]);
+    out
+}
+
+// Position just past the element's start tag, or None if it is self-closing
+fn start_tag_end(xml: &str, node: Node) -> Option<usize> {
+    let range = node.range();
+    let mut quote = None;
+    for (i, c) in xml[range.clone()].char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => {
+                let end = range.start + i;
+                return if xml[..end].ends_with('/') { None } else { Some(end + 1) };
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// Qualified name as written, e.g. "w:p"
+fn tag_name<'a>(xml: &'a str, node: Node) -> &'a str {
+    let rest = &xml[node.range().start + 1..];
+    &rest[..rest.find(|c: char| c.is_whitespace() || c == '>' || c == '/').unwrap_or(rest.len())]
+}
+
+// The element's start tag, turned into an open tag if it was self-closing
+fn start_tag(xml: &str, node: Node) -> String {
+    match start_tag_end(xml, node) {
+        Some(end) => xml[node.range().start..end].to_string(),
+        None => format!("{}>", xml[node.range()].trim_end_matches("/>").trim_end()),
+    }
+}
+
+fn inner<'a>(xml: &'a str, node: Node) -> &'a str {
+    match start_tag_end(xml, node) {
+        Some(start) => &xml[start..node.range().end - tag_name(xml, node).len() - 3],
+        None => "",
+    }
+}
+
+// w:t, w:tab, w:br and w:cr elements holding the paragraph's current text:
+// not deleted and not part of a nested paragraph such as a text box
+fn text_nodes<'a, 'input>(paragraph: Node<'a, 'input>) -> Vec<Node<'a, 'input>> {
+    paragraph
+        .descendants()
+        .filter(|n| is(*n, "t") || is(*n, "tab") || is(*n, "br") || is(*n, "cr"))
+        .filter(|n| {
+            n.ancestors()
+                .skip(1)
+                .take_while(|a| *a != paragraph)
+                .all(|a| !is(a, "del") && !is(a, "moveFrom") && !is(a, "p"))
+        })
+        .collect()
+}
+
+// Text of a node from text_nodes, with tabs and breaks as read_document
+// reads them
+fn node_text<'a>(node: Node<'a, '_>) -> &'a str {
+    match node.tag_name().name() {
+        "tab" => "\t",
+        "br" | "cr" => "\n",
+        _ => node.text().unwrap_or_default(),
+    }
+}
+
+// Run content for text, with tabs and line breaks as their own elements
+fn text_elements(text: &str) -> String {
+    let mut out = String::new();
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            out.push_str("<w:br/>");
+        }
+        for (j, part) in line.split('\t').enumerate() {
+            if j > 0 {
+                out.push_str("<w:tab/>");
+            }
+            if !part.is_empty() {
+                out.push_str(&format!(r#"<w:t xml:space="preserve">{}</w:t>"#, xml_escape(part)));
+            }
+        }
+    }
+    out
+}
+
+// Run content for deleted text
+fn deleted_text_elements(text: &str) -> String {
+    text_elements(text)
+        .replace("<w:t ", "<w:delText ")
+        .replace("</w:t>", "</w:delText>")
+}
+
+// Formatting of the paragraph's first run of text, so replacement text looks the same
+fn first_run_properties<'a>(xml: &'a str, paragraph: Node) -> &'a str {
+    text_nodes(paragraph)
+        .first()
+        .and_then(|t| t.parent_element())
+        .and_then(|run| child(run, "rPr"))
+        .map(|properties| &xml[properties.range()])
+        .unwrap_or("")
+}
+
+// Content of a paragraph's properties, leaving out section breaks,
+// paragraph mark formatting and revision marks
+fn copy_properties(xml: &str, paragraph: Node) -> String {
+    let Some(properties) = child(paragraph, "pPr") else {
+        return String::new();
+    };
+    properties
+        .children()
+        .filter(|n| n.is_element() && !is(*n, "sectPr") && !is(*n, "pPrChange") && !is(*n, "rPr"))
+        .map(|n| &xml[n.range()])
+        .collect()
+}
+
+// Replace the first occurrence of `find`, which may span several runs, or
+// all of the paragraph's text. The new text takes the formatting of the run
+// where the match starts. Tracked, the old text is kept as a deletion.
+fn replace_in_paragraph(
+    xml: &str,
+    paragraph: Node,
+    number: usize,
+    find: Option<&str>,
+    text: &str,
+    mut revision: Option<&mut Revision>,
+) -> Result<Vec<Splice>, String> {
+    let nodes = text_nodes(paragraph);
+    let full: String = nodes.iter().map(|n| node_text(*n)).collect();
+    let (start, end) = match find {
+        Some("") => return Err("The text to find must not be empty".to_string()),
+        Some(find) => {
+            let start = full
+                .find(find)
+                .ok_or_else(|| format!("\"{}\" was not found in paragraph {}", find, number))?;
+            (start, start + find.len())
+        }
+        None => (0, full.len()),
+    };
+
+    // An empty paragraph has nothing to replace; the text is added to it
+    if start == end {
+        let mut run = format!("<w:r>{}</w:r>", text_elements(text));
+        if let Some(revision) = revision {
+            run = format!("<w:ins {}>{}</w:ins>", revision.attributes(), run);
+        }
+        return Ok(vec![match start_tag_end(xml, paragraph) {
+            Some(_) => {
+                let position = paragraph.range().end - tag_name(xml, paragraph).len() - 3;
+                Splice {
+                    range: position..position,
+                    text: run,
+                }
+            }
+            None => Splice {
+                range: paragraph.range(),
+                text: format!("{}{}</{}>", start_tag(xml, paragraph), run, tag_name(xml, paragraph)),
+            },
+        }]);
+    }
+
+    let mut splices = Vec::new();
+    let mut offset = 0;
+    for node in nodes {
+        let current = node_text(node);
+        let (node_start, node_end) = (offset, offset + current.len());
+        offset = node_end;
+        if node_end <= start || node_start >= end {
+            continue;
+        }
+
+        let from = start.saturating_sub(node_start);
+        let to = (end - node_start).min(current.len());
+        let inserted = if node_start <= start { text } else { "" };
+
+        let Some(revision) = revision.as_deref_mut() else {
+            splices.push(Splice {
+                range: node.range(),
+                text: text_elements(&format!("{}{}{}", &current[..from], inserted, &current[to..])),
+            });
+            continue;
+        };
+
+        let run = node.parent_element();
+        let run_properties = run
+            .and_then(|run| child(run, "rPr"))
+            .map(|properties| &xml[properties.range()])
+            .unwrap_or("");
+        let mut changes = format!(
+            "<w:del {}><w:r>{}{}</w:r></w:del>",
+            revision.attributes(),
+            run_properties,
+            deleted_text_elements(&current[from..to])
+        );
+        if !inserted.is_empty() {
+            changes.push_str(&format!(
+                "<w:ins {}><w:r>{}{}</w:r></w:ins>",
+                revision.attributes(),
+                run_properties,
+                text_elements(inserted)
+            ));
+        }
+
+        // A run holding just this text is split in place; otherwise the run
+        // is closed around the changes and reopened for the rest of it
+        let alone = run.filter(|run| run.children().filter(|n| n.is_element()).all(|n| n == node || is(n, "rPr")));
+        splices.push(match alone {
+            Some(run) => {
+                let plain = |text: &str| match text {
+                    "" => String::new(),
+                    text => format!("<w:r>{}{}</w:r>", run_properties, text_elements(text)),
+                };
+                Splice {
+                    range: run.range(),
+                    text: format!("{}{}{}", plain(&current[..from]), changes, plain(&current[to..])),
+                }
+            }
+            None => Splice {
+                range: node.range(),
+                text: format!(
+                    "{}</w:r>{}<w:r>{}{}",
+                    text_elements(&current[..from]),
+                    changes,
+                    run_properties,
+                    text_elements(&current[to..])
+                ),
+            },
+        });
+    }
+    Ok(splices)
+}
+
+// New paragraph text in a single run, keeping the paragraph properties
+fn replace_paragraph(xml: &str, paragraph: Node, text: &str) -> Splice {
+    let properties = child(paragraph, "pPr").map(|n| &xml[n.range()]).unwrap_or("");
+    Splice {
+        range: paragraph.range(),
+        text: format!(
+            "{}{}<w:r>{}{}</w:r></{}>",
+            start_tag(xml, paragraph),
+            properties,
+            first_run_properties(xml, paragraph),
+            text_elements(text),
+            tag_name(xml, paragraph)
+        ),
+    }
+}
+
+// Style id for a heading level. Documents made outside Word may not define
+// heading styles, so a basic one is added when missing.
+fn heading_style(styles: &Styles, styles_xml: &mut Option<String>, level: u8) -> String {
+    if let Some(id) = styles.id(&format!("heading {}", level)) {
+        return id.to_string();
+    }
+    let id = format!("Heading{}", level);
+    if let Some(xml) = styles_xml.as_mut() {
+        if let Some(end) = xml.rfind("</w:styles>") {
+            let size = [32, 28, 26, 24, 22, 22, 22, 22, 22][level as usize - 1];
+            let definition = format!(
+                concat!(
+                    r#"<w:style w:type="paragraph" w:styleId="{id}"><w:name w:val="heading {level}"/>"#,
+                    r#"<w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:uiPriority w:val="9"/><w:qFormat/>"#,
+                    r#"<w:pPr><w:keepNext/><w:keepLines/><w:spacing w:before="240" w:after="60"/>"#,
+                    r#"<w:outlineLvl w:val="{outline}"/></w:pPr><w:rPr><w:b/><w:sz w:val="{size}"/></w:rPr></w:style>"#
+                ),
+                id = id,
+                level = level,
+                outline = level - 1,
+                size = size
+            );
+            xml.insert_str(end, &definition);
+        }
+    }
+    id
+}
+
+// Set or clear (None) the paragraph style. A direct outline level would
+// override the style, so it is removed. Tracked, the old properties are
+// recorded in a pPrChange.
+fn set_paragraph_style(xml: &str, paragraph: Node, style: Option<&str>, revision: Option<&mut Revision>) -> Vec<Splice> {
+    let style_element = style
+        .map(|id| format!(r#"<w:pStyle w:val="{}"/>"#, xml_escape(id)))
+        .unwrap_or_default();
+    let existing = child(paragraph, "pPr");
+    // A paragraph already changed keeps its first recorded properties
+    let change = revision
+        .filter(|_| existing.and_then(|p| child(p, "pPrChange")).is_none())
+        .map(|revision| {
+            format!(
+                "<w:pPrChange {}><w:pPr>{}</w:pPr></w:pPrChange>",
+                revision.attributes(),
+                copy_properties(xml, paragraph)
+            )
+        })
+        .unwrap_or_default();
+
+    let Some(properties) = existing else {
+        if style.is_none() {
+            return Vec::new();
+        }
+        let properties = format!("<w:pPr>{}{}</w:pPr>", style_element, change);
+        return vec![match start_tag_end(xml, paragraph) {
+            Some(end) => Splice {
+                range: end..end,
+                text: properties,
+            },
+            None => Splice {
+                range: paragraph.range(),
+                text: format!("{}{}</{}>", start_tag(xml, paragraph), properties, tag_name(xml, paragraph)),
+            },
+        }];
+    };
+
+    let Some(start) = start_tag_end(xml, properties) else {
+        return vec![Splice {
+            range: properties.range(),
+            text: format!("<w:pPr>{}{}</w:pPr>", style_element, change),
+        }];
+    };
+    let mut splices = Vec::new();
+    if let Some(outline) = child(properties, "outlineLvl") {
+        splices.push(Splice {
+            range: outline.range(),
+            text: String::new(),
+        });
+    }
+    // pStyle is always the first child of pPr, pPrChange the last
+    splices.push(match child(properties, "pStyle") {
+        Some(existing) => Splice {
+            range: existing.range(),
+            text: style_element,
+        },
+        None => Splice {
+            range: start..start,
+            text: style_element,
+        },
+    });
+    let end = properties.range().end - tag_name(xml, properties).len() - 3;
+    splices.push(Splice {
+        range: end..end,
+        text: change,
+    });
+    splices
+}
+
+// Property changes hold the old properties as their first child
+const PROPERTY_CHANGES: [&str; 7] = [
+    "rPrChange",
+    "pPrChange",
+    "sectPrChange",
+    "tblPrChange",
+    "trPrChange",
+    "tcPrChange",
+    "tblGridChange",
+];
+
+// Accept or reject tracked changes. Changes can nest (a deletion inside
+// someone else's insertion), so each pass handles the outermost ones and
+// the document is parsed again until none are left.
+fn resolve_changes(xml: &str, accept: bool, id: Option<&str>) -> Result<String, String> {
+    let mut xml = xml.to_string();
+    let mut resolved = 0;
+
+    loop {
+        let splices = {
+            let doc = parse_xml(&xml, "word/document.xml")?;
+            let mut splices = Vec::new();
+            let mut covered = 0;
+
+            for node in doc.descendants() {
+                if !node.is_element() || node.tag_name().namespace() != Some(W) {
+                    continue;
+                }
+                let name = node.tag_name().name();
+                let is_revision = matches!(name, "ins" | "del" | "moveFrom" | "moveTo");
+                if !is_revision && !PROPERTY_CHANGES.contains(&name) {
+                    continue;
+                }
+                if id.is_some_and(|id| node.attribute((W, "id")) != Some(id)) {
+                    continue;
+                }
+                let Some(parent) = node.parent_element() else {
+                    continue;
+                };
+
+                let splice = if !is_revision {
+                    if accept {
+                        Splice {
+                            range: node.range(),
+                            text: String::new(),
+                        }
+                    } else {
+                        let old = node.first_element_child().map(|n| inner(&xml, n)).unwrap_or("");
+                        Splice {
+                            range: parent.range(),
+                            text: format!("{}{}</{}>", start_tag(&xml, parent), old, tag_name(&xml, parent)),
+                        }
+                    }
+                } else if let Some(paragraph) = marked_paragraph(node) {
+                    // A paragraph mark that goes away joins its paragraph
+                    // with a neighbour; one that stays just loses the mark
+                    let inserted = matches!(name, "ins" | "moveTo");
+                    let merged = (inserted != accept).then(|| merge_paragraph(&xml, paragraph)).flatten();
+                    merged.unwrap_or(Splice {
+                        range: node.range(),
+                        text: String::new(),
+                    })
+                } else if parent.tag_name().name().ends_with("Pr") {
+                    // Marks on table rows are dropped either way; the
+                    // content itself is kept
+                    Splice {
+                        range: node.range(),
+                        text: String::new(),
+                    }
+                } else {
+                    let inserted = matches!(name, "ins" | "moveTo");
+                    let text = if inserted == accept {
+                        inner(&xml, node)
+                            .replace("<w:delText", "<w:t")
+                            .replace("</w:delText>", "</w:t>")
+                            .replace("<w:delInstrText", "<w:instrText")
+                            .replace("</w:delInstrText>", "</w:instrText>")
+                    } else {
+                        String::new()
+                    };
+                    Splice {
+                        range: node.range(),
+                        text,
+                    }
+                };
+
+                // Anything overlapping a change handled in this pass waits
+                if splice.range.start < covered {
+                    continue;
+                }
+                covered = splice.range.end;
+                splices.push(splice);
+                resolved += 1;
+            }
+            splices
+        };
+
+        if splices.is_empty() {
+            break;
+        }
+        xml = splice(&xml, splices);
+    }
+
+    if resolved == 0 {
+        return Err(match id {
+            Some(id) => format!("No tracked change with id {}", id),
+            None => "The document has no tracked changes".to_string(),
+        });
+    }
+    Ok(xml)
+}
+
+// The paragraph whose mark a revision is on: marks are recorded in the rPr
+// of the paragraph properties
+fn marked_paragraph<'a, 'input>(node: Node<'a, 'input>) -> Option<Node<'a, 'input>> {
+    node.parent_element()
+        .filter(|n| is(*n, "rPr"))?
+        .parent_element()
+        .filter(|n| is(*n, "pPr"))?
+        .parent_element()
+        .filter(|n| is(*n, "p"))
+}
+
+// Join a paragraph that lost its mark with the next one, whose properties
+// the joined paragraph keeps, as Word does. The last paragraph of the body
+// or a cell joins the one before instead. A paragraph of inserted text
+// thus disappears once its text is rejected too.
+fn merge_paragraph(xml: &str, paragraph: Node) -> Option<Splice> {
+    let (first, second, kept) = match paragraph.next_sibling_element().filter(|n| is(*n, "p")) {
+        Some(next) => (paragraph, next, next),
+        None => {
+            let previous = paragraph.prev_sibling_element().filter(|n| is(*n, "p"))?;
+            (previous, paragraph, previous)
+        }
+    };
+    let content = |p: Node| -> String {
+        p.children()
+            .filter(|n| !is(*n, "pPr"))
+            .map(|n| &xml[n.range()])
+            .collect()
+    };
+    let properties = child(kept, "pPr").map(|n| &xml[n.range()]).unwrap_or("");
+    Some(Splice {
+        range: first.range().start..second.range().end,
+        text: format!(
+            "{}{}{}{}</{}>",
+            start_tag(xml, kept),
+            properties,
+            content(first),
+            content(second),
+            tag_name(xml, kept)
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(body: &str) -> String {
+        format!(
+            r#"<w:document xmlns:w="{}"><w:body>{}<w:sectPr/></w:body></w:document>"#,
+            W, body
+        )
+    }
+
+    fn edit(xml: &str, edit: DocxEdit, tracked: bool) -> Result<String, String> {
+        let mut revision = Revision::new("Test");
+        apply(xml, &mut None, &edit, tracked.then_some(&mut revision))
+    }
+
+    // Current text of each body paragraph, as the edits see it
+    fn texts(xml: &str) -> Vec<String> {
+        let doc = roxmltree::Document::parse(xml).unwrap();
+        let body = doc.root_element().first_element_child().unwrap();
+        paragraphs(body)
+            .into_iter()
+            .map(|p| text_nodes(p).into_iter().map(node_text).collect())
+            .collect()
+    }
+
+    fn replace(paragraph: usize, find: &str, text: &str) -> DocxEdit {
+        DocxEdit::ReplaceText {
+            paragraph,
+            find: Some(find.to_string()),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn replaces_text_spanning_runs() {
+        let xml = document(r#"<w:p><w:r><w:t>Hello wo</w:t></w:r><w:r><w:rPr><w:b/></w:rPr><w:t>rld</w:t></w:r></w:p>"#);
+        let out = edit(&xml, replace(1, "world", "there"), false).unwrap();
+        assert_eq!(texts(&out), ["Hello there"]);
+    }
+
+    #[test]
+    fn matches_tabs_and_breaks() {
+        let xml = document(r#"<w:p><w:r><w:t>Name</w:t><w:tab/><w:t>Value</w:t><w:br/><w:t>Next</w:t></w:r></w:p>"#);
+        let out = edit(&xml, replace(1, "Name\tValue\nNext", "a\tb"), false).unwrap();
+        assert_eq!(texts(&out), ["a\tb"]);
+        assert!(out.contains("<w:tab/>"));
+    }
+
+    #[test]
+    fn missing_text_is_an_error() {
+        let xml = document(r#"<w:p><w:r><w:t>Hello</w:t></w:r></w:p>"#);
+        assert!(edit(&xml, replace(1, "Goodbye", "x"), false).is_err());
+        assert!(edit(&xml, replace(2, "Hello", "x"), false).is_err());
+    }
+
+    #[test]
+    fn tracked_replacement_can_be_accepted_or_rejected() {
+        let xml = document(r#"<w:p><w:r><w:t>The old plan</w:t></w:r></w:p>"#);
+        let tracked = edit(&xml, replace(1, "old", "new"), true).unwrap();
+        assert!(tracked.contains("<w:del ") && tracked.contains("<w:ins "));
+        assert_eq!(texts(&tracked), ["The new plan"]);
+
+        let accepted = edit(&tracked, DocxEdit::AcceptChanges { id: None }, false).unwrap();
+        assert_eq!(texts(&accepted), ["The new plan"]);
+        assert!(!accepted.contains("<w:del") && !accepted.contains("<w:ins"));
+
+        let rejected = edit(&tracked, DocxEdit::RejectChanges { id: None }, false).unwrap();
+        assert_eq!(texts(&rejected), ["The old plan"]);
+        assert!(!rejected.contains("<w:del") && !rejected.contains("<w:ins"));
+    }
+
+    #[test]
+    fn rejecting_an_inserted_paragraph_removes_it() {
+        let xml = document(r#"<w:p><w:r><w:t>One</w:t></w:r></w:p><w:p><w:r><w:t>Two</w:t></w:r></w:p>"#);
+        for after in [0, 1, 2] {
+            let insert = DocxEdit::InsertParagraph {
+                after,
+                text: "New".to_string(),
+                style: None,
+            };
+            let tracked = edit(&xml, insert, true).unwrap();
+            assert_eq!(texts(&tracked).len(), 3);
+
+            let rejected = edit(&tracked, DocxEdit::RejectChanges { id: None }, false).unwrap();
+            assert_eq!(texts(&rejected), ["One", "Two"], "inserted after {}", after);
+        }
+    }
+
+    #[test]
+    fn accepting_a_deleted_paragraph_mark_joins_the_paragraphs() {
+        let xml = document(concat!(
+            r#"<w:p><w:pPr><w:rPr><w:del w:id="1" w:author="A" w:date="2024-01-01T00:00:00Z"/></w:rPr></w:pPr>"#,
+            r#"<w:r><w:t>One </w:t></w:r></w:p>"#,
+            r#"<w:p><w:pPr><w:jc w:val="center"/></w:pPr><w:r><w:t>Two</w:t></w:r></w:p>"#
+        ));
+        let accepted = edit(&xml, DocxEdit::AcceptChanges { id: None }, false).unwrap();
+        assert_eq!(texts(&accepted), ["One Two"]);
+        // The joined paragraph keeps the properties of the second
+        assert!(accepted.contains(r#"<w:jc w:val="center"/>"#));
+
+        let rejected = edit(&xml, DocxEdit::RejectChanges { id: None }, false).unwrap();
+        assert_eq!(texts(&rejected), ["One ", "Two"]);
+    }
+
+    #[test]
+    fn no_tracked_changes_is_an_error() {
+        let xml = document(r#"<w:p><w:r><w:t>Hello</w:t></w:r></w:p>"#);
+        assert!(edit(&xml, DocxEdit::AcceptChanges { id: None }, false).is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::error::AppError;
+
+mod docx;
+mod docx_edit;
+mod pdf;
+mod pptx;
+mod tools;
+
+pub use docx::{read_docx, DocxContent, DocxParagraph};
+pub use docx_edit::DocxEdit;
+pub use pdf::PdfContent;
+pub use pptx::PptxContent;
+pub use tools::{mcp_config, serve as serve_tools, ALLOWED_TOOLS as DOCX_TOOLS, TOOLS_FLAG as DOCX_TOOLS_FLAG, TRACK_CHANGES_FLAG};
+
+// Text content of a binary document, as structured data plus a markdown
+// rendering that can be shown, searched or sent to Claude as context
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "format", rename_all = "lowercase")]
+pub enum DocumentContent {
+    Docx(DocxContent),
+    Pptx(PptxContent),
+    Pdf(PdfContent),
+}
+
+#[tauri::command]
+pub async fn read_document(path: String) -> Result<DocumentContent, AppError> {
+    let file_path = Path::new(&path);
+
+    if !file_path.exists() {
+        return Err(AppError::NotFound { path });
+    }
+
+    if !file_path.is_file() {
+        return Err(AppError::NotAFile { path });
+    }
+
+    let extension = file_path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "docx" => Ok(DocumentContent::Docx(docx::read_docx(file_path)?)),
+        "pptx" => Ok(DocumentContent::Pptx(pptx::read_pptx(file_path)?)),
+        "pdf" => Ok(DocumentContent::Pdf(pdf::read_pdf(file_path)?)),
+        _ => Err(AppError::invalid(format!("Unsupported document type: {}", path))),
+    }
+}
+
+// The markdown rendering of a .docx, .pptx or .pdf, or None for other files
+pub fn markdown(path: &Path) -> Result<Option<String>, String> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    Ok(Some(match extension.as_str() {
+        "docx" => docx::read_docx(path)?.markdown,
+        "pptx" => pptx::read_pptx(path)?.markdown,
+        "pdf" => {
+            let content = pdf::read_pdf(path)?;
+            if content.no_text {
+                return Err(format!(
+                    "No extractable text in {}: it may be a scanned document. Run OCR on it to use it as context",
+                    path.display()
+                ));
+            }
+            content.markdown
+        }
+        _ => return Ok(None),
+    }))
+}
+
+// Apply edits to a .docx in order and return its new content. Everything
+// the edits do not touch, formatting included, is kept as it was. With
+// `track_changes` the edits become revisions by "Claude" to review in Word.
+#[tauri::command]
+pub async fn edit_docx(
+    path: String,
+    edits: Vec<DocxEdit>,
+    track_changes: Option<bool>,
+) -> Result<DocxContent, AppError> {
+    let file_path = Path::new(&path);
+
+    if !file_path.exists() {
+        return Err(AppError::NotFound { path });
+    }
+
+    if !file_path.is_file() {
+        return Err(AppError::NotAFile { path });
+    }
+
+    docx_edit::edit_docx(file_path, &edits, track_changes.unwrap_or(false))?;
+    Ok(docx::read_docx(file_path)?)
+}
+
+// Reader for the parts of an Office Open XML zip package
+pub struct PackageReader {
+    archive: ZipArchive<File>,
+}
+
+impl PackageReader {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("Failed to open file: {}", e))?;
+        let archive = ZipArchive::new(file).map_err(|e| format!("Failed to read package: {}", e))?;
+        Ok(Self { archive })
+    }
+
+    // Returns None when the package has no such part
+    pub fn part(&mut self, name: &str) -> Result<Option<String>, String> {
+        let mut entry = match self.archive.by_name(name) {
+            Ok(entry) => entry,
+            Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(format!("Failed to read {}: {}", name, e)),
+        };
+        let mut text = String::new();
+        entry
+            .read_to_string(&mut text)
+            .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+        Ok(Some(text))
+    }
+
+    // Relationship id -> target for a part, e.g. "word/document.xml"
+    pub fn relationships(&mut self, part: &str) -> Result<HashMap<String, String>, String> {
+        let (dir, file) = part.rsplit_once('/').unwrap_or(("", part));
+        let rels_name = if dir.is_empty() {
+            format!("_rels/{}.rels", file)
+        } else {
+            format!("{}/_rels/{}.rels", dir, file)
+        };
+        let Some(xml) = self.part(&rels_name)? else {
+            return Ok(HashMap::new());
+        };
+
+        let doc = parse_xml(&xml, &rels_name)?;
+        Ok(doc
+            .descendants()
+            .filter(|n| n.has_tag_name("Relationship"))
+            .filter_map(|n| Some((n.attribute("Id")?.to_string(), n.attribute("Target")?.to_string())))
+            .collect())
+    }
+
+    // Write the package to `path` with some parts replaced, copying every
+    // other entry as is. The new file is moved into place once complete.
+    pub fn rewrite(mut self, path: &Path, parts: &[(&str, &str)]) -> Result<(), String> {
+        let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let temp_path = path.with_file_name(format!(".{}.tmp", file_name));
+
+        let result = (|| {
+            let file = File::create(&temp_path).map_err(|e| format!("Failed to write file: {}", e))?;
+            let mut writer = ZipWriter::new(file);
+            let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+            for index in 0..self.archive.len() {
+                let entry = self
+                    .archive
+                    .by_index_raw(index)
+                    .map_err(|e| format!("Failed to read package: {}", e))?;
+                match parts.iter().find(|(name, _)| *name == entry.name()) {
+                    Some((name, data)) => {
+                        drop(entry);
+                        writer
+                            .start_file(*name, options)
+                            .and_then(|_| writer.write_all(data.as_bytes()).map_err(Into::into))
+                            .map_err(|e| format!("Failed to write {}: {}", name, e))?;
+                    }
+                    None => writer
+                        .raw_copy_file(entry)
+                        .map_err(|e| format!("Failed to write package: {}", e))?,
+                }
+            }
+            writer.finish().map_err(|e| format!("Failed to write package: {}", e))?;
+            fs::rename(&temp_path, path).map_err(|e| format!("Failed to replace file: {}", e))
+        })();
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        result
+    }
+}
+
+pub fn parse_xml<'a>(xml: &'a str, name: &str) -> Result<roxmltree::Document<'a>, String> {
+    roxmltree::Document::parse(xml).map_err(|e| format!("Failed to parse {}: {}", name, e))
+}
+
+// Resolve a relationship target against the part it belongs to, e.g.
+// ("ppt/slides/slide1.xml", "../media/image1.png") -> "ppt/media/image1.png"
+pub fn resolve_target(part: &str, target: &str) -> String {
+    if let Some(absolute) = target.strip_prefix('/') {
+        return absolute.to_string();
+    }
+    let mut segments: Vec<&str> = part.split('/').collect();
+    segments.pop();
+    for segment in target.split('/') {
+        match segment {
+            ".." => {
+                segments.pop();
+            }
+            "." | "" => {}
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+// Pipe table with the first row as header
+pub fn markdown_table(rows: &[Vec<String>]) -> String {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    if columns == 0 {
+        return String::new();
+    }
+    let line = |row: &[String]| {
+        let mut line = String::from("|");
+        for column in 0..columns {
+            let cell = row.get(column).map(String::as_str).unwrap_or("");
+            line.push_str(&format!(" {} |", cell.replace('\n', "<br>").replace('|', "\\|")));
+        }
+        line
+    };
+
+    let mut lines = vec![line(&rows[0]), format!("|{}", " --- |".repeat(columns))];
+    lines.extend(rows[1..].iter().map(|row| line(row)));
+    lines.join("\n")
+}
+
+pub fn word_count(text: &str) -> usize {
+    text.split_whitespace().count()
+}
@@ -0,0 +1,127 @@
+use serde::Serialize;
+use std::fs;
+use std::panic;
+use std::path::Path;
+
+use super::word_count;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PdfContent {
+    // Text under a "## Page N" heading per page, for "p. 12" references
+    pub markdown: String,
+    pub pages: Vec<PdfPage>,
+    pub page_count: usize,
+    // Pages with no extractable text, e.g. scanned images
+    pub pages_without_text: Vec<usize>,
+    // No page has text: a scanned or image-only document, which needs OCR
+    // before it can be read
+    pub no_text: bool,
+    pub word_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PdfPage {
+    // Page number from 1
+    pub number: usize,
+    pub text: String,
+}
+
+pub fn read_pdf(path: &Path) -> Result<PdfContent, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+
+    // The extractor panics on some malformed files; report those as errors
+    let result = panic::catch_unwind(|| pdf_extract::extract_text_from_mem_by_pages(&data))
+        .map_err(|_| "Failed to extract PDF text: the file could not be parsed".to_string())?;
+    let raw_pages = result.map_err(|e| format!("Failed to extract PDF text: {}", e))?;
+
+    let pages: Vec<PdfPage> = raw_pages
+        .iter()
+        .enumerate()
+        .map(|(index, text)| PdfPage {
+            number: index + 1,
+            text: clean_text(text),
+        })
+        .collect();
+
+    let markdown = pages
+        .iter()
+        .filter(|p| !p.text.is_empty())
+        .map(|p| format!("## Page {}\n\n{}\n", p.number, p.text))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(PdfContent {
+        markdown,
+        page_count: pages.len(),
+        pages_without_text: pages.iter().filter(|p| p.text.is_empty()).map(|p| p.number).collect(),
+        no_text: pages.iter().all(|p| p.text.is_empty()),
+        word_count: pages.iter().map(|p| word_count(&p.text)).sum(),
+        pages,
+    })
+}
+
+// Trim trailing spaces and collapse runs of blank lines left by the layout
+fn clean_text(text: &str) -> String {
+    let mut out = String::new();
+    let mut blank_lines = 0;
+    for line in text.lines().map(str::trim_end) {
+        if line.trim().is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+        if !out.is_empty() {
+            out.push_str(if blank_lines > 0 { "\n\n" } else { "\n" });
+        }
+        out.push_str(line);
+        blank_lines = 0;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::pdf::markdown_to_pdf;
+    use crate::export::ExportOptions;
+
+    // Export markdown next to a tall image, then read the PDF back
+    fn read(name: &str, markdown: &str) -> PdfContent {
+        let dir = std::env::temp_dir().join(format!("clause-read-pdf-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        image::RgbImage::new(100, 1000).save(dir.join("tall.png")).unwrap();
+        let (bytes, _) = markdown_to_pdf(markdown, &dir, "Test", &ExportOptions::default()).unwrap();
+        fs::write(dir.join("test.pdf"), bytes).unwrap();
+        let content = read_pdf(&dir.join("test.pdf"));
+        fs::remove_dir_all(&dir).unwrap();
+        content.unwrap()
+    }
+
+    #[test]
+    fn pages_are_headed_by_number() {
+        let content = read("text", "# Title\n\nFirst   paragraph.\n\nSecond paragraph.\n");
+        assert_eq!(content.markdown, "## Page 1\n\nTitle\n\nFirst paragraph.\n\nSecond paragraph.\n");
+        assert_eq!(content.page_count, 1);
+        assert!(content.pages_without_text.is_empty());
+        assert!(!content.no_text);
+        assert_eq!(content.word_count, 5);
+    }
+
+    #[test]
+    fn image_only_pages_are_reported() {
+        // Each image fills most of a page, so the second one is alone on page 2
+        let tall = "![Tall](tall.png)\n\n";
+        let content = read("images", &format!("Before.\n\n{}After.\n", tall.repeat(3)));
+        assert_eq!(content.page_count, 3);
+        assert_eq!(content.pages_without_text, [2]);
+        assert!(!content.no_text);
+        assert_eq!(content.markdown, "## Page 1\n\nBefore.\n\n## Page 3\n\nAfter.\n");
+    }
+
+    #[test]
+    fn scanned_documents_have_no_text() {
+        let content = read("scanned", "![Scan](tall.png)\n");
+        assert!(content.no_text);
+        assert_eq!(content.pages_without_text, [1]);
+        assert_eq!(content.markdown, "");
+    }
+}
@@ -0,0 +1,363 @@
+use roxmltree::Node;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::{markdown_table, parse_xml, resolve_target, word_count, PackageReader};
+
+const P: &str = "http://schemas.openxmlformats.org/presentationml/2006/main";
+const A: &str = "http://schemas.openxmlformats.org/drawingml/2006/main";
+const R: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PptxContent {
+    pub markdown: String,
+    pub slides: Vec<PptxSlide>,
+    // Slide text only; speaker notes are not counted
+    pub word_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PptxSlide {
+    // Position in the deck, from 1
+    pub number: usize,
+    pub title: Option<String>,
+    pub shapes: Vec<SlideShape>,
+    pub notes: Option<String>,
+    pub images: Vec<SlideImage>,
+    pub hidden: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum SlideShape {
+    Text { name: String, paragraphs: Vec<ShapeParagraph> },
+    Table { name: String, rows: Vec<Vec<String>> },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShapeParagraph {
+    pub text: String,
+    pub level: u8,
+    pub bullet: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SlideImage {
+    pub name: String,
+    pub description: Option<String>,
+    // Path of the image inside the package, e.g. ppt/media/image1.png
+    pub target: String,
+}
+
+pub fn read_pptx(path: &Path) -> Result<PptxContent, String> {
+    let mut package = PackageReader::open(path)?;
+    let xml = package
+        .part("ppt/presentation.xml")?
+        .ok_or_else(|| "Not a PowerPoint presentation: ppt/presentation.xml is missing".to_string())?;
+    let relationships = package.relationships("ppt/presentation.xml")?;
+
+    // Slide order comes from the slide id list, not the part names
+    let doc = parse_xml(&xml, "ppt/presentation.xml")?;
+    let slide_parts: Vec<String> = doc
+        .descendants()
+        .filter(|n| is(*n, P, "sldId"))
+        .filter_map(|n| relationships.get(n.attribute((R, "id"))?))
+        .map(|target| resolve_target("ppt/presentation.xml", target))
+        .collect();
+
+    let mut slides = Vec::new();
+    for (index, part) in slide_parts.iter().enumerate() {
+        let Some(xml) = package.part(part)? else {
+            continue;
+        };
+        let links = package.relationships(part)?;
+        let doc = parse_xml(&xml, part)?;
+        let mut slide = PptxSlide {
+            number: index + 1,
+            title: None,
+            shapes: Vec::new(),
+            notes: None,
+            images: Vec::new(),
+            hidden: doc.root_element().attribute("show") == Some("0"),
+        };
+        if let Some(tree) = doc.descendants().find(|n| is(*n, P, "spTree")) {
+            read_shapes(tree, part, &links, &mut slide);
+        }
+
+        let notes_part = links
+            .iter()
+            .find(|(_, target)| target.contains("notesSlide"))
+            .map(|(_, target)| resolve_target(part, target));
+        if let Some(notes_part) = notes_part {
+            if let Some(xml) = package.part(&notes_part)? {
+                slide.notes = notes_text(&parse_xml(&xml, &notes_part)?);
+            }
+        }
+        slides.push(slide);
+    }
+
+    let word_count = slides
+        .iter()
+        .map(|slide| {
+            let shapes: usize = slide
+                .shapes
+                .iter()
+                .map(|shape| match shape {
+                    SlideShape::Text { paragraphs, .. } => paragraphs.iter().map(|p| word_count(&p.text)).sum::<usize>(),
+                    SlideShape::Table { rows, .. } => rows.iter().flatten().map(|c| word_count(c)).sum(),
+                })
+                .sum();
+            shapes + slide.title.as_deref().map(word_count).unwrap_or(0)
+        })
+        .sum();
+
+    Ok(PptxContent {
+        markdown: to_markdown(&slides),
+        slides,
+        word_count,
+    })
+}
+
+fn is(node: Node, namespace: &str, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name && node.tag_name().namespace() == Some(namespace)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, namespace: &str, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| is(*n, namespace, name))
+}
+
+// Placeholder type of a shape ("title", "body", ...), if it is one.
+// Placeholders without a type are body placeholders.
+fn placeholder_type<'a>(shape: Node<'a, '_>) -> Option<&'a str> {
+    let placeholder = shape
+        .children()
+        .find(|n| n.tag_name().name().starts_with("nv"))
+        .and_then(|n| child(n, P, "nvPr"))
+        .and_then(|n| child(n, P, "ph"))?;
+    Some(placeholder.attribute("type").unwrap_or("body"))
+}
+
+fn shape_name(shape: Node) -> String {
+    shape
+        .descendants()
+        .find(|n| is(*n, P, "cNvPr"))
+        .and_then(|n| n.attribute("name"))
+        .unwrap_or_default()
+        .to_string()
+}
+
+// Shapes in z-order, descending into groups
+fn read_shapes(tree: Node, part: &str, links: &HashMap<String, String>, slide: &mut PptxSlide) {
+    for shape in tree.children().filter(|n| n.is_element()) {
+        match shape.tag_name().name() {
+            "sp" => {
+                let Some(body) = child(shape, P, "txBody") else {
+                    continue;
+                };
+                let kind = placeholder_type(shape);
+                let paragraphs = text_paragraphs(body, matches!(kind, Some("body" | "obj")));
+                if paragraphs.is_empty() {
+                    continue;
+                }
+                if matches!(kind, Some("title" | "ctrTitle")) && slide.title.is_none() {
+                    let title: Vec<&str> = paragraphs.iter().map(|p| p.text.as_str()).collect();
+                    slide.title = Some(title.join(" "));
+                } else {
+                    slide.shapes.push(SlideShape::Text {
+                        name: shape_name(shape),
+                        paragraphs,
+                    });
+                }
+            }
+            "graphicFrame" => {
+                let Some(table) = shape.descendants().find(|n| is(*n, A, "tbl")) else {
+                    continue;
+                };
+                let rows = table
+                    .children()
+                    .filter(|n| is(*n, A, "tr"))
+                    .map(|row| {
+                        row.children()
+                            .filter(|n| is(*n, A, "tc"))
+                            .map(|cell| {
+                                let paragraphs = child(cell, A, "txBody")
+                                    .map(|body| text_paragraphs(body, false))
+                                    .unwrap_or_default();
+                                let text: Vec<&str> = paragraphs.iter().map(|p| p.text.as_str()).collect();
+                                text.join("\n")
+                            })
+                            .collect()
+                    })
+                    .collect();
+                slide.shapes.push(SlideShape::Table {
+                    name: shape_name(shape),
+                    rows,
+                });
+            }
+            "pic" => {
+                let target = shape
+                    .descendants()
+                    .find(|n| is(*n, A, "blip"))
+                    .and_then(|n| n.attribute((R, "embed")))
+                    .and_then(|id| links.get(id));
+                let Some(target) = target else {
+                    continue;
+                };
+                let properties = shape.descendants().find(|n| is(*n, P, "cNvPr"));
+                slide.images.push(SlideImage {
+                    name: shape_name(shape),
+                    description: properties
+                        .and_then(|n| n.attribute("descr"))
+                        .filter(|d| !d.is_empty())
+                        .map(str::to_string),
+                    target: resolve_target(part, target),
+                });
+            }
+            "grpSp" => read_shapes(shape, part, links, slide),
+            _ => {}
+        }
+    }
+}
+
+// Paragraphs of a text body. In body placeholders paragraphs are bullets
+// unless switched off; elsewhere only explicit bullets count.
+fn text_paragraphs(body: Node, bulleted: bool) -> Vec<ShapeParagraph> {
+    let mut out = Vec::new();
+    for paragraph in body.children().filter(|n| is(*n, A, "p")) {
+        let mut text = String::new();
+        for item in paragraph.children() {
+            if is(item, A, "r") || is(item, A, "fld") {
+                if let Some(t) = child(item, A, "t").and_then(|t| t.text()) {
+                    text.push_str(t);
+                }
+            } else if is(item, A, "br") {
+                text.push('\n');
+            }
+        }
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        let properties = child(paragraph, A, "pPr");
+        let level = properties
+            .and_then(|p| p.attribute("lvl"))
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(0);
+        let has = |name: &str| properties.and_then(|p| child(p, A, name)).is_some();
+        let bullet = !has("buNone") && (bulleted || has("buChar") || has("buAutoNum"));
+        out.push(ShapeParagraph { text, level, bullet });
+    }
+    out
+}
+
+fn notes_text(doc: &roxmltree::Document) -> Option<String> {
+    let body = doc
+        .descendants()
+        .filter(|n| is(*n, P, "sp"))
+        .find(|shape| placeholder_type(*shape) == Some("body"))
+        .and_then(|shape| child(shape, P, "txBody"))?;
+    let paragraphs: Vec<String> = text_paragraphs(body, false).into_iter().map(|p| p.text).collect();
+    if paragraphs.is_empty() {
+        None
+    } else {
+        Some(paragraphs.join("\n"))
+    }
+}
+
+fn to_markdown(slides: &[PptxSlide]) -> String {
+    let mut blocks = Vec::new();
+    for slide in slides {
+        let mut heading = format!("## Slide {}", slide.number);
+        if let Some(title) = &slide.title {
+            heading.push_str(&format!(": {}", title.replace('\n', " ")));
+        }
+        if slide.hidden {
+            heading.push_str(" (hidden)");
+        }
+        blocks.push(heading);
+
+        for shape in &slide.shapes {
+            match shape {
+                SlideShape::Text { paragraphs, .. } => {
+                    let lines: Vec<String> = paragraphs
+                        .iter()
+                        .map(|p| {
+                            if p.bullet {
+                                let indent = "  ".repeat(p.level as usize);
+                                format!("{}- {}", indent, p.text.replace('\n', &format!("\n{}  ", indent)))
+                            } else {
+                                p.text.replace('\n', "  \n")
+                            }
+                        })
+                        .collect();
+                    // Bullets stay together; plain paragraphs get blank lines
+                    let mut block = String::new();
+                    for (i, line) in lines.iter().enumerate() {
+                        if i > 0 {
+                            let tight = paragraphs[i].bullet && paragraphs[i - 1].bullet;
+                            block.push_str(if tight { "\n" } else { "\n\n" });
+                        }
+                        block.push_str(line);
+                    }
+                    blocks.push(block);
+                }
+                SlideShape::Table { rows, .. } => {
+                    if !rows.is_empty() {
+                        blocks.push(markdown_table(rows));
+                    }
+                }
+            }
+        }
+
+        for image in &slide.images {
+            let alt = image.description.as_deref().unwrap_or(&image.name);
+            blocks.push(format!("![{}]({})", alt, image.target));
+        }
+
+        if let Some(notes) = &slide.notes {
+            let quoted: Vec<String> = notes.lines().map(|line| format!("> {}", line)).collect();
+            blocks.push(format!("> **Notes:**\n{}", quoted.join("\n")));
+        }
+    }
+
+    let mut markdown = blocks.join("\n\n");
+    if !markdown.is_empty() {
+        markdown.push('\n');
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::pptx::markdown_to_pptx;
+    use crate::export::SlideBreakStrategy;
+    use std::fs;
+
+    fn read(name: &str, markdown: &str) -> PptxContent {
+        let bytes = markdown_to_pptx(markdown, Path::new("."), "Deck", SlideBreakStrategy::Headings).unwrap();
+        let path = std::env::temp_dir().join(format!("clause-read-{}-{}.pptx", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        let content = read_pptx(&path);
+        fs::remove_file(&path).unwrap();
+        content.unwrap()
+    }
+
+    #[test]
+    fn reads_slides_with_their_notes() {
+        let markdown = "# Deck\n\nBy us\n\n## One\n\n- a\n  - b\n\n<!-- notes: Say this\nand that -->\n\n## Two\n\n| A | B |\n|---|---|\n| 1 | 2 |\n";
+        let content = read("notes", markdown);
+
+        let titles: Vec<Option<&str>> = content.slides.iter().map(|s| s.title.as_deref()).collect();
+        assert_eq!(titles, [Some("Deck"), Some("One"), Some("Two")]);
+        let notes: Vec<Option<&str>> = content.slides.iter().map(|s| s.notes.as_deref()).collect();
+        assert_eq!(notes, [None, Some("Say this\nand that"), None]);
+        assert_eq!(
+            content.markdown,
+            "## Slide 1: Deck\n\nBy us\n\n## Slide 2: One\n\n- a\n  - b\n\n> **Notes:**\n> Say this\n> and that\n\n## Slide 3: Two\n\n| A | B |\n| --- | --- |\n| 1 | 2 |\n"
+        );
+        // Notes are not counted
+        assert_eq!(content.word_count, 11);
+    }
+}
@@ -0,0 +1,230 @@
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use super::docx::{read_docx, ChangeKind, DocxContent, DocxParagraph};
+use super::docx_edit::{edit_docx, DocxEdit};
+
+// The app serves the .docx tools to Claude over MCP (JSON-RPC on
+// stdin/stdout) when started with this flag
+pub const TOOLS_FLAG: &str = "--docx-tools";
+// Added to TOOLS_FLAG when Claude's edits should be tracked changes
+pub const TRACK_CHANGES_FLAG: &str = "--track-changes";
+// Tool name prefix the Claude CLI gives tools from our server
+pub const ALLOWED_TOOLS: &str = "mcp__clause";
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+// Value for the Claude CLI's --mcp-config, starting this executable as the server
+pub fn mcp_config(track_changes: bool) -> Option<String> {
+    let exe = std::env::current_exe().ok()?;
+    let mut args = vec![TOOLS_FLAG];
+    if track_changes {
+        args.push(TRACK_CHANGES_FLAG);
+    }
+    Some(json!({ "mcpServers": { "clause": { "command": exe, "args": args } } }).to_string())
+}
+
+pub fn serve(track_changes: bool) {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let message = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => {
+                // Notifications have no id and get no reply
+                let Some(id) = request.get("id").cloned() else {
+                    continue;
+                };
+                match handle(&request, track_changes) {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+                }
+            }
+            Err(e) => json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": -32700, "message": format!("Parse error: {}", e) }
+            }),
+        };
+
+        if writeln!(stdout, "{}", message).and_then(|_| stdout.flush()).is_err() {
+            break;
+        }
+    }
+}
+
+fn handle(request: &Value, track_changes: bool) -> Result<Value, Value> {
+    let method = request["method"].as_str().unwrap_or_default();
+    let params = &request["params"];
+    match method {
+        "initialize" => Ok(json!({
+            "protocolVersion": params["protocolVersion"].as_str().unwrap_or(PROTOCOL_VERSION),
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "clause", "version": env!("CARGO_PKG_VERSION") }
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tool_definitions() })),
+        "tools/call" => {
+            let name = params["name"].as_str().unwrap_or_default();
+            let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+            // Tool failures are results Claude can read and act on
+            Ok(match call_tool(name, arguments, track_changes) {
+                Ok(text) => json!({ "content": [{ "type": "text", "text": text }] }),
+                Err(e) => json!({ "content": [{ "type": "text", "text": e }], "isError": true }),
+            })
+        }
+        _ => Err(json!({ "code": -32601, "message": format!("Method not found: {}", method) })),
+    }
+}
+
+fn tool_definitions() -> Value {
+    let path = json!({ "type": "string", "description": "Path of the .docx file" });
+    let paragraph = json!({ "type": "integer", "minimum": 1, "description": "Paragraph number from docx_read" });
+    let change_id = json!({ "type": "string", "description": "Id of one tracked change; all changes when omitted" });
+
+    json!([
+        {
+            "name": "docx_read",
+            "description": "List the numbered paragraphs of a Word document with their styles, plus its tracked changes and comments. Call this before editing to get paragraph numbers.",
+            "inputSchema": { "type": "object", "properties": { "path": path }, "required": ["path"] }
+        },
+        {
+            "name": "docx_replace_text",
+            "description": "Replace the first occurrence of `find` in a paragraph, keeping its formatting. Without `find` the whole paragraph text is replaced.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": path,
+                    "paragraph": paragraph,
+                    "find": { "type": "string", "description": "Exact text to replace" },
+                    "text": { "type": "string", "description": "New text" }
+                },
+                "required": ["path", "paragraph", "text"]
+            }
+        },
+        {
+            "name": "docx_insert_paragraph",
+            "description": "Insert a paragraph after the given one (0 for the start of the document). It takes the formatting of the paragraph before unless a style is given.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": path,
+                    "after": { "type": "integer", "minimum": 0, "description": "Paragraph number to insert after" },
+                    "text": { "type": "string" },
+                    "style": { "type": "string", "description": "Style name or id, e.g. \"Quote\"" }
+                },
+                "required": ["path", "after", "text"]
+            }
+        },
+        {
+            "name": "docx_set_heading",
+            "description": "Make a paragraph a heading of the given level, or body text with level 0.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": path,
+                    "paragraph": paragraph,
+                    "level": { "type": "integer", "minimum": 0, "maximum": 9 }
+                },
+                "required": ["path", "paragraph", "level"]
+            }
+        },
+        {
+            "name": "docx_accept_changes",
+            "description": "Accept tracked changes in a Word document.",
+            "inputSchema": { "type": "object", "properties": { "path": path, "id": change_id }, "required": ["path"] }
+        },
+        {
+            "name": "docx_reject_changes",
+            "description": "Reject tracked changes in a Word document.",
+            "inputSchema": { "type": "object", "properties": { "path": path, "id": change_id }, "required": ["path"] }
+        }
+    ])
+}
+
+fn call_tool(name: &str, mut arguments: Value, track_changes: bool) -> Result<String, String> {
+    let path = arguments["path"]
+        .as_str()
+        .ok_or_else(|| "Missing argument: path".to_string())?;
+    // Claude runs in the working directory, so relative paths start there
+    let path = std::env::current_dir()
+        .map(|dir| dir.join(path))
+        .unwrap_or_else(|_| PathBuf::from(path));
+    if !path.is_file() {
+        return Err(format!("File does not exist: {}", path.display()));
+    }
+
+    if name == "docx_read" {
+        return Ok(describe(&read_docx(&path)?));
+    }
+
+    // Each edit tool is a DocxEdit with the tool name as its op
+    let op = name
+        .strip_prefix("docx_")
+        .ok_or_else(|| format!("Unknown tool: {}", name))?;
+    arguments["op"] = json!(op);
+    let edit: DocxEdit = serde_json::from_value(arguments).map_err(|e| format!("Invalid arguments for {}: {}", name, e))?;
+    edit_docx(&path, std::slice::from_ref(&edit), track_changes)?;
+
+    let content = read_docx(&path)?;
+    let changed = match &edit {
+        DocxEdit::ReplaceText { paragraph, .. } | DocxEdit::SetHeading { paragraph, .. } => Some(*paragraph),
+        DocxEdit::InsertParagraph { after, .. } => Some(after + 1),
+        DocxEdit::AcceptChanges { .. } | DocxEdit::RejectChanges { .. } => None,
+    };
+    Ok(match changed.and_then(|n| content.paragraphs.get(n - 1)) {
+        Some(paragraph) => format!("Done. {}", paragraph_line(paragraph)),
+        None => format!("Done. {} tracked changes remain.", content.tracked_changes.len()),
+    })
+}
+
+fn paragraph_line(paragraph: &DocxParagraph) -> String {
+    let marker = match paragraph.heading_level {
+        Some(level) => format!("{} ", "#".repeat(level as usize)),
+        None => String::new(),
+    };
+    let style = paragraph
+        .style
+        .as_deref()
+        .filter(|_| paragraph.heading_level.is_none())
+        .map(|s| format!(" ({})", s))
+        .unwrap_or_default();
+    format!("[{}]{} {}{}", paragraph.index, style, marker, paragraph.text)
+}
+
+// Plain text overview for Claude: paragraphs, then changes and comments
+fn describe(content: &DocxContent) -> String {
+    let mut lines: Vec<String> = content.paragraphs.iter().map(paragraph_line).collect();
+
+    if !content.tracked_changes.is_empty() {
+        lines.push(String::new());
+        lines.push("Tracked changes:".to_string());
+        for change in &content.tracked_changes {
+            let kind = match change.kind {
+                ChangeKind::Insertion => "inserted",
+                ChangeKind::Deletion => "deleted",
+            };
+            lines.push(format!(
+                "- id {} in [{}]: {} {} \"{}\"",
+                change.id, change.paragraph, change.author, kind, change.text
+            ));
+        }
+    }
+
+    if !content.comments.is_empty() {
+        lines.push(String::new());
+        lines.push("Comments:".to_string());
+        for comment in &content.comments {
+            let location = comment.paragraph.map(|n| format!(" on [{}]", n)).unwrap_or_default();
+            lines.push(format!("- {}{}: {}", comment.author, location, comment.text));
+        }
+    }
+    lines.join("\n")
+}
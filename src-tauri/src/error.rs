@@ -0,0 +1,135 @@
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+// Error returned by every command. It serializes as
+// {"code": "not_found", "message": "...", "path": "..."} so the frontend
+// can branch on `code`, which stays stable, and show `message`.
+#[derive(Debug, Clone)]
+pub enum AppError {
+    NotFound { path: String },
+    NotADirectory { path: String },
+    NotAFile { path: String },
+    PermissionDenied { path: String },
+    // The operation does not fit the current state, e.g. retrying a task
+    // that has not failed
+    Conflict { message: String },
+    OutsideWorkspace { path: String },
+    ClaudeNotInstalled,
+    // The Claude CLI is older than the oldest release we support
+    ClaudeUnsupported { version: String },
+    // The Claude process kept exiting and was not restarted again; the
+    // next message starts a new session
+    ClaudeCrashed { message: String },
+    InvalidInput { message: String },
+    Io { message: String, path: Option<String> },
+    // Anything else, such as a malformed document
+    Failed { message: String },
+}
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound { .. } => "not_found",
+            AppError::NotADirectory { .. } => "not_a_directory",
+            AppError::NotAFile { .. } => "not_a_file",
+            AppError::PermissionDenied { .. } => "permission_denied",
+            AppError::Conflict { .. } => "conflict",
+            AppError::OutsideWorkspace { .. } => "outside_workspace",
+            AppError::ClaudeNotInstalled => "claude_not_installed",
+            AppError::ClaudeUnsupported { .. } => "claude_unsupported",
+            AppError::ClaudeCrashed { .. } => "claude_crashed",
+            AppError::InvalidInput { .. } => "invalid_input",
+            AppError::Io { .. } => "io",
+            AppError::Failed { .. } => "failed",
+        }
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            AppError::NotFound { path }
+            | AppError::NotADirectory { path }
+            | AppError::NotAFile { path }
+            | AppError::PermissionDenied { path }
+            | AppError::OutsideWorkspace { path } => Some(path),
+            AppError::Io { path, .. } => path.as_deref(),
+            _ => None,
+        }
+    }
+
+    // An I/O error on `path`, keeping missing files and denied access
+    // apart from other failures. `action` reads like "Failed to read file".
+    pub fn io(action: &str, path: &Path, error: io::Error) -> Self {
+        let path = path.display().to_string();
+        match error.kind() {
+            ErrorKind::NotFound => AppError::NotFound { path },
+            ErrorKind::PermissionDenied => AppError::PermissionDenied { path },
+            _ => AppError::Io {
+                message: format!("{}: {}", action, error),
+                path: Some(path),
+            },
+        }
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        AppError::InvalidInput { message: message.into() }
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        AppError::Conflict { message: message.into() }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::NotFound { path } => write!(f, "Path does not exist: {}", path),
+            AppError::NotADirectory { path } => write!(f, "Path is not a directory: {}", path),
+            AppError::NotAFile { path } => write!(f, "Path is not a file: {}", path),
+            AppError::PermissionDenied { path } => write!(f, "Permission denied: {}", path),
+            AppError::OutsideWorkspace { path } => write!(f, "Path is outside the open folder: {}", path),
+            AppError::ClaudeNotInstalled => write!(f, "Claude CLI was not found; install it or set its path in settings"),
+            AppError::ClaudeUnsupported { version } => write!(
+                f,
+                "Claude CLI {} is too old, {} or newer is needed",
+                version,
+                crate::claude_cli::MIN_VERSION
+            ),
+            AppError::Conflict { message }
+            | AppError::ClaudeCrashed { message }
+            | AppError::InvalidInput { message }
+            | AppError::Io { message, .. }
+            | AppError::Failed { message } => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("code", self.code())?;
+        map.serialize_entry("message", &self.to_string())?;
+        if let Some(path) = self.path() {
+            map.serialize_entry("path", path)?;
+        }
+        map.end()
+    }
+}
+
+// Internal helpers report failures as messages
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::Failed { message }
+    }
+}
+
+impl From<&str> for AppError {
+    fn from(message: &str) -> Self {
+        AppError::Failed {
+            message: message.to_string(),
+        }
+    }
+}
@@ -0,0 +1,448 @@
+use pulldown_cmark::{Alignment, Event, Parser, Tag, TagEnd};
+use std::collections::HashMap;
+
+// Markdown flattened into a list of blocks, for exporters that lay the
+// text out themselves instead of handing it to a word processor.
+
+#[derive(Debug, Clone, Default)]
+pub struct Span {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub code: bool,
+    pub strike: bool,
+    pub link: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Marker {
+    Bullet,
+    Number(u64),
+    Task(bool),
+}
+
+// Nesting of a block inside lists and block quotes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Context {
+    pub list_depth: usize,
+    pub quote_depth: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum Block {
+    Heading {
+        level: u8,
+        spans: Vec<Span>,
+    },
+    Paragraph {
+        spans: Vec<Span>,
+        // Set on the first paragraph of a list item
+        marker: Option<Marker>,
+        context: Context,
+    },
+    Code {
+        text: String,
+        context: Context,
+    },
+    Table {
+        alignments: Vec<Alignment>,
+        rows: Vec<Vec<Vec<Span>>>,
+        header_rows: usize,
+        context: Context,
+    },
+    Image {
+        url: String,
+        alt: String,
+        context: Context,
+    },
+    Rule,
+    // Text of an HTML comment on its own line, e.g. <!-- notes: ... -->
+    Comment(String),
+}
+
+pub struct Footnote {
+    pub number: u32,
+    pub blocks: Vec<Block>,
+}
+
+pub struct Document {
+    pub blocks: Vec<Block>,
+    pub footnotes: Vec<Footnote>,
+}
+
+pub fn parse(markdown: &str) -> Document {
+    let mut builder = Builder::default();
+    for event in Parser::new_ext(markdown, super::markdown_options()) {
+        builder.event(event);
+    }
+    builder.finish()
+}
+
+// Concatenated text of a run of spans
+pub fn plain_text(spans: &[Span]) -> String {
+    spans.iter().map(|s| s.text.as_str()).collect()
+}
+
+struct PendingTable {
+    alignments: Vec<Alignment>,
+    rows: Vec<Vec<Vec<Span>>>,
+    header_rows: usize,
+}
+
+#[derive(Default)]
+struct Builder {
+    blocks: Vec<Block>,
+    spans: Vec<Span>,
+
+    bold: u32,
+    italic: u32,
+    strike: u32,
+    links: Vec<String>,
+
+    heading: Option<u8>,
+    quote_depth: usize,
+    // Next number of each open list; None for bullet lists
+    lists: Vec<Option<u64>>,
+    marker: Option<Marker>,
+    code_block: Option<String>,
+    image: Option<(String, String)>,
+    html_block: Option<String>,
+    table: Option<PendingTable>,
+    table_head: bool,
+    metadata: bool,
+
+    footnote_numbers: HashMap<String, u32>,
+    footnotes: Vec<Footnote>,
+    // Body blocks while a footnote definition is being read
+    body: Option<Vec<Block>>,
+}
+
+impl Builder {
+    fn event(&mut self, event: Event) {
+        if self.metadata {
+            if let Event::End(TagEnd::MetadataBlock(_)) = event {
+                self.metadata = false;
+            }
+            return;
+        }
+
+        if let Some((_, alt)) = &mut self.image {
+            match event {
+                Event::Text(text) | Event::Code(text) => alt.push_str(&text),
+                Event::End(TagEnd::Image) => self.end_image(),
+                _ => {}
+            }
+            return;
+        }
+
+        if let Some(code) = &mut self.code_block {
+            match event {
+                Event::Text(text) => code.push_str(&text),
+                Event::End(TagEnd::CodeBlock) => {
+                    let text = self.code_block.take().unwrap_or_default();
+                    let text = text.strip_suffix('\n').unwrap_or(&text).to_string();
+                    let context = self.context();
+                    self.blocks.push(Block::Code { text, context });
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => self.push_text(&text, false),
+            Event::Code(text) | Event::InlineMath(text) | Event::DisplayMath(text) => {
+                self.push_text(&text, true)
+            }
+            Event::Html(html) | Event::InlineHtml(html) => {
+                if let Some(block) = &mut self.html_block {
+                    block.push_str(&html);
+                } else if matches!(html.trim().to_lowercase().as_str(), "<br>" | "<br/>" | "<br />") {
+                    self.push_text("\n", false);
+                }
+            }
+            Event::FootnoteReference(label) => {
+                let number = self.footnote_number(&label);
+                self.push_text(&format!("[{}]", number), false);
+            }
+            Event::SoftBreak => self.push_text(" ", false),
+            Event::HardBreak => self.push_text("\n", false),
+            Event::Rule => {
+                self.flush_paragraph();
+                self.blocks.push(Block::Rule);
+            }
+            Event::TaskListMarker(checked) => {
+                if self.marker.is_some() {
+                    self.marker = Some(Marker::Task(checked));
+                }
+            }
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => self.flush_paragraph(),
+            Tag::Heading { level, .. } => {
+                self.flush_paragraph();
+                self.heading = Some(level as u8);
+            }
+            Tag::BlockQuote(_) => {
+                self.flush_paragraph();
+                self.quote_depth += 1;
+            }
+            Tag::CodeBlock(_) => {
+                self.flush_paragraph();
+                self.code_block = Some(String::new());
+            }
+            Tag::HtmlBlock => {
+                self.flush_paragraph();
+                self.html_block = Some(String::new());
+            }
+            Tag::List(start) => {
+                self.flush_paragraph();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.flush_paragraph();
+                self.marker = Some(match self.lists.last_mut() {
+                    Some(Some(next)) => {
+                        *next += 1;
+                        Marker::Number(*next - 1)
+                    }
+                    _ => Marker::Bullet,
+                });
+            }
+            Tag::FootnoteDefinition(label) => {
+                self.flush_paragraph();
+                let number = self.footnote_number(&label);
+                self.footnotes.push(Footnote {
+                    number,
+                    blocks: Vec::new(),
+                });
+                self.body = Some(std::mem::take(&mut self.blocks));
+            }
+            Tag::Table(alignments) => {
+                self.flush_paragraph();
+                self.table = Some(PendingTable {
+                    alignments,
+                    rows: Vec::new(),
+                    header_rows: 0,
+                });
+            }
+            Tag::TableHead | Tag::TableRow => {
+                self.table_head = matches!(tag, Tag::TableHead);
+                if let Some(table) = &mut self.table {
+                    table.rows.push(Vec::new());
+                }
+            }
+            Tag::TableCell => self.spans.clear(),
+            Tag::Emphasis => self.italic += 1,
+            Tag::Strong => self.bold += 1,
+            Tag::Strikethrough => self.strike += 1,
+            Tag::Link { dest_url, .. } => self.links.push(dest_url.to_string()),
+            Tag::Image { dest_url, .. } => self.image = Some((dest_url.to_string(), String::new())),
+            Tag::MetadataBlock(_) => self.metadata = true,
+            Tag::Superscript
+            | Tag::Subscript
+            | Tag::DefinitionList
+            | Tag::DefinitionListTitle
+            | Tag::DefinitionListDefinition => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph | TagEnd::Item => self.flush_paragraph(),
+            TagEnd::DefinitionListTitle | TagEnd::DefinitionListDefinition => self.flush_paragraph(),
+            TagEnd::Heading(_) => {
+                let spans = std::mem::take(&mut self.spans);
+                let level = self.heading.take().unwrap_or(1);
+                self.blocks.push(Block::Heading { level, spans });
+            }
+            TagEnd::BlockQuote(_) => {
+                self.flush_paragraph();
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+            }
+            TagEnd::HtmlBlock => {
+                let html = self.html_block.take().unwrap_or_default();
+                for comment in html_comments(&html) {
+                    self.blocks.push(Block::Comment(comment));
+                }
+            }
+            TagEnd::List(_) => {
+                self.flush_paragraph();
+                self.lists.pop();
+            }
+            TagEnd::FootnoteDefinition => {
+                self.flush_paragraph();
+                let blocks = std::mem::replace(&mut self.blocks, self.body.take().unwrap_or_default());
+                if let Some(last) = self.footnotes.last_mut() {
+                    last.blocks = blocks;
+                }
+            }
+            TagEnd::Table => {
+                if let Some(table) = self.table.take() {
+                    let context = self.context();
+                    self.blocks.push(Block::Table {
+                        alignments: table.alignments,
+                        rows: table.rows,
+                        header_rows: table.header_rows,
+                        context,
+                    });
+                }
+            }
+            TagEnd::TableHead => {
+                self.table_head = false;
+                if let Some(table) = &mut self.table {
+                    table.header_rows = table.rows.len();
+                }
+            }
+            TagEnd::TableCell => {
+                let spans = std::mem::take(&mut self.spans);
+                if let Some(row) = self.table.as_mut().and_then(|t| t.rows.last_mut()) {
+                    row.push(spans);
+                }
+            }
+            TagEnd::Emphasis => self.italic = self.italic.saturating_sub(1),
+            TagEnd::Strong => self.bold = self.bold.saturating_sub(1),
+            TagEnd::Strikethrough => self.strike = self.strike.saturating_sub(1),
+            TagEnd::Link => {
+                self.links.pop();
+            }
+            TagEnd::TableRow
+            | TagEnd::CodeBlock
+            | TagEnd::Image
+            | TagEnd::Superscript
+            | TagEnd::Subscript
+            | TagEnd::DefinitionList
+            | TagEnd::MetadataBlock(_) => {}
+        }
+    }
+
+    fn context(&self) -> Context {
+        Context {
+            list_depth: self.lists.len(),
+            quote_depth: self.quote_depth,
+        }
+    }
+
+    fn push_text(&mut self, text: &str, code: bool) {
+        let span = Span {
+            text: text.to_string(),
+            bold: self.bold > 0 || self.table_head,
+            italic: self.italic > 0,
+            code,
+            strike: self.strike > 0,
+            link: self.links.last().cloned(),
+        };
+
+        // Merge with the previous span when the formatting is the same
+        if let Some(last) = self.spans.last_mut() {
+            if last.bold == span.bold
+                && last.italic == span.italic
+                && last.code == span.code
+                && last.strike == span.strike
+                && last.link == span.link
+            {
+                last.text.push_str(&span.text);
+                return;
+            }
+        }
+        self.spans.push(span);
+    }
+
+    // Images that stand alone become image blocks; inside headings and
+    // table cells only the alt text is kept.
+    fn end_image(&mut self) {
+        let Some((url, alt)) = self.image.take() else {
+            return;
+        };
+        if self.heading.is_some() || self.table.is_some() {
+            self.push_text(&alt, false);
+            return;
+        }
+        self.flush_paragraph();
+        let context = self.context();
+        self.blocks.push(Block::Image { url, alt, context });
+    }
+
+    fn flush_paragraph(&mut self) {
+        if self.heading.is_some() || self.table.is_some() {
+            return;
+        }
+        if self.spans.iter().all(|s| s.text.trim().is_empty()) {
+            self.spans.clear();
+            return;
+        }
+        let spans = std::mem::take(&mut self.spans);
+        let marker = self.marker.take();
+        let context = self.context();
+        self.blocks.push(Block::Paragraph {
+            spans,
+            marker,
+            context,
+        });
+    }
+
+    fn footnote_number(&mut self, label: &str) -> u32 {
+        let next = self.footnote_numbers.len() as u32 + 1;
+        *self.footnote_numbers.entry(label.to_string()).or_insert(next)
+    }
+
+    fn finish(mut self) -> Document {
+        self.flush_paragraph();
+        let mut footnotes = self.footnotes;
+        footnotes.sort_by_key(|f| f.number);
+        Document {
+            blocks: self.blocks,
+            footnotes,
+        }
+    }
+}
+
+// Contents of every <!-- ... --> comment in a block of HTML
+fn html_comments(html: &str) -> Vec<String> {
+    let mut comments = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find("<!--") {
+        let after = &rest[start + 4..];
+        let Some(end) = after.find("-->") else {
+            break;
+        };
+        comments.push(after[..end].trim().to_string());
+        rest = &after[end + 3..];
+    }
+    comments
+}
+
+// Anchor id for a heading, as GitHub renders them: lowercase, spaces to
+// dashes, punctuation dropped
+pub fn slug(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+// Heading slugs made unique: repeated headings get -1, -2, ... suffixes
+#[derive(Default)]
+pub struct HeadingIds {
+    seen: HashMap<String, usize>,
+}
+
+impl HeadingIds {
+    pub fn next(&mut self, text: &str) -> String {
+        let base = slug(text);
+        let count = self.seen.entry(base.clone()).or_insert(0);
+        let id = if *count == 0 { base.clone() } else { format!("{}-{}", base, count) };
+        *count += 1;
+        id
+    }
+}
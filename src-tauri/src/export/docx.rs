@@ -0,0 +1,913 @@
+use pulldown_cmark::{Alignment, Event, HeadingLevel, Parser, Tag, TagEnd};
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::document::HeadingIds;
+use super::media::{self, ImageFormat};
+use super::ooxml::{self, xml_escape, Package};
+
+// Text width of a Letter page with 1" margins, in twips (1/20 pt) and EMUs
+const TEXT_WIDTH_TWIPS: u32 = 9360;
+const TEXT_WIDTH_EMU: u64 = 5_943_600;
+const EMU_PER_PIXEL: u64 = 9525;
+
+// Indent per list level, in twips
+const LIST_INDENT_TWIPS: u32 = 720;
+
+const BULLET_LIST: u32 = 0;
+const ORDERED_LIST: u32 = 1;
+
+// Fixed relationship ids; hyperlinks and images are numbered after these
+const STYLES_REL: &str = "rId1";
+const NUMBERING_REL: &str = "rId2";
+const FOOTNOTES_REL: &str = "rId3";
+const SETTINGS_REL: &str = "rId4";
+const FIRST_DYNAMIC_REL: usize = 5;
+
+// Convert markdown to a .docx file. Images are resolved relative to `base_dir`.
+pub fn markdown_to_docx(markdown: &str, base_dir: &Path, title: &str) -> Result<Vec<u8>, String> {
+    let mut writer = DocxWriter::new(base_dir);
+    for event in Parser::new_ext(markdown, super::markdown_options()) {
+        writer.event(event);
+    }
+    writer.finish(title)
+}
+
+struct ListLevel {
+    num_id: u32,
+}
+
+struct PendingImage {
+    url: String,
+    alt: String,
+}
+
+// A heading being written, bookmarked once its text is known so that
+// "#heading" links resolve as they do in HTML and PDF exports
+struct PendingHeading {
+    // Where the bookmark starts in `out`, just after the paragraph properties
+    start: usize,
+    text: String,
+    // An explicit {#id}, kept as the HTML export keeps it
+    id: Option<String>,
+}
+
+struct Relationship {
+    id: String,
+    kind: &'static str,
+    target: String,
+    external: bool,
+}
+
+struct DocxWriter<'a> {
+    base_dir: &'a Path,
+    // Where paragraphs are currently written: the body, or a footnote
+    out: String,
+    body: Option<String>,
+    paragraph_open: bool,
+
+    bold: u32,
+    italic: u32,
+    strike: u32,
+    superscript: u32,
+    subscript: u32,
+    link: u32,
+
+    quote_depth: u32,
+    lists: Vec<ListLevel>,
+    // The next paragraph is the first of a list item and gets the number/bullet
+    item_start: bool,
+    code_block: Option<String>,
+    image: Option<PendingImage>,
+    heading: Option<PendingHeading>,
+    heading_ids: HeadingIds,
+    bookmark_id: u32,
+    metadata: bool,
+
+    table_alignments: Vec<Alignment>,
+    table_cell: usize,
+
+    footnote_first_paragraph: bool,
+    footnote_ids: HashMap<String, u32>,
+    footnotes: Vec<(u32, String)>,
+
+    relationships: Vec<Relationship>,
+    media: Vec<(String, ImageFormat, Vec<u8>)>,
+    image_rels: HashMap<String, String>,
+    // (numId, abstractNumId, level, start) for every list in the document
+    numbering: Vec<(u32, u32, usize, u64)>,
+    drawing_id: u32,
+}
+
+impl<'a> DocxWriter<'a> {
+    fn new(base_dir: &'a Path) -> Self {
+        Self {
+            base_dir,
+            out: String::new(),
+            body: None,
+            paragraph_open: false,
+            bold: 0,
+            italic: 0,
+            strike: 0,
+            superscript: 0,
+            subscript: 0,
+            link: 0,
+            quote_depth: 0,
+            lists: Vec::new(),
+            item_start: false,
+            code_block: None,
+            image: None,
+            heading: None,
+            heading_ids: HeadingIds::default(),
+            bookmark_id: 0,
+            metadata: false,
+            table_alignments: Vec::new(),
+            table_cell: 0,
+            footnote_first_paragraph: false,
+            footnote_ids: HashMap::new(),
+            footnotes: Vec::new(),
+            relationships: Vec::new(),
+            media: Vec::new(),
+            image_rels: HashMap::new(),
+            numbering: Vec::new(),
+            drawing_id: 0,
+        }
+    }
+
+    fn event(&mut self, event: Event) {
+        if self.metadata {
+            if let Event::End(TagEnd::MetadataBlock(_)) = event {
+                self.metadata = false;
+            }
+            return;
+        }
+
+        if let Some(image) = &mut self.image {
+            match event {
+                Event::Text(text) | Event::Code(text) => image.alt.push_str(&text),
+                Event::End(TagEnd::Image) => self.end_image(),
+                _ => {}
+            }
+            return;
+        }
+
+        if let Some(code) = &mut self.code_block {
+            match event {
+                Event::Text(text) => code.push_str(&text),
+                Event::End(TagEnd::CodeBlock) => self.end_code_block(),
+                _ => {}
+            }
+            return;
+        }
+
+        if let (Some(heading), Event::Text(text) | Event::Code(text)) = (&mut self.heading, &event) {
+            heading.text.push_str(text);
+        }
+
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => self.text_run(&text, false),
+            Event::Code(text) => self.text_run(&text, true),
+            Event::InlineMath(text) | Event::DisplayMath(text) => self.text_run(&text, true),
+            Event::Html(html) | Event::InlineHtml(html) => {
+                let tag = html.trim().to_lowercase();
+                if matches!(tag.as_str(), "<br>" | "<br/>" | "<br />") {
+                    self.ensure_paragraph();
+                    self.out.push_str("<w:r><w:br/></w:r>");
+                }
+            }
+            Event::FootnoteReference(label) => {
+                let id = self.footnote_id(&label);
+                self.ensure_paragraph();
+                self.out.push_str(&format!(
+                    r#"<w:r><w:rPr><w:rStyle w:val="FootnoteReference"/></w:rPr><w:footnoteReference w:id="{}"/></w:r>"#,
+                    id
+                ));
+            }
+            Event::SoftBreak => self.text_run(" ", false),
+            Event::HardBreak => {
+                self.ensure_paragraph();
+                self.out.push_str("<w:r><w:br/></w:r>");
+            }
+            Event::Rule => {
+                self.close_paragraph();
+                self.out.push_str(
+                    r#"<w:p><w:pPr><w:pBdr><w:bottom w:val="single" w:sz="6" w:space="1" w:color="auto"/></w:pBdr></w:pPr></w:p>"#,
+                );
+            }
+            Event::TaskListMarker(checked) => {
+                self.text_run(if checked { "\u{2612} " } else { "\u{2610} " }, false)
+            }
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => {
+                self.close_paragraph();
+                self.open_paragraph(None, None);
+            }
+            Tag::Heading { level, id, .. } => {
+                self.close_paragraph();
+                let style = format!("Heading{}", heading_number(level));
+                self.open_paragraph(Some(&style), None);
+                self.heading = Some(PendingHeading {
+                    start: self.out.len(),
+                    text: String::new(),
+                    id: id.map(|id| id.to_string()),
+                });
+            }
+            Tag::BlockQuote(_) => {
+                self.close_paragraph();
+                self.quote_depth += 1;
+            }
+            Tag::CodeBlock(_) => {
+                self.close_paragraph();
+                self.code_block = Some(String::new());
+            }
+            Tag::List(start) => {
+                self.close_paragraph();
+                let num_id = self.numbering.len() as u32 + 1;
+                let level = self.lists.len().min(8);
+                match start {
+                    Some(start) => self.numbering.push((num_id, ORDERED_LIST, level, start)),
+                    None => self.numbering.push((num_id, BULLET_LIST, level, 1)),
+                }
+                self.lists.push(ListLevel { num_id });
+            }
+            Tag::Item => {
+                self.close_paragraph();
+                self.item_start = true;
+            }
+            Tag::FootnoteDefinition(label) => {
+                self.close_paragraph();
+                let id = self.footnote_id(&label);
+                self.footnotes.push((id, String::new()));
+                self.body = Some(std::mem::take(&mut self.out));
+                self.footnote_first_paragraph = true;
+            }
+            Tag::Table(alignments) => {
+                self.close_paragraph();
+                let columns = alignments.len().max(1) as u32;
+                self.out.push_str(
+                    r#"<w:tbl><w:tblPr><w:tblStyle w:val="TableGrid"/><w:tblW w:w="0" w:type="auto"/><w:tblLook w:val="04A0" w:firstRow="1" w:lastRow="0" w:firstColumn="0" w:lastColumn="0" w:noHBand="0" w:noVBand="1"/></w:tblPr><w:tblGrid>"#,
+                );
+                for _ in 0..columns {
+                    self.out.push_str(&format!(r#"<w:gridCol w:w="{}"/>"#, TEXT_WIDTH_TWIPS / columns));
+                }
+                self.out.push_str("</w:tblGrid>");
+                self.table_alignments = alignments;
+            }
+            Tag::TableHead => {
+                self.out.push_str("<w:tr><w:trPr><w:tblHeader/></w:trPr>");
+                self.table_cell = 0;
+                self.bold += 1;
+            }
+            Tag::TableRow => {
+                self.out.push_str("<w:tr>");
+                self.table_cell = 0;
+            }
+            Tag::TableCell => {
+                let columns = self.table_alignments.len().max(1) as u32;
+                self.out.push_str(&format!(
+                    r#"<w:tc><w:tcPr><w:tcW w:w="{}" w:type="dxa"/></w:tcPr>"#,
+                    TEXT_WIDTH_TWIPS / columns
+                ));
+                let align = match self.table_alignments.get(self.table_cell) {
+                    Some(Alignment::Center) => Some("center"),
+                    Some(Alignment::Right) => Some("right"),
+                    _ => None,
+                };
+                self.open_paragraph(None, align);
+            }
+            Tag::Emphasis => self.italic += 1,
+            Tag::Strong => self.bold += 1,
+            Tag::Strikethrough => self.strike += 1,
+            Tag::Superscript => self.superscript += 1,
+            Tag::Subscript => self.subscript += 1,
+            Tag::Link { dest_url, .. } => {
+                self.ensure_paragraph();
+                if let Some(anchor) = dest_url.strip_prefix('#') {
+                    self.out
+                        .push_str(&format!(r#"<w:hyperlink w:anchor="{}">"#, xml_escape(anchor)));
+                } else {
+                    let id = self.add_relationship(
+                        "http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink",
+                        dest_url.to_string(),
+                        true,
+                    );
+                    self.out.push_str(&format!(r#"<w:hyperlink r:id="{}">"#, id));
+                }
+                self.link += 1;
+            }
+            Tag::Image { dest_url, .. } => {
+                self.image = Some(PendingImage {
+                    url: dest_url.to_string(),
+                    alt: String::new(),
+                });
+            }
+            Tag::MetadataBlock(_) => self.metadata = true,
+            Tag::HtmlBlock
+            | Tag::DefinitionList
+            | Tag::DefinitionListTitle
+            | Tag::DefinitionListDefinition => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Heading(_) => {
+                self.end_heading();
+                self.close_paragraph();
+            }
+            TagEnd::Paragraph | TagEnd::Item => self.close_paragraph(),
+            TagEnd::DefinitionListTitle | TagEnd::DefinitionListDefinition => self.close_paragraph(),
+            TagEnd::BlockQuote(_) => {
+                self.close_paragraph();
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+            }
+            TagEnd::List(_) => {
+                self.close_paragraph();
+                self.lists.pop();
+            }
+            TagEnd::FootnoteDefinition => {
+                if self.footnote_first_paragraph {
+                    // Empty definition: still needs the footnote mark
+                    self.open_paragraph(None, None);
+                }
+                self.close_paragraph();
+                let content = std::mem::replace(&mut self.out, self.body.take().unwrap_or_default());
+                if let Some(last) = self.footnotes.last_mut() {
+                    last.1 = content;
+                }
+            }
+            TagEnd::Table => {
+                self.out.push_str("</w:tbl>");
+                // Keeps consecutive tables from merging into one in Word
+                self.out.push_str("<w:p/>");
+                self.table_alignments.clear();
+            }
+            TagEnd::TableHead => {
+                self.out.push_str("</w:tr>");
+                self.bold = self.bold.saturating_sub(1);
+            }
+            TagEnd::TableRow => self.out.push_str("</w:tr>"),
+            TagEnd::TableCell => {
+                self.ensure_paragraph();
+                self.close_paragraph();
+                self.out.push_str("</w:tc>");
+                self.table_cell += 1;
+            }
+            TagEnd::Emphasis => self.italic = self.italic.saturating_sub(1),
+            TagEnd::Strong => self.bold = self.bold.saturating_sub(1),
+            TagEnd::Strikethrough => self.strike = self.strike.saturating_sub(1),
+            TagEnd::Superscript => self.superscript = self.superscript.saturating_sub(1),
+            TagEnd::Subscript => self.subscript = self.subscript.saturating_sub(1),
+            TagEnd::Link => {
+                self.out.push_str("</w:hyperlink>");
+                self.link = self.link.saturating_sub(1);
+            }
+            TagEnd::CodeBlock
+            | TagEnd::Image
+            | TagEnd::HtmlBlock
+            | TagEnd::DefinitionList
+            | TagEnd::MetadataBlock(_) => {}
+        }
+    }
+
+    fn open_paragraph(&mut self, style: Option<&str>, align: Option<&str>) {
+        let in_footnote = self.body.is_some();
+        let style = style.or(if in_footnote {
+            Some("FootnoteText")
+        } else if self.quote_depth > 0 {
+            Some("Quote")
+        } else if !self.lists.is_empty() {
+            Some("ListParagraph")
+        } else {
+            None
+        });
+
+        let mut ppr = String::new();
+        if let Some(style) = style {
+            ppr.push_str(&format!(r#"<w:pStyle w:val="{}"/>"#, style));
+        }
+        if let Some(list) = self.lists.last() {
+            let level = self.lists.len() - 1;
+            if self.item_start {
+                ppr.push_str(&format!(
+                    r#"<w:numPr><w:ilvl w:val="{}"/><w:numId w:val="{}"/></w:numPr>"#,
+                    level.min(8),
+                    list.num_id
+                ));
+            } else {
+                // Continuation paragraph of an item: align with the item text
+                ppr.push_str(&format!(
+                    r#"<w:ind w:left="{}"/>"#,
+                    LIST_INDENT_TWIPS * (level as u32 + 1)
+                ));
+            }
+        }
+        if let Some(align) = align {
+            ppr.push_str(&format!(r#"<w:jc w:val="{}"/>"#, align));
+        }
+        self.item_start = false;
+
+        self.out.push_str("<w:p>");
+        if !ppr.is_empty() {
+            self.out.push_str(&format!("<w:pPr>{}</w:pPr>", ppr));
+        }
+        if in_footnote && self.footnote_first_paragraph {
+            self.out.push_str(
+                r#"<w:r><w:rPr><w:rStyle w:val="FootnoteReference"/></w:rPr><w:footnoteRef/></w:r><w:r><w:t xml:space="preserve"> </w:t></w:r>"#,
+            );
+            self.footnote_first_paragraph = false;
+        }
+        self.paragraph_open = true;
+    }
+
+    fn ensure_paragraph(&mut self) {
+        if !self.paragraph_open {
+            self.open_paragraph(None, None);
+        }
+    }
+
+    fn end_heading(&mut self) {
+        let Some(heading) = self.heading.take() else {
+            return;
+        };
+        let name = heading
+            .id
+            .unwrap_or_else(|| self.heading_ids.next(heading.text.trim()));
+        let id = self.bookmark_id;
+        self.bookmark_id += 1;
+        self.out.insert_str(
+            heading.start,
+            &format!(r#"<w:bookmarkStart w:id="{}" w:name="{}"/>"#, id, xml_escape(&name)),
+        );
+        self.out.push_str(&format!(r#"<w:bookmarkEnd w:id="{}"/>"#, id));
+    }
+
+    fn close_paragraph(&mut self) {
+        if self.paragraph_open {
+            self.out.push_str("</w:p>");
+            self.paragraph_open = false;
+        }
+    }
+
+    fn run_properties(&self, code: bool) -> String {
+        let mut rpr = String::new();
+        if self.link > 0 {
+            rpr.push_str(r#"<w:rStyle w:val="Hyperlink"/>"#);
+        } else if code {
+            rpr.push_str(r#"<w:rStyle w:val="VerbatimChar"/>"#);
+        }
+        if self.bold > 0 {
+            rpr.push_str("<w:b/>");
+        }
+        if self.italic > 0 {
+            rpr.push_str("<w:i/>");
+        }
+        if self.strike > 0 {
+            rpr.push_str("<w:strike/>");
+        }
+        if self.superscript > 0 {
+            rpr.push_str(r#"<w:vertAlign w:val="superscript"/>"#);
+        } else if self.subscript > 0 {
+            rpr.push_str(r#"<w:vertAlign w:val="subscript"/>"#);
+        }
+        rpr
+    }
+
+    fn text_run(&mut self, text: &str, code: bool) {
+        if text.is_empty() {
+            return;
+        }
+        self.ensure_paragraph();
+        let rpr = self.run_properties(code);
+        self.out.push_str("<w:r>");
+        if !rpr.is_empty() {
+            self.out.push_str(&format!("<w:rPr>{}</w:rPr>", rpr));
+        }
+        self.out
+            .push_str(&format!(r#"<w:t xml:space="preserve">{}</w:t></w:r>"#, xml_escape(text)));
+    }
+
+    fn end_code_block(&mut self) {
+        let code = self.code_block.take().unwrap_or_default();
+        self.open_paragraph(Some("SourceCode"), None);
+        for (i, line) in code.trim_end_matches('\n').split('\n').enumerate() {
+            if i > 0 {
+                self.out.push_str("<w:r><w:br/></w:r>");
+            }
+            self.text_run(line, false);
+        }
+        self.close_paragraph();
+    }
+
+    fn end_image(&mut self) {
+        let Some(image) = self.image.take() else { return };
+
+        let embedded = self.embed_image(&image.url);
+        let Some((rel_id, name, width_px, height_px)) = embedded else {
+            // Remote or unsupported image: keep the alt text so nothing is silently lost
+            self.italic += 1;
+            let alt = if image.alt.is_empty() { image.url.clone() } else { image.alt.clone() };
+            self.text_run(&format!("[{}]", alt), false);
+            self.italic -= 1;
+            return;
+        };
+
+        // Scale down to the text width, keeping the aspect ratio
+        let mut cx = width_px as u64 * EMU_PER_PIXEL;
+        let mut cy = height_px as u64 * EMU_PER_PIXEL;
+        if cx > TEXT_WIDTH_EMU {
+            cy = cy * TEXT_WIDTH_EMU / cx;
+            cx = TEXT_WIDTH_EMU;
+        }
+
+        self.drawing_id += 1;
+        self.ensure_paragraph();
+        self.out.push_str(&format!(
+            concat!(
+                r#"<w:r><w:drawing><wp:inline distT="0" distB="0" distL="0" distR="0">"#,
+                r#"<wp:extent cx="{cx}" cy="{cy}"/><wp:docPr id="{id}" name="Picture {id}" descr="{alt}"/>"#,
+                r#"<wp:cNvGraphicFramePr><a:graphicFrameLocks noChangeAspect="1"/></wp:cNvGraphicFramePr>"#,
+                r#"<a:graphic><a:graphicData uri="http://schemas.openxmlformats.org/drawingml/2006/picture">"#,
+                r#"<pic:pic><pic:nvPicPr><pic:cNvPr id="0" name="{name}"/><pic:cNvPicPr/></pic:nvPicPr>"#,
+                r#"<pic:blipFill><a:blip r:embed="{rel}"/><a:stretch><a:fillRect/></a:stretch></pic:blipFill>"#,
+                r#"<pic:spPr><a:xfrm><a:off x="0" y="0"/><a:ext cx="{cx}" cy="{cy}"/></a:xfrm>"#,
+                r#"<a:prstGeom prst="rect"><a:avLst/></a:prstGeom></pic:spPr></pic:pic>"#,
+                r#"</a:graphicData></a:graphic></wp:inline></w:drawing></w:r>"#
+            ),
+            cx = cx,
+            cy = cy,
+            id = self.drawing_id,
+            alt = xml_escape(&image.alt),
+            name = name,
+            rel = rel_id,
+        ));
+    }
+
+    // Add an image to word/media (once per file) and return its relationship id,
+    // media name and pixel size
+    fn embed_image(&mut self, url: &str) -> Option<(String, String, u32, u32)> {
+        let image = media::load_image(self.base_dir, url)?;
+        // Word cannot display WebP or SVG without a raster fallback
+        if matches!(image.format, ImageFormat::Webp | ImageFormat::Svg) {
+            return None;
+        }
+        let (width, height) = image.size?;
+
+        let key = image.path.to_string_lossy().to_string();
+        if let Some(rel_id) = self.image_rels.get(&key) {
+            let name = self
+                .relationships
+                .iter()
+                .find(|r| &r.id == rel_id)
+                .map(|r| r.target.trim_start_matches("media/").to_string())
+                .unwrap_or_default();
+            return Some((rel_id.clone(), name, width, height));
+        }
+
+        let name = format!("image{}.{}", self.media.len() + 1, image.format.extension());
+        let rel_id = self.add_relationship(
+            "http://schemas.openxmlformats.org/officeDocument/2006/relationships/image",
+            format!("media/{}", name),
+            false,
+        );
+        self.media.push((name.clone(), image.format, image.data));
+        self.image_rels.insert(key, rel_id.clone());
+        Some((rel_id, name, width, height))
+    }
+
+    fn add_relationship(&mut self, kind: &'static str, target: String, external: bool) -> String {
+        let id = format!("rId{}", FIRST_DYNAMIC_REL + self.relationships.len());
+        self.relationships.push(Relationship {
+            id: id.clone(),
+            kind,
+            target,
+            external,
+        });
+        id
+    }
+
+    // Footnote ids are assigned in order of first mention; 0 and -1 are
+    // reserved for Word's separator footnotes
+    fn footnote_id(&mut self, label: &str) -> u32 {
+        let next = self.footnote_ids.len() as u32 + 1;
+        *self.footnote_ids.entry(label.to_string()).or_insert(next)
+    }
+
+    fn finish(mut self, title: &str) -> Result<Vec<u8>, String> {
+        self.close_paragraph();
+        if let Some(body) = self.body.take() {
+            self.out = body;
+        }
+
+        // A reference without a definition would make Word refuse the file
+        let mut labels: Vec<(String, u32)> = self.footnote_ids.iter().map(|(l, id)| (l.clone(), *id)).collect();
+        labels.sort_by_key(|(_, id)| *id);
+        for (label, id) in labels {
+            if !self.footnotes.iter().any(|(fid, _)| *fid == id) {
+                self.footnotes.push((
+                    id,
+                    format!(
+                        r#"<w:p><w:pPr><w:pStyle w:val="FootnoteText"/></w:pPr><w:r><w:rPr><w:rStyle w:val="FootnoteReference"/></w:rPr><w:footnoteRef/></w:r><w:r><w:t xml:space="preserve"> {}</w:t></w:r></w:p>"#,
+                        xml_escape(&label)
+                    ),
+                ));
+            }
+        }
+        self.footnotes.sort_by_key(|(id, _)| *id);
+
+        let mut package = Package::new();
+        package.add("[Content_Types].xml", self.content_types().as_bytes())?;
+        package.add("_rels/.rels", ROOT_RELS.as_bytes())?;
+        package.add("docProps/core.xml", ooxml::core_properties(title).as_bytes())?;
+        package.add("word/document.xml", self.document().as_bytes())?;
+        package.add("word/styles.xml", STYLES.as_bytes())?;
+        package.add("word/settings.xml", SETTINGS.as_bytes())?;
+        package.add("word/numbering.xml", self.numbering_xml().as_bytes())?;
+        package.add("word/footnotes.xml", self.footnotes_xml().as_bytes())?;
+        package.add("word/_rels/document.xml.rels", self.document_rels().as_bytes())?;
+        for (name, _, data) in &self.media {
+            package.add(&format!("word/media/{}", name), data)?;
+        }
+        package.finish()
+    }
+
+    fn document(&self) -> String {
+        format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                "\n",
+                r#"<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" "#,
+                r#"xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships" "#,
+                r#"xmlns:wp="http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing" "#,
+                r#"xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" "#,
+                r#"xmlns:pic="http://schemas.openxmlformats.org/drawingml/2006/picture">"#,
+                "<w:body>{}",
+                r#"<w:sectPr><w:footnotePr><w:numFmt w:val="decimal"/></w:footnotePr>"#,
+                r#"<w:pgSz w:w="12240" w:h="15840"/>"#,
+                r#"<w:pgMar w:top="1440" w:right="1440" w:bottom="1440" w:left="1440" w:header="720" w:footer="720" w:gutter="0"/>"#,
+                "</w:sectPr></w:body></w:document>"
+            ),
+            self.out
+        )
+    }
+
+    fn content_types(&self) -> String {
+        let mut defaults = String::new();
+        let mut seen: Vec<ImageFormat> = Vec::new();
+        for (_, format, _) in &self.media {
+            if !seen.contains(format) {
+                seen.push(*format);
+                defaults.push_str(&format!(
+                    r#"<Default Extension="{}" ContentType="{}"/>"#,
+                    format.extension(),
+                    format.mime_type()
+                ));
+            }
+        }
+
+        format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                "\n",
+                r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">"#,
+                r#"<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>"#,
+                r#"<Default Extension="xml" ContentType="application/xml"/>{}"#,
+                r#"<Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/>"#,
+                r#"<Override PartName="/word/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/>"#,
+                r#"<Override PartName="/word/settings.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.settings+xml"/>"#,
+                r#"<Override PartName="/word/numbering.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.numbering+xml"/>"#,
+                r#"<Override PartName="/word/footnotes.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.footnotes+xml"/>"#,
+                r#"<Override PartName="/docProps/core.xml" ContentType="application/vnd.openxmlformats-package.core-properties+xml"/>"#,
+                "</Types>"
+            ),
+            defaults
+        )
+    }
+
+    fn document_rels(&self) -> String {
+        let mut rels = format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                "\n",
+                r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+                r#"<Relationship Id="{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/>"#,
+                r#"<Relationship Id="{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/numbering" Target="numbering.xml"/>"#,
+                r#"<Relationship Id="{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/footnotes" Target="footnotes.xml"/>"#,
+                r#"<Relationship Id="{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/settings" Target="settings.xml"/>"#
+            ),
+            STYLES_REL, NUMBERING_REL, FOOTNOTES_REL, SETTINGS_REL
+        );
+        for rel in &self.relationships {
+            rels.push_str(&format!(
+                r#"<Relationship Id="{}" Type="{}" Target="{}"{}/>"#,
+                rel.id,
+                rel.kind,
+                xml_escape(&rel.target),
+                if rel.external { r#" TargetMode="External""# } else { "" }
+            ));
+        }
+        rels.push_str("</Relationships>");
+        rels
+    }
+
+    fn numbering_xml(&self) -> String {
+        let mut xml = String::from(concat!(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+            "\n",
+            r#"<w:numbering xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">"#
+        ));
+
+        for abstract_id in [BULLET_LIST, ORDERED_LIST] {
+            xml.push_str(&format!(
+                r#"<w:abstractNum w:abstractNumId="{}"><w:multiLevelType w:val="hybridMultilevel"/>"#,
+                abstract_id
+            ));
+            for level in 0..9u32 {
+                let (format, text) = if abstract_id == BULLET_LIST {
+                    ("bullet", ["\u{2022}", "\u{25E6}", "\u{25AA}"][level as usize % 3].to_string())
+                } else {
+                    let format = ["decimal", "lowerLetter", "lowerRoman"][level as usize % 3];
+                    (format, format!("%{}.", level + 1))
+                };
+                xml.push_str(&format!(
+                    concat!(
+                        r#"<w:lvl w:ilvl="{}"><w:start w:val="1"/><w:numFmt w:val="{}"/><w:lvlText w:val="{}"/>"#,
+                        r#"<w:lvlJc w:val="left"/><w:pPr><w:ind w:left="{}" w:hanging="360"/></w:pPr></w:lvl>"#
+                    ),
+                    level,
+                    format,
+                    text,
+                    LIST_INDENT_TWIPS * (level + 1)
+                ));
+            }
+            xml.push_str("</w:abstractNum>");
+        }
+
+        // One w:num per markdown list, so every ordered list restarts at its own start number
+        for (num_id, abstract_id, level, start) in &self.numbering {
+            xml.push_str(&format!(
+                r#"<w:num w:numId="{}"><w:abstractNumId w:val="{}"/><w:lvlOverride w:ilvl="{}"><w:startOverride w:val="{}"/></w:lvlOverride></w:num>"#,
+                num_id, abstract_id, level, start
+            ));
+        }
+
+        xml.push_str("</w:numbering>");
+        xml
+    }
+
+    fn footnotes_xml(&self) -> String {
+        let mut xml = String::from(concat!(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+            "\n",
+            r#"<w:footnotes xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" "#,
+            r#"xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships" "#,
+            r#"xmlns:wp="http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing" "#,
+            r#"xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" "#,
+            r#"xmlns:pic="http://schemas.openxmlformats.org/drawingml/2006/picture">"#,
+            r#"<w:footnote w:type="separator" w:id="-1"><w:p><w:pPr><w:spacing w:after="0" w:line="240" w:lineRule="auto"/></w:pPr><w:r><w:separator/></w:r></w:p></w:footnote>"#,
+            r#"<w:footnote w:type="continuationSeparator" w:id="0"><w:p><w:pPr><w:spacing w:after="0" w:line="240" w:lineRule="auto"/></w:pPr><w:r><w:continuationSeparator/></w:r></w:p></w:footnote>"#
+        ));
+        for (id, content) in &self.footnotes {
+            xml.push_str(&format!(r#"<w:footnote w:id="{}">{}</w:footnote>"#, id, content));
+        }
+        xml.push_str("</w:footnotes>");
+        xml
+    }
+}
+
+fn heading_number(level: HeadingLevel) -> u8 {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+const ROOT_RELS: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    "\n",
+    r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+    r#"<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/>"#,
+    r#"<Relationship Id="rId2" Type="http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties" Target="docProps/core.xml"/>"#,
+    "</Relationships>"
+);
+
+const SETTINGS: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    "\n",
+    r#"<w:settings xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">"#,
+    r#"<w:defaultTabStop w:val="720"/><w:characterSpacingControl w:val="doNotCompress"/>"#,
+    r#"<w:footnotePr><w:footnote w:id="-1"/><w:footnote w:id="0"/></w:footnotePr>"#,
+    "</w:settings>"
+);
+
+const STYLES: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    "\n",
+    r#"<w:styles xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">"#,
+    r#"<w:docDefaults><w:rPrDefault><w:rPr><w:rFonts w:ascii="Calibri" w:hAnsi="Calibri" w:eastAsia="Calibri" w:cs="Calibri"/><w:sz w:val="22"/><w:szCs w:val="22"/><w:lang w:val="en-US"/></w:rPr></w:rPrDefault>"#,
+    r#"<w:pPrDefault><w:pPr><w:spacing w:after="160" w:line="276" w:lineRule="auto"/></w:pPr></w:pPrDefault></w:docDefaults>"#,
+    r#"<w:style w:type="paragraph" w:default="1" w:styleId="Normal"><w:name w:val="Normal"/><w:qFormat/></w:style>"#,
+    r#"<w:style w:type="paragraph" w:styleId="Heading1"><w:name w:val="heading 1"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="480" w:after="120"/><w:outlineLvl w:val="0"/></w:pPr><w:rPr><w:b/><w:sz w:val="36"/><w:szCs w:val="36"/></w:rPr></w:style>"#,
+    r#"<w:style w:type="paragraph" w:styleId="Heading2"><w:name w:val="heading 2"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="360" w:after="120"/><w:outlineLvl w:val="1"/></w:pPr><w:rPr><w:b/><w:sz w:val="30"/><w:szCs w:val="30"/></w:rPr></w:style>"#,
+    r#"<w:style w:type="paragraph" w:styleId="Heading3"><w:name w:val="heading 3"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="280" w:after="80"/><w:outlineLvl w:val="2"/></w:pPr><w:rPr><w:b/><w:sz w:val="26"/><w:szCs w:val="26"/></w:rPr></w:style>"#,
+    r#"<w:style w:type="paragraph" w:styleId="Heading4"><w:name w:val="heading 4"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="240" w:after="80"/><w:outlineLvl w:val="3"/></w:pPr><w:rPr><w:b/><w:i/><w:sz w:val="24"/><w:szCs w:val="24"/></w:rPr></w:style>"#,
+    r#"<w:style w:type="paragraph" w:styleId="Heading5"><w:name w:val="heading 5"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="200" w:after="40"/><w:outlineLvl w:val="4"/></w:pPr><w:rPr><w:b/><w:sz w:val="22"/><w:szCs w:val="22"/></w:rPr></w:style>"#,
+    r#"<w:style w:type="paragraph" w:styleId="Heading6"><w:name w:val="heading 6"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="200" w:after="40"/><w:outlineLvl w:val="5"/></w:pPr><w:rPr><w:i/><w:sz w:val="22"/><w:szCs w:val="22"/></w:rPr></w:style>"#,
+    r#"<w:style w:type="paragraph" w:styleId="Quote"><w:name w:val="Quote"/><w:basedOn w:val="Normal"/><w:qFormat/><w:pPr><w:pBdr><w:left w:val="single" w:sz="18" w:space="8" w:color="C7C7C7"/></w:pBdr><w:ind w:left="720"/></w:pPr><w:rPr><w:i/><w:color w:val="555555"/></w:rPr></w:style>"#,
+    r#"<w:style w:type="paragraph" w:styleId="ListParagraph"><w:name w:val="List Paragraph"/><w:basedOn w:val="Normal"/><w:qFormat/><w:pPr><w:spacing w:after="60"/><w:contextualSpacing/></w:pPr></w:style>"#,
+    r#"<w:style w:type="paragraph" w:styleId="SourceCode"><w:name w:val="Source Code"/><w:basedOn w:val="Normal"/><w:pPr><w:shd w:val="clear" w:color="auto" w:fill="F4F4F4"/><w:spacing w:after="160" w:line="240" w:lineRule="auto"/></w:pPr><w:rPr><w:rFonts w:ascii="Consolas" w:hAnsi="Consolas" w:cs="Consolas"/><w:sz w:val="19"/><w:szCs w:val="19"/></w:rPr></w:style>"#,
+    r#"<w:style w:type="paragraph" w:styleId="FootnoteText"><w:name w:val="footnote text"/><w:basedOn w:val="Normal"/><w:pPr><w:spacing w:after="0" w:line="240" w:lineRule="auto"/></w:pPr><w:rPr><w:sz w:val="18"/><w:szCs w:val="18"/></w:rPr></w:style>"#,
+    r#"<w:style w:type="character" w:default="1" w:styleId="DefaultParagraphFont"><w:name w:val="Default Paragraph Font"/><w:uiPriority w:val="1"/><w:semiHidden/></w:style>"#,
+    r#"<w:style w:type="character" w:styleId="VerbatimChar"><w:name w:val="Verbatim Char"/><w:rPr><w:rFonts w:ascii="Consolas" w:hAnsi="Consolas" w:cs="Consolas"/><w:sz w:val="20"/><w:shd w:val="clear" w:color="auto" w:fill="F4F4F4"/></w:rPr></w:style>"#,
+    r#"<w:style w:type="character" w:styleId="Hyperlink"><w:name w:val="Hyperlink"/><w:rPr><w:color w:val="4F46E5"/><w:u w:val="single"/></w:rPr></w:style>"#,
+    r#"<w:style w:type="character" w:styleId="FootnoteReference"><w:name w:val="footnote reference"/><w:rPr><w:vertAlign w:val="superscript"/></w:rPr></w:style>"#,
+    r#"<w:style w:type="table" w:default="1" w:styleId="TableNormal"><w:name w:val="Normal Table"/><w:tblPr><w:tblInd w:w="0" w:type="dxa"/><w:tblCellMar><w:top w:w="0" w:type="dxa"/><w:left w:w="108" w:type="dxa"/><w:bottom w:w="0" w:type="dxa"/><w:right w:w="108" w:type="dxa"/></w:tblCellMar></w:tblPr></w:style>"#,
+    r#"<w:style w:type="table" w:styleId="TableGrid"><w:name w:val="Table Grid"/><w:basedOn w:val="TableNormal"/><w:pPr><w:spacing w:after="0" w:line="240" w:lineRule="auto"/></w:pPr><w:tblPr><w:tblBorders><w:top w:val="single" w:sz="4" w:space="0" w:color="auto"/><w:left w:val="single" w:sz="4" w:space="0" w:color="auto"/><w:bottom w:val="single" w:sz="4" w:space="0" w:color="auto"/><w:right w:val="single" w:sz="4" w:space="0" w:color="auto"/><w:insideH w:val="single" w:sz="4" w:space="0" w:color="auto"/><w:insideV w:val="single" w:sz="4" w:space="0" w:color="auto"/></w:tblBorders></w:tblPr></w:style>"#,
+    "</w:styles>"
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents::{read_docx, DocxContent, PackageReader};
+    use std::fs;
+
+    // Export markdown, then read it back along with some raw package parts
+    fn round_trip(name: &str, markdown: &str, parts: &[&str]) -> (DocxContent, Vec<String>) {
+        let bytes = markdown_to_docx(markdown, Path::new("."), "Test").unwrap();
+        let path = std::env::temp_dir().join(format!("clause-export-{}-{}.docx", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        let content = read_docx(&path).unwrap();
+        let mut package = PackageReader::open(&path).unwrap();
+        let parts = parts.iter().map(|part| package.part(part).unwrap().unwrap()).collect();
+        fs::remove_file(&path).unwrap();
+        (content, parts)
+    }
+
+    fn ids<'a>(xml: &'a roxmltree::Document, element: &str) -> Vec<&'a str> {
+        xml.descendants()
+            .filter(|n| n.tag_name().name() == element)
+            .filter_map(|n| n.attributes().find(|a| a.name() == "id").map(|a| a.value()))
+            .collect()
+    }
+
+    #[test]
+    fn footnotes_are_numbered_from_one() {
+        let markdown = "Text[^a] and more[^b], again[^a].\n\n[^b]: Second.\n[^a]: First.\n";
+        let (content, parts) = round_trip("footnotes", markdown, &["word/document.xml", "word/footnotes.xml"]);
+        assert_eq!(content.markdown, "Text and more, again.\n");
+
+        // 0 and -1 are Word's separators; ours follow in order of reference
+        let document = roxmltree::Document::parse(&parts[0]).unwrap();
+        assert_eq!(ids(&document, "footnoteReference"), ["1", "2", "1"]);
+        let footnotes = roxmltree::Document::parse(&parts[1]).unwrap();
+        assert_eq!(ids(&footnotes, "footnote"), ["-1", "0", "1", "2"]);
+        let text: Vec<String> = footnotes
+            .descendants()
+            .filter(|n| n.tag_name().name() == "footnote")
+            .map(|n| n.descendants().filter(|t| t.is_text()).filter_map(|t| t.text()).collect::<String>().trim().to_string())
+            .collect();
+        assert_eq!(text[2..], ["First.", "Second."]);
+    }
+
+    #[test]
+    fn lists_keep_their_numbering() {
+        let markdown = "1. one\n2. two\n   - nested\n3. three\n\nBetween\n\n5. five\n6. six\n";
+        let (content, parts) = round_trip("lists", markdown, &["word/numbering.xml"]);
+        assert_eq!(
+            content.markdown,
+            "1. one\n2. two\n  - nested\n3. three\n\nBetween\n\n1. five\n2. six\n"
+        );
+
+        // Each list gets its own numbering, so the second one starts at 5
+        let numbering = roxmltree::Document::parse(&parts[0]).unwrap();
+        let starts: Vec<&str> = numbering
+            .descendants()
+            .filter(|n| n.tag_name().name() == "startOverride")
+            .filter_map(|n| n.attributes().find(|a| a.name() == "val").map(|a| a.value()))
+            .collect();
+        assert_eq!(starts, ["1", "1", "5"]);
+    }
+
+    #[test]
+    fn tables_round_trip() {
+        let markdown = "| Name | Value |\n|---|:-:|\n| a | 1 |\n| *b* | |\n";
+        let (content, _) = round_trip("tables", markdown, &[]);
+        assert_eq!(
+            content.markdown,
+            "| **Name** | **Value** |\n| --- | --- |\n| a | 1 |\n| *b* |  |\n"
+        );
+        let cells: Vec<&str> = content.paragraphs.iter().map(|p| p.text.as_str()).collect();
+        assert_eq!(cells[..6], ["Name", "Value", "a", "1", "b", ""]);
+    }
+}
@@ -0,0 +1,245 @@
+use base64::Engine;
+use pulldown_cmark::{html, CowStr, Event, HeadingLevel, Parser, Tag, TagEnd};
+use std::path::Path;
+
+use super::document::HeadingIds;
+use super::media;
+use super::ExportOptions;
+
+const STYLESHEET: &str = r#"
+:root { color-scheme: light; }
+body { margin: 0; background: #fff; color: #1a1a1a; font: 16px/1.6 -apple-system, BlinkMacSystemFont, "Segoe UI", Helvetica, Arial, sans-serif; }
+main { max-width: 46em; margin: 0 auto; padding: 3em 1.5em; }
+h1, h2, h3, h4, h5, h6 { line-height: 1.25; margin: 1.6em 0 0.6em; }
+h1 { font-size: 2em; } h2 { font-size: 1.5em; } h3 { font-size: 1.25em; }
+h1, h2 { padding-bottom: 0.3em; border-bottom: 1px solid #e5e5e5; }
+p, ul, ol, blockquote, pre, table { margin: 0 0 1em; }
+a { color: #0b5cad; }
+img { max-width: 100%; height: auto; }
+code { font: 0.9em/1.4 ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; background: #f3f3f3; padding: 0.1em 0.3em; border-radius: 3px; }
+pre { background: #f3f3f3; padding: 0.9em 1em; border-radius: 4px; overflow-x: auto; }
+pre code { background: none; padding: 0; }
+blockquote { margin-left: 0; padding: 0 1em; color: #555; border-left: 4px solid #ddd; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #d0d0d0; padding: 0.35em 0.7em; }
+th { background: #f3f3f3; }
+hr { border: 0; border-top: 1px solid #d0d0d0; margin: 2em 0; }
+li > input[type=checkbox] { margin-right: 0.4em; }
+.footnote-definition { font-size: 0.9em; color: #444; }
+.footnote-definition p { display: inline; }
+nav.toc { margin: 0 0 2em; padding: 1em 1.5em; background: #fafafa; border: 1px solid #e5e5e5; border-radius: 4px; }
+nav.toc h2 { margin-top: 0; border: 0; font-size: 1.1em; }
+nav.toc ul { list-style: none; margin: 0; padding-left: 0; }
+nav.toc ul ul { padding-left: 1.2em; }
+@media print {
+  main { max-width: none; padding: 0; }
+  a { color: inherit; }
+  pre, blockquote, table, img { page-break-inside: avoid; }
+  h1, h2, h3 { page-break-after: avoid; }
+}
+"#;
+
+struct TocEntry {
+    level: u8,
+    id: String,
+    text: String,
+}
+
+// Convert markdown to a single HTML file with the stylesheet inlined and
+// local images embedded as data: URLs, so it can be opened anywhere.
+pub fn markdown_to_html(markdown: &str, base_dir: &Path, title: &str, options: &ExportOptions) -> String {
+    let mut events: Vec<Event> = Parser::new_ext(markdown, super::markdown_options()).collect();
+    let toc = assign_heading_ids(&mut events);
+
+    // Front matter is metadata, not content
+    let mut in_metadata = false;
+    events.retain(|event| match event {
+        Event::Start(Tag::MetadataBlock(_)) => {
+            in_metadata = true;
+            false
+        }
+        Event::End(TagEnd::MetadataBlock(_)) => {
+            in_metadata = false;
+            false
+        }
+        _ => !in_metadata,
+    });
+
+    for event in &mut events {
+        if let Event::Start(Tag::Image { dest_url, .. }) = event {
+            if let Some(image) = media::load_image(base_dir, dest_url) {
+                let encoded = base64::engine::general_purpose::STANDARD.encode(&image.data);
+                let data_url = format!("data:{};base64,{}", image.format.mime_type(), encoded);
+                *dest_url = CowStr::from(data_url);
+            }
+        }
+    }
+
+    let mut body = String::with_capacity(markdown.len() * 2);
+    if options.toc && !toc.is_empty() {
+        body.push_str(&render_toc(&toc));
+    }
+    html::push_html(&mut body, events.into_iter());
+
+    let (width, height) = options.page_size.dimensions();
+    let margins = &options.margins;
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="generator" content="Clause">
+<title>{}</title>
+<style>{}@page {{ size: {:.0}pt {:.0}pt; margin: {}mm {}mm {}mm {}mm; }}
+</style>
+</head>
+<body>
+<main>
+{}</main>
+</body>
+</html>
+"#,
+        escape(title),
+        STYLESHEET,
+        width,
+        height,
+        margins.top,
+        margins.right,
+        margins.bottom,
+        margins.left,
+        body
+    )
+}
+
+// Give every heading an id (keeping explicit {#id} attributes) and
+// collect the entries for the table of contents
+fn assign_heading_ids(events: &mut [Event]) -> Vec<TocEntry> {
+    let mut ids = HeadingIds::default();
+    let mut entries = Vec::new();
+
+    let mut index = 0;
+    while index < events.len() {
+        let Event::Start(Tag::Heading { level, .. }) = &events[index] else {
+            index += 1;
+            continue;
+        };
+        let level = *level;
+
+        let mut text = String::new();
+        let mut end = index + 1;
+        while end < events.len() {
+            match &events[end] {
+                Event::End(TagEnd::Heading(_)) => break,
+                Event::Text(t) | Event::Code(t) => text.push_str(t),
+                _ => {}
+            }
+            end += 1;
+        }
+        let text = text.trim().to_string();
+
+        if let Event::Start(Tag::Heading { id, .. }) = &mut events[index] {
+            let heading_id = match id {
+                Some(existing) => existing.to_string(),
+                None => {
+                    let generated = ids.next(&text);
+                    *id = Some(CowStr::from(generated.clone()));
+                    generated
+                }
+            };
+            if level <= HeadingLevel::H3 {
+                entries.push(TocEntry {
+                    level: level as u8,
+                    id: heading_id,
+                    text,
+                });
+            }
+        }
+        index = end;
+    }
+
+    entries
+}
+
+// Nested list of links to the headings
+fn render_toc(entries: &[TocEntry]) -> String {
+    let base = entries.iter().map(|e| e.level).min().unwrap_or(1);
+    let mut out = String::from("<nav class=\"toc\">\n<h2>Contents</h2>\n<ul>\n");
+    let mut depth = base;
+    let mut first = true;
+
+    for entry in entries {
+        let level = entry.level.max(base);
+        if first {
+            first = false;
+        } else if level > depth {
+            out.push('\n');
+        } else {
+            out.push_str("</li>\n");
+        }
+        while depth < level {
+            out.push_str("<ul>\n<li>");
+            depth += 1;
+        }
+        while depth > level {
+            out.push_str("</ul>\n</li>\n");
+            depth -= 1;
+        }
+        if !out.ends_with("<li>") {
+            out.push_str("<li>");
+        }
+        out.push_str(&format!("<a href=\"#{}\">{}</a>", escape(&entry.id), escape(&entry.text)));
+    }
+
+    out.push_str("</li>\n");
+    while depth > base {
+        out.push_str("</ul>\n</li>\n");
+        depth -= 1;
+    }
+    out.push_str("</ul>\n</nav>\n");
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn embeds_local_images() {
+        let dir = std::env::temp_dir().join(format!("clause-html-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        image::RgbImage::new(2, 2).save(dir.join("dot.png")).unwrap();
+
+        let markdown = "![Dot](dot.png)\n\n![Missing](missing.png)\n\n![Remote](https://example.com/a.png)\n";
+        let html = markdown_to_html(markdown, &dir, "Images", &ExportOptions::default());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(html.contains(r#"<img src="data:image/png;base64,iVBORw0KGgo"#));
+        assert!(html.contains(r#"<img src="missing.png" alt="Missing" />"#));
+        assert!(html.contains(r#"<img src="https://example.com/a.png" alt="Remote" />"#));
+    }
+
+    #[test]
+    fn contents_link_to_the_headings() {
+        let markdown = "---\ntitle: Hidden\n---\n\n# A & B\n\n## Part\n\n## Part\n";
+        let options = ExportOptions {
+            toc: true,
+            ..ExportOptions::default()
+        };
+        let html = markdown_to_html(markdown, Path::new("."), "Doc", &options);
+
+        assert!(!html.contains("Hidden"));
+        assert!(html.contains(r##"<a href="#a--b">A &amp; B</a>"##));
+        assert!(html.contains(r##"<a href="#part">Part</a>"##));
+        assert!(html.contains(r##"<a href="#part-1">Part</a>"##));
+        assert!(html.contains(r#"<h2 id="part-1">Part</h2>"#));
+    }
+}
@@ -0,0 +1,153 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Bmp,
+    Webp,
+    Svg,
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpeg",
+            ImageFormat::Gif => "gif",
+            ImageFormat::Bmp => "bmp",
+            ImageFormat::Webp => "webp",
+            ImageFormat::Svg => "svg",
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Gif => "image/gif",
+            ImageFormat::Bmp => "image/bmp",
+            ImageFormat::Webp => "image/webp",
+            ImageFormat::Svg => "image/svg+xml",
+        }
+    }
+}
+
+pub struct Image {
+    pub path: PathBuf,
+    pub data: Vec<u8>,
+    pub format: ImageFormat,
+    // Pixel size, when it could be read from the file header
+    pub size: Option<(u32, u32)>,
+}
+
+// Load an image referenced from markdown. Only local files are supported;
+// remote and data: URLs return None so the caller can fall back to alt text.
+pub fn load_image(base_dir: &Path, url: &str) -> Option<Image> {
+    if (url.contains("://") && !url.starts_with("file://")) || url.starts_with("data:") {
+        return None;
+    }
+
+    let url = url.strip_prefix("file://").unwrap_or(url);
+    let decoded = percent_decode(url.split(['?', '#']).next().unwrap_or(url));
+    let path = base_dir.join(decoded);
+
+    let data = fs::read(&path).ok()?;
+    let format = detect_format(&data)?;
+    let size = dimensions(&data, format);
+
+    Some(Image {
+        path,
+        data,
+        format,
+        size,
+    })
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 3 <= bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+pub fn detect_format(data: &[u8]) -> Option<ImageFormat> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(ImageFormat::Png)
+    } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(ImageFormat::Jpeg)
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some(ImageFormat::Gif)
+    } else if data.starts_with(b"BM") {
+        Some(ImageFormat::Bmp)
+    } else if data.len() > 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some(ImageFormat::Webp)
+    } else {
+        let head = String::from_utf8_lossy(&data[..data.len().min(1024)]);
+        if head.contains("<svg") {
+            Some(ImageFormat::Svg)
+        } else {
+            None
+        }
+    }
+}
+
+// Read the pixel size from the image header without decoding the image
+pub fn dimensions(data: &[u8], format: ImageFormat) -> Option<(u32, u32)> {
+    let be16 = |i: usize| -> Option<u32> { Some(u16::from_be_bytes([*data.get(i)?, *data.get(i + 1)?]) as u32) };
+    let le16 = |i: usize| -> Option<u32> { Some(u16::from_le_bytes([*data.get(i)?, *data.get(i + 1)?]) as u32) };
+    let be32 = |i: usize| -> Option<u32> { Some(u32::from_be_bytes(data.get(i..i + 4)?.try_into().ok()?)) };
+    let le32 = |i: usize| -> Option<u32> { Some(u32::from_le_bytes(data.get(i..i + 4)?.try_into().ok()?)) };
+    let le24 = |i: usize| -> Option<u32> { Some(le32(i)? & 0x00FF_FFFF) };
+
+    let size = match format {
+        ImageFormat::Png => (be32(16)?, be32(20)?),
+        ImageFormat::Gif => (le16(6)?, le16(8)?),
+        ImageFormat::Bmp => (le32(18)?, (le32(22)? as i32).unsigned_abs()),
+        ImageFormat::Jpeg => {
+            // Walk the segments until a start-of-frame marker
+            let mut i = 2;
+            loop {
+                if *data.get(i)? != 0xFF {
+                    return None;
+                }
+                let marker = *data.get(i + 1)?;
+                let is_sof = (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+                if is_sof {
+                    break (be16(i + 7)?, be16(i + 5)?);
+                }
+                i += 2 + be16(i + 2)? as usize;
+            }
+        }
+        ImageFormat::Webp => match data.get(12..16)? {
+            b"VP8 " => (le16(26)? & 0x3FFF, le16(28)? & 0x3FFF),
+            b"VP8L" => {
+                let bits = le32(21)?;
+                ((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1)
+            }
+            b"VP8X" => (le24(24)? + 1, le24(27)? + 1),
+            _ => return None,
+        },
+        ImageFormat::Svg => return None,
+    };
+
+    if size.0 == 0 || size.1 == 0 {
+        None
+    } else {
+        Some(size)
+    }
+}
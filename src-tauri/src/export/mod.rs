@@ -0,0 +1,273 @@
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use tauri::{AppHandle, Emitter};
+
+use crate::error::AppError;
+use crate::files::{self, FileContent};
+
+mod document;
+pub mod docx;
+mod html;
+pub mod media;
+pub mod ooxml;
+pub mod pdf;
+pub mod pptx;
+
+pub use document::slug;
+
+// Folder (next to the exported document) where exports are written
+const EXPORT_DIR: &str = "exports";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Docx,
+    Html,
+    Pdf,
+    Pptx,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Docx => "docx",
+            ExportFormat::Html => "html",
+            ExportFormat::Pdf => "pdf",
+            ExportFormat::Pptx => "pptx",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageSize {
+    A4,
+    #[default]
+    Letter,
+    Legal,
+}
+
+impl PageSize {
+    // Width and height in points
+    pub fn dimensions(self) -> (f32, f32) {
+        match self {
+            PageSize::A4 => (595.28, 841.89),
+            PageSize::Letter => (612.0, 792.0),
+            PageSize::Legal => (612.0, 1008.0),
+        }
+    }
+}
+
+// How a slide deck is cut into slides
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlideBreakStrategy {
+    // H1 starts a section divider, H2 a new slide
+    #[default]
+    Headings,
+    // Slides are separated by horizontal rules (---)
+    Rules,
+    // Either of the above starts a new slide
+    Both,
+}
+
+// Page margins in millimetres
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Margins {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+impl Default for Margins {
+    fn default() -> Self {
+        Self {
+            top: 25.4,
+            right: 25.4,
+            bottom: 25.4,
+            left: 25.4,
+        }
+    }
+}
+
+// Layout options for paginated formats. Header and footer text may use
+// {title}, {page} and {pages} placeholders. `slide_break_strategy` only
+// applies to slide decks.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ExportOptions {
+    pub page_size: PageSize,
+    pub margins: Margins,
+    pub header: Option<String>,
+    pub footer: Option<String>,
+    pub toc: bool,
+    pub slide_break_strategy: SlideBreakStrategy,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportProgressEvent {
+    pub path: String,
+    pub format: ExportFormat,
+    pub stage: String,
+    // 0.0 to 1.0
+    pub progress: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportCompleteEvent {
+    pub path: String,
+    pub format: ExportFormat,
+    pub output_path: Option<String>,
+    pub error: Option<String>,
+    // Problems that did not stop the export, e.g. text the format cannot show
+    pub warnings: Vec<String>,
+}
+
+// CommonMark plus the GFM extensions writers actually use
+fn markdown_options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+}
+
+// Plain text of the first H1, used as the document title
+fn first_heading(markdown: &str) -> Option<String> {
+    let mut title: Option<String> = None;
+    for event in Parser::new_ext(markdown, markdown_options()) {
+        match (&mut title, event) {
+            (None, Event::Start(Tag::Heading { level: pulldown_cmark::HeadingLevel::H1, .. })) => {
+                title = Some(String::new());
+            }
+            (Some(t), Event::Text(text) | Event::Code(text)) => t.push_str(&text),
+            (Some(t), Event::End(TagEnd::Heading(_))) => {
+                let t = t.trim().to_string();
+                return if t.is_empty() { None } else { Some(t) };
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// Convert a markdown file and write the result to exports/<name>.<ext>,
+// replacing any earlier export of the same file. Returns the output path.
+// Progress is reported with export-progress events and the outcome with
+// an export-complete event.
+#[tauri::command]
+pub async fn export_document(
+    path: String,
+    format: ExportFormat,
+    options: Option<ExportOptions>,
+    app_handle: AppHandle,
+) -> Result<String, AppError> {
+    let options = options.unwrap_or_default();
+    let progress = |stage: &str, progress: f32| {
+        let event = ExportProgressEvent {
+            path: path.clone(),
+            format,
+            stage: stage.to_string(),
+            progress,
+        };
+        let _ = app_handle.emit("export-progress", event);
+    };
+
+    let result = export(&path, format, &options, &progress);
+
+    let event = ExportCompleteEvent {
+        path: path.clone(),
+        format,
+        output_path: result.as_ref().ok().map(|(output_path, _)| output_path.clone()),
+        error: result.as_ref().err().map(|e| e.to_string()),
+        warnings: result.as_ref().map(|(_, warnings)| warnings.clone()).unwrap_or_default(),
+    };
+    let _ = app_handle.emit("export-complete", event);
+
+    result.map(|(output_path, _)| output_path)
+}
+
+fn export(
+    path: &str,
+    format: ExportFormat,
+    options: &ExportOptions,
+    progress: &dyn Fn(&str, f32),
+) -> Result<(String, Vec<String>), AppError> {
+    let source = Path::new(path);
+
+    if !source.exists() {
+        return Err(AppError::NotFound { path: path.to_string() });
+    }
+
+    if !source.is_file() {
+        return Err(AppError::NotAFile { path: path.to_string() });
+    }
+
+    progress("reading", 0.0);
+    let bytes = fs::read(source).map_err(|e| AppError::io("Failed to read file", source, e))?;
+    let markdown = match files::decode(source, &bytes) {
+        FileContent::Text { content, .. } => content,
+        _ => return Err(AppError::invalid(format!("Cannot export binary file: {}", path))),
+    };
+    let base_dir = source.parent().unwrap_or(Path::new("."));
+    let stem = source
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "document".to_string());
+    let title = first_heading(&markdown).unwrap_or_else(|| stem.clone());
+
+    progress("rendering", 0.2);
+    let mut warnings = Vec::new();
+    let bytes = match format {
+        ExportFormat::Docx => docx::markdown_to_docx(&markdown, base_dir, &title)?,
+        ExportFormat::Html => html::markdown_to_html(&markdown, base_dir, &title, options).into_bytes(),
+        ExportFormat::Pdf => {
+            let (bytes, pdf_warnings) = pdf::markdown_to_pdf(&markdown, base_dir, &title, options)?;
+            warnings = pdf_warnings;
+            bytes
+        }
+        ExportFormat::Pptx => pptx::markdown_to_pptx(&markdown, base_dir, &title, options.slide_break_strategy)?,
+    };
+
+    progress("writing", 0.9);
+    let export_dir = base_dir.join(EXPORT_DIR);
+    fs::create_dir_all(&export_dir).map_err(|e| AppError::io("Failed to create export directory", &export_dir, e))?;
+
+    let output_path = export_dir.join(format!("{}.{}", stem, format.extension()));
+    fs::write(&output_path, bytes).map_err(|e| AppError::io("Failed to write export", &output_path, e))?;
+
+    progress("done", 1.0);
+    Ok((output_path.to_string_lossy().to_string(), warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_legacy_encoded_sources() {
+        let dir = std::env::temp_dir().join(format!("clause-export-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("notes.md");
+        // "# Café" in windows-1252
+        fs::write(&source, b"# Caf\xe9\r\n\r\nText\r\n").unwrap();
+
+        let (output, warnings) = export(source.to_str().unwrap(), ExportFormat::Html, &ExportOptions::default(), &|_, _| {}).unwrap();
+        let html = fs::read_to_string(&output).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(warnings.is_empty());
+        assert!(output.ends_with("notes.html"));
+        assert!(html.contains("<title>Caf\u{e9}</title>"));
+    }
+
+    #[test]
+    fn finds_the_title() {
+        assert_eq!(first_heading("Intro\n\n# The `main` title\n\n# Second\n").as_deref(), Some("The main title"));
+        assert_eq!(first_heading("## Only a subheading\n"), None);
+    }
+}
@@ -0,0 +1,66 @@
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipWriter};
+
+// Zip container for Office Open XML files (.docx, .pptx).
+// Every entry gets the same fixed timestamp and permissions so that
+// exporting the same markdown twice produces byte-identical files.
+pub struct Package {
+    zip: ZipWriter<Cursor<Vec<u8>>>,
+}
+
+impl Package {
+    pub fn new() -> Self {
+        Self {
+            zip: ZipWriter::new(Cursor::new(Vec::new())),
+        }
+    }
+
+    pub fn add(&mut self, name: &str, data: &[u8]) -> Result<(), String> {
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .last_modified_time(DateTime::default())
+            .unix_permissions(0o644);
+
+        self.zip
+            .start_file(name, options)
+            .map_err(|e| format!("Failed to add {} to package: {}", name, e))?;
+        self.zip
+            .write_all(data)
+            .map_err(|e| format!("Failed to add {} to package: {}", name, e))
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>, String> {
+        self.zip
+            .finish()
+            .map(|cursor| cursor.into_inner())
+            .map_err(|e| format!("Failed to finish package: {}", e))
+    }
+}
+
+// Escape text for use in XML content and attribute values
+pub fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            // Control characters other than tab/newline are not allowed in XML 1.0
+            c if c.is_control() && c != '\t' && c != '\n' && c != '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// docProps/core.xml with just a title; no timestamps, to keep exports deterministic
+pub fn core_properties(title: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:dcterms="http://purl.org/dc/terms/" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"><dc:title>{}</dc:title><dc:creator>Clause</dc:creator></cp:coreProperties>"#,
+        xml_escape(title)
+    )
+}
@@ -0,0 +1,188 @@
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+
+use super::super::media::{Image, ImageFormat};
+use super::writer::deflate;
+
+// Image data ready to be written as an image XObject
+pub struct PdfImage {
+    pub width: u32,
+    pub height: u32,
+    pub color_space: &'static str,
+    // Extra dictionary entries, e.g. /Filter /DCTDecode
+    pub filter: &'static str,
+    pub data: Vec<u8>,
+    // Deflated 8-bit alpha channel, written as a soft mask
+    pub alpha: Option<Vec<u8>>,
+}
+
+// JPEG data is embedded as is; PNG is decoded and recompressed so alpha can
+// become a soft mask. Other formats return None and the caller falls back
+// to the alt text.
+pub fn prepare(image: &Image) -> Option<PdfImage> {
+    match image.format {
+        ImageFormat::Jpeg => jpeg(&image.data),
+        ImageFormat::Png => png(&image.data),
+        _ => None,
+    }
+}
+
+fn jpeg(data: &[u8]) -> Option<PdfImage> {
+    let be16 = |i: usize| -> Option<u32> { Some(u16::from_be_bytes([*data.get(i)?, *data.get(i + 1)?]) as u32) };
+
+    let mut i = 2;
+    let (width, height, components) = loop {
+        if *data.get(i)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(i + 1)?;
+        let is_sof = (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+        if is_sof {
+            break (be16(i + 7)?, be16(i + 5)?, *data.get(i + 9)?);
+        }
+        i += 2 + be16(i + 2)? as usize;
+    };
+
+    let (color_space, filter) = match components {
+        1 => ("/DeviceGray", "/Filter /DCTDecode"),
+        3 => ("/DeviceRGB", "/Filter /DCTDecode"),
+        // Adobe writes CMYK JPEGs inverted
+        4 => ("/DeviceCMYK", "/Filter /DCTDecode /Decode [1 0 1 0 1 0 1 0]"),
+        _ => return None,
+    };
+
+    Some(PdfImage {
+        width,
+        height,
+        color_space,
+        filter,
+        data: data.to_vec(),
+        alpha: None,
+    })
+}
+
+// Decode an 8-bit, non-interlaced PNG
+fn png(data: &[u8]) -> Option<PdfImage> {
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut compressed = Vec::new();
+
+    let mut i = 8;
+    while i + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[i..i + 4].try_into().ok()?) as usize;
+        let kind = &data[i + 4..i + 8];
+        let body = data.get(i + 8..i + 8 + length)?;
+        match kind {
+            b"IHDR" => header = Some(body),
+            b"PLTE" => palette = body,
+            b"tRNS" => transparency = body,
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        i += 12 + length;
+    }
+
+    let header = header?;
+    let width = u32::from_be_bytes(header.get(0..4)?.try_into().ok()?);
+    let height = u32::from_be_bytes(header.get(4..8)?.try_into().ok()?);
+    let (bit_depth, color_type, interlace) = (*header.get(8)?, *header.get(9)?, *header.get(12)?);
+    if bit_depth != 8 || interlace != 0 || width == 0 || height == 0 {
+        return None;
+    }
+
+    let channels = match color_type {
+        0 | 3 => 1,
+        4 => 2,
+        2 => 3,
+        6 => 4,
+        _ => return None,
+    };
+
+    let mut raw = Vec::new();
+    ZlibDecoder::new(compressed.as_slice()).read_to_end(&mut raw).ok()?;
+    let pixels = unfilter(&raw, width as usize, height as usize, channels)?;
+
+    let mut color = Vec::with_capacity(pixels.len());
+    let mut alpha = Vec::new();
+    for pixel in pixels.chunks_exact(channels) {
+        match color_type {
+            0 => color.push(pixel[0]),
+            2 => color.extend_from_slice(pixel),
+            3 => {
+                let index = pixel[0] as usize;
+                color.extend_from_slice(palette.get(index * 3..index * 3 + 3)?);
+                alpha.push(transparency.get(index).copied().unwrap_or(255));
+            }
+            4 => {
+                color.push(pixel[0]);
+                alpha.push(pixel[1]);
+            }
+            _ => {
+                color.extend_from_slice(&pixel[..3]);
+                alpha.push(pixel[3]);
+            }
+        }
+    }
+
+    // Skip the soft mask when the image is fully opaque
+    let alpha = if alpha.iter().all(|&a| a == 255) {
+        None
+    } else {
+        Some(deflate(&alpha))
+    };
+
+    Some(PdfImage {
+        width,
+        height,
+        color_space: if matches!(color_type, 0 | 4) { "/DeviceGray" } else { "/DeviceRGB" },
+        filter: "/Filter /FlateDecode",
+        data: deflate(&color),
+        alpha,
+    })
+}
+
+// Undo the per-row PNG filters
+fn unfilter(raw: &[u8], width: usize, height: usize, channels: usize) -> Option<Vec<u8>> {
+    let stride = width * channels;
+    let mut out = vec![0u8; stride * height];
+
+    for row in 0..height {
+        let start = row * (stride + 1);
+        let filter = *raw.get(start)?;
+        let line = raw.get(start + 1..start + 1 + stride)?;
+        let (done, rest) = out.split_at_mut(row * stride);
+        let previous = if row > 0 { &done[(row - 1) * stride..] } else { &[][..] };
+        let current = &mut rest[..stride];
+
+        for x in 0..stride {
+            let a = if x >= channels { current[x - channels] } else { 0 };
+            let b = previous.get(x).copied().unwrap_or(0);
+            let c = if x >= channels { previous.get(x - channels).copied().unwrap_or(0) } else { 0 };
+            let predictor = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return None,
+            };
+            current[x] = line[x].wrapping_add(predictor);
+        }
+    }
+
+    Some(out)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
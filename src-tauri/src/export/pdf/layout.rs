@@ -0,0 +1,827 @@
+use pulldown_cmark::Alignment;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use super::super::document::{plain_text, Block, Context, Footnote, HeadingIds, Marker, Span};
+use super::super::media;
+use super::fonts::{encode, unsupported, Font};
+use super::image::{self, PdfImage};
+use super::writer::{literal_string, num};
+
+// Sizes in points
+const BODY_SIZE: f32 = 10.5;
+const FOOTNOTE_SIZE: f32 = 8.5;
+const CODE_SIZE: f32 = 9.0;
+const TABLE_SIZE: f32 = 9.5;
+const HEADING_SIZES: [f32; 6] = [20.0, 16.0, 13.5, 12.0, 11.0, 10.5];
+const LINE_SPACING: f32 = 1.45;
+const LIST_INDENT: f32 = 18.0;
+const QUOTE_INDENT: f32 = 14.0;
+const CELL_PADDING: f32 = 4.0;
+const CODE_PADDING: f32 = 5.0;
+// Images are assumed to be 96 dpi
+const POINTS_PER_PIXEL: f32 = 0.75;
+
+const TEXT_COLOR: &str = "0.1 0.1 0.1";
+const MUTED_COLOR: &str = "0.4 0.4 0.4";
+const LINK_COLOR: &str = "0.05 0.35 0.75";
+
+// Page size and margins in points
+#[derive(Debug, Clone, Copy)]
+pub struct Geometry {
+    pub width: f32,
+    pub height: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+impl Geometry {
+    fn content_width(&self) -> f32 {
+        self.width - self.left - self.right
+    }
+
+    fn content_top(&self) -> f32 {
+        self.height - self.top
+    }
+}
+
+pub enum LinkTarget {
+    Uri(String),
+    // #fragment of a heading in the same document
+    Anchor(String),
+    Heading(usize),
+}
+
+pub struct Link {
+    pub rect: [f32; 4],
+    pub target: LinkTarget,
+}
+
+#[derive(Default)]
+pub struct Page {
+    pub content: String,
+    pub links: Vec<Link>,
+    // Indices into Layout::images used on this page
+    pub images: Vec<usize>,
+}
+
+pub struct HeadingEntry {
+    pub level: u8,
+    pub text: String,
+    pub id: String,
+    pub page: usize,
+    // Top of the heading on its page
+    pub y: f32,
+}
+
+#[derive(Clone)]
+struct Piece {
+    text: String,
+    font: Font,
+    size: f32,
+    width: f32,
+    link: Option<String>,
+    strike: bool,
+}
+
+impl Piece {
+    fn same_style(&self, other: &Piece) -> bool {
+        self.font == other.font && self.size == other.size && self.link == other.link && self.strike == other.strike
+    }
+}
+
+enum Token {
+    // Pieces with no break opportunity between them, e.g. "**bold**,"
+    Word(Vec<Piece>),
+    Space(Piece),
+    Newline,
+}
+
+#[derive(Default)]
+struct Line {
+    pieces: Vec<Piece>,
+    width: f32,
+}
+
+impl Line {
+    fn push(&mut self, piece: Piece) {
+        self.width += piece.width;
+        match self.pieces.last_mut() {
+            Some(last) if last.same_style(&piece) => {
+                last.text.push_str(&piece.text);
+                last.width += piece.width;
+            }
+            _ => self.pieces.push(piece),
+        }
+    }
+}
+
+// Lays document blocks out on pages as PDF content stream operators
+pub struct Layout<'a> {
+    geometry: Geometry,
+    base_dir: &'a Path,
+    pub pages: Vec<Page>,
+    pub headings: Vec<HeadingEntry>,
+    pub images: Vec<PdfImage>,
+    loaded_images: HashMap<String, Option<usize>>,
+    heading_ids: HeadingIds,
+    // Characters drawn as '?' because the standard fonts lack them
+    pub replaced: BTreeSet<char>,
+    // Top of the free space on the current page
+    y: f32,
+    body_size: f32,
+}
+
+impl<'a> Layout<'a> {
+    pub fn new(geometry: Geometry, base_dir: &'a Path) -> Self {
+        Self {
+            geometry,
+            base_dir,
+            pages: vec![Page::default()],
+            headings: Vec::new(),
+            images: Vec::new(),
+            loaded_images: HashMap::new(),
+            heading_ids: HeadingIds::default(),
+            replaced: BTreeSet::new(),
+            y: geometry.content_top(),
+            body_size: BODY_SIZE,
+        }
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(Page::default());
+        self.y = self.geometry.content_top();
+    }
+
+    fn page(&mut self) -> &mut Page {
+        // There is always at least one page
+        let last = self.pages.len() - 1;
+        &mut self.pages[last]
+    }
+
+    fn at_page_top(&self) -> bool {
+        self.y >= self.geometry.content_top() - 0.01
+    }
+
+    // Vertical space between blocks; dropped at the top of a page
+    fn gap(&mut self, amount: f32) {
+        if !self.at_page_top() {
+            self.y -= amount;
+        }
+    }
+
+    // Start a new page unless `height` fits below the cursor
+    fn ensure(&mut self, height: f32) {
+        if self.y - height < self.geometry.bottom && !self.at_page_top() {
+            self.new_page();
+        }
+    }
+
+    pub fn blocks(&mut self, blocks: &[Block]) {
+        for block in blocks {
+            self.block(block);
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        match block {
+            Block::Heading { level, spans } => self.heading(*level, spans),
+            Block::Paragraph { spans, marker, context } => self.paragraph(spans, *marker, *context, false),
+            Block::Code { text, context } => self.code_block(text, *context),
+            Block::Table {
+                alignments,
+                rows,
+                header_rows,
+                context,
+            } => self.table(alignments, rows, *header_rows, *context),
+            Block::Image { url, alt, context } => self.image(url, alt, *context),
+            Block::Rule => {
+                self.gap(4.0);
+                self.ensure(12.0);
+                self.y -= 4.0;
+                let (left, right, y) = (self.geometry.left, self.geometry.width - self.geometry.right, self.y);
+                self.stroke_line(left, y, right, y, 0.75, "0.75");
+                self.y -= 8.0;
+            }
+            Block::Comment(_) => {}
+        }
+    }
+
+    fn heading(&mut self, level: u8, spans: &[Span]) {
+        let size = HEADING_SIZES[(level.clamp(1, 6) - 1) as usize];
+        let leading = size * 1.25;
+        let tokens = tokenize(spans, size, true);
+        let lines = break_lines(&tokens, self.geometry.content_width());
+
+        self.gap(size * 0.9);
+        // Keep the heading on the same page as the start of its section
+        let body_leading = self.body_size * LINE_SPACING;
+        self.ensure(lines.len() as f32 * leading + body_leading * 2.0);
+
+        let text = plain_text(spans).trim().to_string();
+        let id = self.heading_ids.next(&text);
+        self.headings.push(HeadingEntry {
+            level,
+            text,
+            id,
+            page: self.pages.len() - 1,
+            y: self.y,
+        });
+
+        let x = self.geometry.left;
+        let width = self.geometry.content_width();
+        for line in &lines {
+            self.ensure(leading);
+            let top = self.y;
+            self.draw_line(line, x, width, Alignment::Left, top, leading, size, TEXT_COLOR);
+            self.y -= leading;
+        }
+        self.y -= size * 0.3;
+    }
+
+    fn paragraph(&mut self, spans: &[Span], marker: Option<Marker>, context: Context, italic: bool) {
+        let size = self.body_size;
+        let leading = size * LINE_SPACING;
+        let indent = indent(context);
+        let x = self.geometry.left + indent;
+        let width = self.geometry.content_width() - indent;
+
+        let tokens = if italic {
+            let spans: Vec<Span> = spans.iter().map(|s| Span { italic: true, ..s.clone() }).collect();
+            tokenize(&spans, size, false)
+        } else {
+            tokenize(spans, size, false)
+        };
+        let lines = break_lines(&tokens, width);
+        let color = if context.quote_depth > 0 { MUTED_COLOR } else { TEXT_COLOR };
+
+        for (index, line) in lines.iter().enumerate() {
+            self.ensure(leading);
+            let top = self.y;
+            self.draw_line(line, x, width, Alignment::Left, top, leading, size, color);
+
+            if index == 0 {
+                if let Some(marker) = marker {
+                    let text = match marker {
+                        Marker::Bullet => "\u{2022}".to_string(),
+                        Marker::Number(n) => format!("{}.", n),
+                        Marker::Task(true) => "[x]".to_string(),
+                        Marker::Task(false) => "[ ]".to_string(),
+                    };
+                    let marker_x = x - Font::Regular.text_width(&text, size) - 5.0;
+                    let baseline = baseline(top, leading, size);
+                    self.draw_text(&text, Font::Regular, size, marker_x, baseline, color);
+                }
+            }
+
+            self.quote_bars(context, top, leading);
+            self.y -= leading;
+        }
+
+        let after = if context.list_depth > 0 { size * 0.3 } else { size * 0.7 };
+        self.y -= after;
+    }
+
+    fn code_block(&mut self, text: &str, context: Context) {
+        let indent = indent(context);
+        let x = self.geometry.left + indent;
+        let width = self.geometry.content_width() - indent;
+        let leading = CODE_SIZE * 1.35;
+        let char_width = Font::Mono.text_width(" ", CODE_SIZE);
+        let max_chars = (((width - 2.0 * CODE_PADDING) / char_width).floor() as usize).max(1);
+
+        let mut lines = Vec::new();
+        for line in text.split('\n') {
+            let chars: Vec<char> = line.replace('\t', "    ").trim_end().chars().collect();
+            if chars.is_empty() {
+                lines.push(String::new());
+            }
+            for chunk in chars.chunks(max_chars) {
+                lines.push(chunk.iter().collect::<String>());
+            }
+        }
+
+        self.gap(self.body_size * 0.2);
+        self.ensure(leading + 2.0 * CODE_PADDING);
+        self.fill_band(x, width, CODE_PADDING);
+        for line in &lines {
+            if self.y - leading < self.geometry.bottom {
+                self.new_page();
+            }
+            let top = self.y;
+            self.fill_rect(x, top - leading, width, leading, "0.95");
+            let baseline = baseline(top, leading, CODE_SIZE);
+            self.draw_text(line, Font::Mono, CODE_SIZE, x + CODE_PADDING, baseline, TEXT_COLOR);
+            self.quote_bars(context, top, leading);
+            self.y -= leading;
+        }
+        if self.y - CODE_PADDING >= self.geometry.bottom {
+            self.fill_band(x, width, CODE_PADDING);
+        }
+        self.y -= self.body_size * 0.7;
+    }
+
+    fn table(&mut self, alignments: &[Alignment], rows: &[Vec<Vec<Span>>], header_rows: usize, context: Context) {
+        let columns = rows.iter().map(|r| r.len()).max().unwrap_or(0).max(alignments.len());
+        if columns == 0 {
+            return;
+        }
+
+        let indent = indent(context);
+        let x = self.geometry.left + indent;
+        let available = self.geometry.content_width() - indent;
+        let leading = TABLE_SIZE * 1.35;
+
+        let cells: Vec<Vec<Vec<Token>>> = rows
+            .iter()
+            .map(|row| row.iter().map(|spans| tokenize(spans, TABLE_SIZE, false)).collect())
+            .collect();
+
+        // Share the width out in proportion to each column's natural width
+        let mut natural = vec![24.0f32; columns];
+        for row in &cells {
+            for (column, tokens) in row.iter().enumerate() {
+                let width: f32 = tokens.iter().map(token_width).sum();
+                natural[column] = natural[column].max(width + 2.0 * CELL_PADDING);
+            }
+        }
+        let total: f32 = natural.iter().sum();
+        let widths: Vec<f32> = natural.iter().map(|w| w * available / total).collect();
+
+        let laid_out: Vec<Vec<Vec<Line>>> = cells
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .enumerate()
+                    .map(|(column, tokens)| break_lines(tokens, widths[column] - 2.0 * CELL_PADDING))
+                    .collect()
+            })
+            .collect();
+        let heights: Vec<f32> = laid_out
+            .iter()
+            .map(|row| {
+                let lines = row.iter().map(|lines| lines.len()).max().unwrap_or(1).max(1);
+                lines as f32 * leading + 2.0 * CELL_PADDING
+            })
+            .collect();
+
+        self.gap(self.body_size * 0.3);
+        for (index, row) in laid_out.iter().enumerate() {
+            if self.y - heights[index] < self.geometry.bottom && !self.at_page_top() {
+                self.new_page();
+                // Repeat the header row on each page the table spans
+                if index >= header_rows {
+                    for header in 0..header_rows {
+                        self.table_row(&laid_out[header], &widths, alignments, x, heights[header], leading, true);
+                    }
+                }
+            }
+            self.table_row(row, &widths, alignments, x, heights[index], leading, index < header_rows);
+        }
+        self.y -= self.body_size * 0.7;
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn table_row(
+        &mut self,
+        row: &[Vec<Line>],
+        widths: &[f32],
+        alignments: &[Alignment],
+        x: f32,
+        height: f32,
+        leading: f32,
+        header: bool,
+    ) {
+        let top = self.y;
+        let total: f32 = widths.iter().sum();
+        if header {
+            self.fill_rect(x, top - height, total, height, "0.93");
+        }
+
+        let mut cell_x = x;
+        for (column, width) in widths.iter().enumerate() {
+            self.page().content.push_str(&format!(
+                "0.7 G 0.5 w {} {} {} {} re S\n",
+                num(cell_x),
+                num(top - height),
+                num(*width),
+                num(height)
+            ));
+            let align = alignments.get(column).copied().unwrap_or(Alignment::None);
+            if let Some(lines) = row.get(column) {
+                let mut line_top = top - CELL_PADDING;
+                for line in lines {
+                    self.draw_line(
+                        line,
+                        cell_x + CELL_PADDING,
+                        width - 2.0 * CELL_PADDING,
+                        align,
+                        line_top,
+                        leading,
+                        TABLE_SIZE,
+                        TEXT_COLOR,
+                    );
+                    line_top -= leading;
+                }
+            }
+            cell_x += width;
+        }
+        self.y -= height;
+    }
+
+    fn image(&mut self, url: &str, alt: &str, context: Context) {
+        let Some(index) = self.load_image(url) else {
+            // Formats the PDF can't embed fall back to the alt text
+            let text = if alt.trim().is_empty() { "[image]".to_string() } else { format!("[{}]", alt.trim()) };
+            let spans = [Span {
+                text,
+                ..Span::default()
+            }];
+            self.paragraph(&spans, None, context, true);
+            return;
+        };
+
+        let indent = indent(context);
+        let image = &self.images[index];
+        let mut width = image.width as f32 * POINTS_PER_PIXEL;
+        let mut height = image.height as f32 * POINTS_PER_PIXEL;
+        let max_width = self.geometry.content_width() - indent;
+        let max_height = (self.geometry.content_top() - self.geometry.bottom) * 0.9;
+        let scale = (max_width / width).min(max_height / height).min(1.0);
+        width *= scale;
+        height *= scale;
+
+        self.gap(self.body_size * 0.3);
+        self.ensure(height);
+        self.y -= height;
+        let (x, y) = (self.geometry.left + indent, self.y);
+        let page = self.page();
+        page.content.push_str(&format!(
+            "q {} 0 0 {} {} {} cm /Im{} Do Q\n",
+            num(width),
+            num(height),
+            num(x),
+            num(y),
+            index + 1
+        ));
+        if !page.images.contains(&index) {
+            page.images.push(index);
+        }
+        self.y -= self.body_size * 0.7;
+    }
+
+    fn load_image(&mut self, url: &str) -> Option<usize> {
+        if let Some(index) = self.loaded_images.get(url) {
+            return *index;
+        }
+        let index = media::load_image(self.base_dir, url)
+            .and_then(|image| image::prepare(&image))
+            .map(|image| {
+                self.images.push(image);
+                self.images.len() - 1
+            });
+        self.loaded_images.insert(url.to_string(), index);
+        index
+    }
+
+    // Footnote definitions, collected under a short rule after the body
+    pub fn footnotes(&mut self, footnotes: &[Footnote]) {
+        if footnotes.is_empty() {
+            return;
+        }
+
+        self.gap(self.body_size);
+        self.ensure(24.0);
+        let (left, y) = (self.geometry.left, self.y);
+        self.stroke_line(left, y, left + self.geometry.content_width() / 3.0, y, 0.5, "0.6");
+        self.y -= 8.0;
+
+        self.body_size = FOOTNOTE_SIZE;
+        for footnote in footnotes {
+            let mut numbered = false;
+            for block in &footnote.blocks {
+                match block {
+                    Block::Paragraph { spans, marker, context } if !numbered => {
+                        let mut spans = spans.clone();
+                        spans.insert(
+                            0,
+                            Span {
+                                text: format!("{}. ", footnote.number),
+                                ..Span::default()
+                            },
+                        );
+                        self.paragraph(&spans, *marker, *context, false);
+                    }
+                    block => self.block(block),
+                }
+                numbered = true;
+            }
+        }
+        self.body_size = BODY_SIZE;
+    }
+
+    // Contents pages listing headings down to H3. `page_offset` is the
+    // number of contents pages that will be inserted before the body.
+    pub fn table_of_contents(&mut self, headings: &[HeadingEntry], page_offset: usize) {
+        let title_size = HEADING_SIZES[0];
+        let title = Line {
+            width: Font::Bold.text_width("Contents", title_size),
+            pieces: vec![Piece {
+                text: "Contents".to_string(),
+                font: Font::Bold,
+                size: title_size,
+                width: Font::Bold.text_width("Contents", title_size),
+                link: None,
+                strike: false,
+            }],
+        };
+        let (x, width) = (self.geometry.left, self.geometry.content_width());
+        let top = self.y;
+        self.draw_line(&title, x, width, Alignment::Left, top, title_size * 1.25, title_size, TEXT_COLOR);
+        self.y -= title_size * 1.25 + 10.0;
+
+        for (index, heading) in headings.iter().enumerate().filter(|(_, h)| h.level <= 3) {
+            let (font, size) = if heading.level == 1 { (Font::Bold, 11.0) } else { (Font::Regular, 10.5) };
+            let leading = size * 1.6;
+            let indent = (heading.level as f32 - 1.0) * 14.0;
+            let number = (heading.page + page_offset + 1).to_string();
+            let number_width = font.text_width(&number, size);
+            let text = truncate_to_width(&heading.text, font, size, width - indent - 36.0);
+            let text_width = font.text_width(&text, size);
+
+            self.ensure(leading);
+            let top = self.y;
+            let baseline = baseline(top, leading, size);
+            let right = x + width;
+            self.draw_text(&text, font, size, x + indent, baseline, TEXT_COLOR);
+            self.draw_text(&number, font, size, right - number_width, baseline, TEXT_COLOR);
+
+            // Dotted leader between the title and the page number
+            let (leader_start, leader_end) = (x + indent + text_width + 6.0, right - number_width - 6.0);
+            if leader_end > leader_start {
+                self.page().content.push_str(&format!(
+                    "0.6 G 0.75 w [1 2.5] 0 d {} {} m {} {} l S [] 0 d\n",
+                    num(leader_start),
+                    num(baseline),
+                    num(leader_end),
+                    num(baseline)
+                ));
+            }
+
+            self.page().links.push(Link {
+                rect: [x + indent, top - leading, right, top],
+                target: LinkTarget::Heading(index),
+            });
+            self.y -= leading;
+        }
+    }
+
+    // Draw one laid out line with its top edge at `top`
+    #[allow(clippy::too_many_arguments)]
+    fn draw_line(
+        &mut self,
+        line: &Line,
+        x: f32,
+        width: f32,
+        align: Alignment,
+        top: f32,
+        leading: f32,
+        size: f32,
+        color: &str,
+    ) {
+        let mut x = match align {
+            Alignment::Center => x + (width - line.width) / 2.0,
+            Alignment::Right => x + width - line.width,
+            _ => x,
+        };
+        let baseline = baseline(top, leading, size);
+
+        for piece in &line.pieces {
+            let color = if piece.link.is_some() { LINK_COLOR } else { color };
+            self.draw_text(&piece.text, piece.font, piece.size, x, baseline, color);
+
+            if piece.strike {
+                let y = baseline + piece.size * 0.3;
+                self.stroke_line(x, y, x + piece.width, y, 0.6, "0.1");
+            }
+            if let Some(link) = &piece.link {
+                let target = match link.strip_prefix('#') {
+                    Some(anchor) => LinkTarget::Anchor(anchor.to_string()),
+                    None => LinkTarget::Uri(link.clone()),
+                };
+                self.page().links.push(Link {
+                    rect: [x, baseline - piece.size * 0.25, x + piece.width, baseline + piece.size * 0.9],
+                    target,
+                });
+            }
+            x += piece.width;
+        }
+    }
+
+    fn draw_text(&mut self, text: &str, font: Font, size: f32, x: f32, baseline: f32, color: &str) {
+        if text.trim().is_empty() {
+            return;
+        }
+        self.replaced.extend(unsupported(text));
+        self.page().content.push_str(&format!(
+            "BT {} rg /{} {} Tf {} {} Td {} Tj ET\n",
+            color,
+            font.resource_name(),
+            num(size),
+            num(x),
+            num(baseline),
+            literal_string(&encode(text))
+        ));
+    }
+
+    fn stroke_line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, width: f32, gray: &str) {
+        self.page().content.push_str(&format!(
+            "{} G {} w {} {} m {} {} l S\n",
+            gray,
+            num(width),
+            num(x1),
+            num(y1),
+            num(x2),
+            num(y2)
+        ));
+    }
+
+    fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, gray: &str) {
+        self.page().content.push_str(&format!(
+            "{} g {} {} {} {} re f\n",
+            gray,
+            num(x),
+            num(y),
+            num(width),
+            num(height)
+        ));
+    }
+
+    // Fill a code block background band and move the cursor below it
+    fn fill_band(&mut self, x: f32, width: f32, height: f32) {
+        let y = self.y - height;
+        self.fill_rect(x, y, width, height, "0.95");
+        self.y = y;
+    }
+
+    // Grey bars left of text inside block quotes
+    fn quote_bars(&mut self, context: Context, top: f32, height: f32) {
+        for depth in 0..context.quote_depth {
+            let x = self.geometry.left + context.list_depth as f32 * LIST_INDENT + depth as f32 * QUOTE_INDENT;
+            self.fill_rect(x, top - height, 2.0, height, "0.8");
+        }
+    }
+}
+
+// Header or footer text centred at `baseline`
+pub fn draw_running_text(page: &mut Page, text: &str, geometry: &Geometry, baseline: f32) {
+    let size = 9.0;
+    let text = truncate_to_width(text, Font::Regular, size, geometry.content_width());
+    let width = Font::Regular.text_width(&text, size);
+    let x = geometry.left + (geometry.content_width() - width) / 2.0;
+    page.content.push_str(&format!(
+        "BT {} rg /{} {} Tf {} {} Td {} Tj ET\n",
+        MUTED_COLOR,
+        Font::Regular.resource_name(),
+        num(size),
+        num(x),
+        num(baseline),
+        literal_string(&encode(&text))
+    ));
+}
+
+fn indent(context: Context) -> f32 {
+    context.list_depth as f32 * LIST_INDENT + context.quote_depth as f32 * QUOTE_INDENT
+}
+
+// Baseline that vertically centres text of `size` in a line box
+fn baseline(top: f32, leading: f32, size: f32) -> f32 {
+    top - leading / 2.0 - size * 0.3
+}
+
+fn tokenize(spans: &[Span], size: f32, bold: bool) -> Vec<Token> {
+    let mut tokens = Vec::new();
+
+    for span in spans {
+        let font = Font::styled(span.bold || bold, span.italic, span.code);
+        let size = if span.code { size * 0.92 } else { size };
+        let piece = |text: &str| Piece {
+            text: text.to_string(),
+            font,
+            size,
+            width: font.text_width(text, size),
+            link: span.link.clone(),
+            strike: span.strike,
+        };
+
+        let mut word = String::new();
+        for c in span.text.chars() {
+            if c == ' ' || c == '\n' || c == '\t' {
+                if !word.is_empty() {
+                    push_fragment(&mut tokens, piece(&word));
+                    word.clear();
+                }
+                if c == '\n' {
+                    tokens.push(Token::Newline);
+                } else if !matches!(tokens.last(), Some(Token::Space(_)) | Some(Token::Newline) | None) {
+                    tokens.push(Token::Space(piece(" ")));
+                }
+            } else {
+                word.push(c);
+            }
+        }
+        if !word.is_empty() {
+            push_fragment(&mut tokens, piece(&word));
+        }
+    }
+
+    tokens
+}
+
+// Glue a fragment to the previous word when no space separates them
+fn push_fragment(tokens: &mut Vec<Token>, piece: Piece) {
+    if let Some(Token::Word(pieces)) = tokens.last_mut() {
+        pieces.push(piece);
+    } else {
+        tokens.push(Token::Word(vec![piece]));
+    }
+}
+
+fn token_width(token: &Token) -> f32 {
+    match token {
+        Token::Word(pieces) => pieces.iter().map(|p| p.width).sum(),
+        Token::Space(piece) => piece.width,
+        Token::Newline => 0.0,
+    }
+}
+
+// Greedy line breaking at spaces; words wider than a line are split
+fn break_lines(tokens: &[Token], width: f32) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut line = Line::default();
+    let mut space: Option<&Piece> = None;
+
+    for token in tokens {
+        match token {
+            Token::Newline => {
+                lines.push(std::mem::take(&mut line));
+                space = None;
+            }
+            Token::Space(piece) => {
+                if !line.pieces.is_empty() {
+                    space = Some(piece);
+                }
+            }
+            Token::Word(pieces) => {
+                let word_width = token_width(token);
+                let space_width = space.map(|s| s.width).unwrap_or(0.0);
+                if !line.pieces.is_empty() && line.width + space_width + word_width > width {
+                    lines.push(std::mem::take(&mut line));
+                } else if let Some(space) = space {
+                    line.push(space.clone());
+                }
+                space = None;
+
+                if word_width <= width {
+                    for piece in pieces {
+                        line.push(piece.clone());
+                    }
+                    continue;
+                }
+
+                for piece in pieces {
+                    for c in piece.text.chars() {
+                        let text = c.to_string();
+                        let char_width = piece.font.text_width(&text, piece.size);
+                        if !line.pieces.is_empty() && line.width + char_width > width {
+                            lines.push(std::mem::take(&mut line));
+                        }
+                        line.push(Piece {
+                            text,
+                            width: char_width,
+                            ..piece.clone()
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    if !line.pieces.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn truncate_to_width(text: &str, font: Font, size: f32, width: f32) -> String {
+    if font.text_width(text, size) <= width {
+        return text.to_string();
+    }
+    let mut truncated: String = text.to_string();
+    while !truncated.is_empty() && font.text_width(&format!("{}\u{2026}", truncated), size) > width {
+        truncated.pop();
+    }
+    format!("{}\u{2026}", truncated.trim_end())
+}
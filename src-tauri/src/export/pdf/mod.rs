@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::document;
+use super::ExportOptions;
+
+mod fonts;
+mod image;
+mod layout;
+mod writer;
+
+use fonts::{unsupported, Font};
+use layout::{draw_running_text, Geometry, HeadingEntry, Layout, LinkTarget};
+use writer::{literal_string, num, text_string, PdfWriter};
+
+const MM_TO_POINTS: f32 = 72.0 / 25.4;
+
+// Convert markdown to a paginated PDF. Images are resolved relative to `base_dir`.
+// Returns the PDF and warnings about text it could not show.
+pub fn markdown_to_pdf(
+    markdown: &str,
+    base_dir: &Path,
+    title: &str,
+    options: &ExportOptions,
+) -> Result<(Vec<u8>, Vec<String>), String> {
+    let (width, height) = options.page_size.dimensions();
+    let geometry = Geometry {
+        width,
+        height,
+        top: options.margins.top * MM_TO_POINTS,
+        right: options.margins.right * MM_TO_POINTS,
+        bottom: options.margins.bottom * MM_TO_POINTS,
+        left: options.margins.left * MM_TO_POINTS,
+    };
+    if geometry.left + geometry.right >= width - 72.0 || geometry.top + geometry.bottom >= height - 72.0 {
+        return Err("Margins leave no room for the page content".to_string());
+    }
+
+    let document = document::parse(markdown);
+    let mut body = Layout::new(geometry, base_dir);
+    body.blocks(&document.blocks);
+    body.footnotes(&document.footnotes);
+
+    let mut pages = std::mem::take(&mut body.pages);
+    let mut replaced = std::mem::take(&mut body.replaced);
+    let mut headings = std::mem::take(&mut body.headings);
+
+    // The contents pages go first, so lay them out once to count them and
+    // again with the final page numbers
+    if options.toc && headings.iter().any(|h| h.level <= 3) {
+        let count = {
+            let mut toc = Layout::new(geometry, base_dir);
+            toc.table_of_contents(&headings, 0);
+            toc.pages.len()
+        };
+        let mut toc = Layout::new(geometry, base_dir);
+        toc.table_of_contents(&headings, count);
+        pages.splice(0..0, toc.pages);
+        for heading in &mut headings {
+            heading.page += count;
+        }
+    }
+
+    let total = pages.len();
+    for (index, page) in pages.iter_mut().enumerate() {
+        let fill = |template: &str| {
+            template
+                .replace("{title}", title)
+                .replace("{page}", &(index + 1).to_string())
+                .replace("{pages}", &total.to_string())
+        };
+        if let Some(header) = options.header.as_deref().filter(|h| !h.trim().is_empty()) {
+            let header = fill(header);
+            replaced.extend(unsupported(&header));
+            draw_running_text(page, &header, &geometry, height - geometry.top / 2.0);
+        }
+        if let Some(footer) = options.footer.as_deref().filter(|f| !f.trim().is_empty()) {
+            let footer = fill(footer);
+            replaced.extend(unsupported(&footer));
+            draw_running_text(page, &footer, &geometry, geometry.bottom / 2.0 - 3.0);
+        }
+    }
+
+    let mut pdf = PdfWriter::new();
+    let catalog_id = pdf.reserve();
+    let pages_id = pdf.reserve();
+
+    let font_ids: Vec<(Font, u32)> = Font::ALL
+        .iter()
+        .map(|&font| {
+            let id = pdf.add(format!(
+                "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+                font.base_font()
+            ));
+            (font, id)
+        })
+        .collect();
+    let fonts: String = font_ids
+        .iter()
+        .map(|(font, id)| format!("/{} {} 0 R", font.resource_name(), id))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let image_ids: Vec<u32> = body
+        .images
+        .iter()
+        .map(|image| {
+            let mut dict = format!(
+                "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace {} /BitsPerComponent 8 {}",
+                image.width, image.height, image.color_space, image.filter
+            );
+            if let Some(alpha) = &image.alpha {
+                let mask = pdf.add_stream(
+                    &format!(
+                        "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceGray /BitsPerComponent 8 /Filter /FlateDecode",
+                        image.width, image.height
+                    ),
+                    alpha,
+                );
+                dict.push_str(&format!(" /SMask {} 0 R", mask));
+            }
+            pdf.add_stream(&dict, &image.data)
+        })
+        .collect();
+
+    let page_ids: Vec<u32> = pages.iter().map(|_| pdf.reserve()).collect();
+    let anchors: HashMap<&str, usize> = headings.iter().enumerate().map(|(i, h)| (h.id.as_str(), i)).collect();
+    let destination = |heading: &HeadingEntry| format!("[{} 0 R /XYZ 0 {} 0]", page_ids[heading.page], num(heading.y));
+
+    for (page, &page_id) in pages.iter().zip(&page_ids) {
+        let content_id = pdf.add_compressed_stream("", page.content.as_bytes());
+
+        let annotations: Vec<String> = page
+            .links
+            .iter()
+            .filter_map(|link| {
+                let action = match &link.target {
+                    LinkTarget::Uri(uri) => format!("/A << /S /URI /URI {} >>", literal_string(uri.as_bytes())),
+                    LinkTarget::Anchor(anchor) => format!("/Dest {}", destination(&headings[*anchors.get(anchor.as_str())?])),
+                    LinkTarget::Heading(index) => format!("/Dest {}", destination(headings.get(*index)?)),
+                };
+                let [x1, y1, x2, y2] = link.rect;
+                Some(format!(
+                    "<< /Type /Annot /Subtype /Link /Rect [{} {} {} {}] /Border [0 0 0] {} >>",
+                    num(x1),
+                    num(y1),
+                    num(x2),
+                    num(y2),
+                    action
+                ))
+            })
+            .collect();
+
+        let images: String = page
+            .images
+            .iter()
+            .map(|&index| format!("/Im{} {} 0 R", index + 1, image_ids[index]))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut dict = format!(
+            "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] /Resources << /Font << {} >>",
+            pages_id,
+            num(width),
+            num(height),
+            fonts
+        );
+        if !images.is_empty() {
+            dict.push_str(&format!(" /XObject << {} >>", images));
+        }
+        dict.push_str(&format!(" >> /Contents {} 0 R", content_id));
+        if !annotations.is_empty() {
+            dict.push_str(&format!(" /Annots [{}]", annotations.join(" ")));
+        }
+        dict.push_str(" >>");
+        pdf.set(page_id, dict);
+    }
+
+    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+    pdf.set(
+        pages_id,
+        format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), page_ids.len()),
+    );
+
+    let mut catalog = format!("<< /Type /Catalog /Pages {} 0 R", pages_id);
+    if let Some(outlines_id) = write_outlines(&mut pdf, &headings, &destination) {
+        catalog.push_str(&format!(" /Outlines {} 0 R /PageMode /UseOutlines", outlines_id));
+    }
+    catalog.push_str(" >>");
+    pdf.set(catalog_id, catalog);
+
+    // No creation date, so exporting the same markdown twice gives the same file
+    let info_id = pdf.add(format!("<< /Title {} /Producer (Clause) >>", text_string(title)));
+
+    let mut warnings = Vec::new();
+    if !replaced.is_empty() {
+        let shown: String = replaced.iter().take(20).collect();
+        warnings.push(format!(
+            "The PDF fonts cannot show {} character{} ({}{}), printed as '?' instead",
+            replaced.len(),
+            if replaced.len() == 1 { "" } else { "s" },
+            shown,
+            if replaced.len() > 20 { "..." } else { "" }
+        ));
+    }
+    Ok((pdf.finish(catalog_id, info_id), warnings))
+}
+
+// Bookmarks for the headings, nested by level
+fn write_outlines(
+    pdf: &mut PdfWriter,
+    headings: &[HeadingEntry],
+    destination: &dyn Fn(&HeadingEntry) -> String,
+) -> Option<u32> {
+    if headings.is_empty() {
+        return None;
+    }
+
+    // Parent of each heading: the closest earlier heading with a lower level
+    let mut parents: Vec<Option<usize>> = Vec::with_capacity(headings.len());
+    let mut stack: Vec<usize> = Vec::new();
+    for (index, heading) in headings.iter().enumerate() {
+        while stack.last().is_some_and(|&open| headings[open].level >= heading.level) {
+            stack.pop();
+        }
+        parents.push(stack.last().copied());
+        stack.push(index);
+    }
+
+    let root_id = pdf.reserve();
+    let ids: Vec<u32> = headings.iter().map(|_| pdf.reserve()).collect();
+    let children = |parent: Option<usize>| -> Vec<usize> {
+        (0..headings.len()).filter(|&i| parents[i] == parent).collect()
+    };
+
+    for (index, heading) in headings.iter().enumerate() {
+        let siblings = children(parents[index]);
+        let position = siblings.iter().position(|&i| i == index).unwrap_or(0);
+        let parent_id = parents[index].map(|p| ids[p]).unwrap_or(root_id);
+
+        let mut dict = format!(
+            "<< /Title {} /Parent {} 0 R /Dest {}",
+            text_string(&heading.text),
+            parent_id,
+            destination(heading)
+        );
+        if position > 0 {
+            dict.push_str(&format!(" /Prev {} 0 R", ids[siblings[position - 1]]));
+        }
+        if let Some(&next) = siblings.get(position + 1) {
+            dict.push_str(&format!(" /Next {} 0 R", ids[next]));
+        }
+        let own = children(Some(index));
+        if let (Some(&first), Some(&last)) = (own.first(), own.last()) {
+            // Negative count: show the entry collapsed below H2
+            let count = own.len() as i64;
+            let count = if heading.level >= 2 { -count } else { count };
+            dict.push_str(&format!(" /First {} 0 R /Last {} 0 R /Count {}", ids[first], ids[last], count));
+        }
+        dict.push_str(" >>");
+        pdf.set(ids[index], dict);
+    }
+
+    // Top-level entries plus the children of H1s, which start expanded
+    let top = children(None);
+    let visible = top.len()
+        + top
+            .iter()
+            .filter(|&&i| headings[i].level < 2)
+            .map(|&i| children(Some(i)).len())
+            .sum::<usize>();
+    pdf.set(
+        root_id,
+        format!(
+            "<< /Type /Outlines /First {} 0 R /Last {} 0 R /Count {} >>",
+            ids[top[0]],
+            ids[top[top.len() - 1]],
+            visible
+        ),
+    );
+    Some(root_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Text of each page with whitespace collapsed, and the warnings
+    fn export(markdown: &str, options: &ExportOptions) -> (Vec<String>, Vec<String>) {
+        let (bytes, warnings) = markdown_to_pdf(markdown, Path::new("."), "Report", options).unwrap();
+        let pages = pdf_extract::extract_text_from_mem_by_pages(&bytes)
+            .unwrap()
+            .iter()
+            .map(|page| page.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect();
+        (pages, warnings)
+    }
+
+    fn long_document() -> String {
+        let body: String = (1..=80).map(|i| format!("Paragraph number {} of the body.\n\n", i)).collect();
+        format!("# Intro\n\nHello.\n\n## Details\n\n{}## End\n\nBye.\n", body)
+    }
+
+    #[test]
+    fn paginates_with_running_text() {
+        let options = ExportOptions {
+            header: Some("{title}".to_string()),
+            footer: Some("Page {page} of {pages}".to_string()),
+            ..ExportOptions::default()
+        };
+        let (pages, warnings) = export(&long_document(), &options);
+        assert!(pages.len() > 1);
+        assert!(warnings.is_empty());
+        for (index, page) in pages.iter().enumerate() {
+            assert!(page.contains("Report"));
+            assert!(page.ends_with(&format!("Page {} of {}", index + 1, pages.len())));
+        }
+        // Every paragraph is laid out once, in order
+        let text = pages.join(" ");
+        let positions: Vec<usize> = (1..=80)
+            .map(|i| text.find(&format!("Paragraph number {} of", i)).unwrap())
+            .collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn contents_come_first_with_page_numbers() {
+        let options = ExportOptions {
+            toc: true,
+            footer: Some("Page {page} of {pages}".to_string()),
+            ..ExportOptions::default()
+        };
+        let (pages, _) = export(&long_document(), &options);
+        let (without_toc, _) = export(&long_document(), &ExportOptions::default());
+        assert_eq!(pages.len(), without_toc.len() + 1);
+
+        let contents = &pages[0];
+        assert!(contents.starts_with("Contents"));
+        assert!(contents.contains("Intro 2"));
+        assert!(contents.contains("Details 2"));
+        // The end heading is on the last page
+        assert!(contents.contains(&format!("End {}", pages.len())));
+        assert!(pages[pages.len() - 1].contains("End Bye."));
+        assert!(contents.ends_with(&format!("Page 1 of {}", pages.len())));
+    }
+
+    #[test]
+    fn warns_about_characters_the_fonts_cannot_show() {
+        let (pages, warnings) = export("Caf\u{e9} \u{2013} \u{201c}fine\u{201d}\n", &ExportOptions::default());
+        assert!(warnings.is_empty());
+        assert!(pages[0].contains("Caf\u{e9}"));
+
+        // Arrows and ticks have stand-ins; other characters become '?'
+        let options = ExportOptions {
+            footer: Some("\u{7b2c} {page} \u{2713}".to_string()),
+            ..ExportOptions::default()
+        };
+        let (pages, warnings) = export("Arrows \u{2192} and \u{4e16}\u{754c} \u{4e16}\n", &options);
+        assert!(pages[0].starts_with("Arrows > and ?? ?"));
+        assert!(pages[0].ends_with("? 1 x"));
+        assert_eq!(
+            warnings,
+            ["The PDF fonts cannot show 3 characters (\u{4e16}\u{754c}\u{7b2c}), printed as '?' instead"]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::Write;
+
+// Minimal PDF 1.4 object writer. Objects are numbered from 1 and can be
+// reserved before their contents are known, so pages and annotations can
+// refer to each other.
+pub struct PdfWriter {
+    objects: Vec<Option<Vec<u8>>>,
+}
+
+impl PdfWriter {
+    pub fn new() -> Self {
+        Self { objects: Vec::new() }
+    }
+
+    pub fn reserve(&mut self) -> u32 {
+        self.objects.push(None);
+        self.objects.len() as u32
+    }
+
+    pub fn set(&mut self, id: u32, body: impl Into<Vec<u8>>) {
+        self.objects[id as usize - 1] = Some(body.into());
+    }
+
+    pub fn add(&mut self, body: impl Into<Vec<u8>>) -> u32 {
+        let id = self.reserve();
+        self.set(id, body);
+        id
+    }
+
+    // Add a stream object. `dict` holds the dictionary entries other than
+    // /Length; the data is written as is.
+    pub fn add_stream(&mut self, dict: &str, data: &[u8]) -> u32 {
+        let mut body = format!("<< {} /Length {} >>\nstream\n", dict, data.len()).into_bytes();
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\nendstream");
+        self.add(body)
+    }
+
+    pub fn add_compressed_stream(&mut self, dict: &str, data: &[u8]) -> u32 {
+        let dict = format!("{} /Filter /FlateDecode", dict).trim().to_string();
+        self.add_stream(&dict, &deflate(data))
+    }
+
+    pub fn finish(self, catalog: u32, info: u32) -> Vec<u8> {
+        let mut out = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = Vec::with_capacity(self.objects.len());
+
+        for (index, object) in self.objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+            // A reserved object that was never filled in is written as null
+            out.extend_from_slice(object.as_deref().unwrap_or(b"null"));
+            out.extend_from_slice(b"\nendobj\n");
+        }
+
+        let xref = out.len();
+        out.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", self.objects.len() + 1).as_bytes());
+        for offset in offsets {
+            out.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        out.extend_from_slice(
+            format!(
+                "trailer\n<< /Size {} /Root {} 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+                self.objects.len() + 1,
+                catalog,
+                info,
+                xref
+            )
+            .as_bytes(),
+        );
+        out
+    }
+}
+
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    // Writing to a Vec cannot fail
+    let _ = encoder.write_all(data);
+    encoder.finish().unwrap_or_default()
+}
+
+// Literal string for already-encoded (WinAnsi) text in a content stream
+pub fn literal_string(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() + 2);
+    out.push('(');
+    for &b in bytes {
+        match b {
+            b'(' | b')' | b'\\' => {
+                out.push('\\');
+                out.push(b as char);
+            }
+            0x20..=0x7E => out.push(b as char),
+            _ => out.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    out.push(')');
+    out
+}
+
+// Text string for metadata and outline titles, which may hold any Unicode
+pub fn text_string(text: &str) -> String {
+    if text.chars().all(|c| (' '..='~').contains(&c)) {
+        return literal_string(text.as_bytes());
+    }
+    let mut out = String::from("<FEFF");
+    for unit in text.encode_utf16() {
+        out.push_str(&format!("{:04X}", unit));
+    }
+    out.push('>');
+    out
+}
+
+// Format a coordinate without trailing zeros, e.g. 72 or 12.5
+pub fn num(value: f32) -> String {
+    let text = format!("{:.2}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,1113 @@
+use pulldown_cmark::Alignment;
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::document::{self, plain_text, Block, Document, Marker, Span};
+use super::media::{self, ImageFormat};
+use super::ooxml::{self, xml_escape, Package};
+use super::SlideBreakStrategy;
+
+mod template;
+
+use template::{BODY_FRAME, EMPTY_GROUP, NAMESPACES, SLIDE_HEIGHT, SLIDE_WIDTH, TABLE_STYLE_ID, XML_HEADER};
+
+// Paragraphs longer than this go to the speaker notes; the slide keeps
+// only their first sentence
+const LONG_PARAGRAPH_CHARS: usize = 300;
+// Rough capacity of a content slide, in lines of body text
+const MAX_SLIDE_LINES: usize = 12;
+const CHARS_PER_LINE: usize = 70;
+
+const EMU_PER_PIXEL: i64 = 9525;
+const TABLE_ROW_HEIGHT: i64 = 370_840;
+// Space between the text and visuals columns
+const COLUMN_GAP: i64 = 304_800;
+
+const TITLE_LAYOUT: usize = 1;
+const CONTENT_LAYOUT: usize = 2;
+const SECTION_LAYOUT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SlideKind {
+    Title,
+    Section,
+    Content,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextStyle {
+    Normal,
+    Heading,
+    Quote,
+}
+
+enum Item {
+    Text {
+        spans: Vec<Span>,
+        level: usize,
+        marker: Option<Marker>,
+        // Continuation paragraph of a list item
+        in_list: bool,
+        style: TextStyle,
+    },
+    Code(String),
+    Table {
+        alignments: Vec<Alignment>,
+        rows: Vec<Vec<Vec<Span>>>,
+        header_rows: usize,
+    },
+    Image {
+        url: String,
+        alt: String,
+    },
+}
+
+impl Item {
+    fn is_visual(&self) -> bool {
+        matches!(self, Item::Table { .. } | Item::Image { .. })
+    }
+
+    // Approximate height in lines of body text, for splitting full slides
+    fn weight(&self) -> usize {
+        match self {
+            Item::Text { spans, .. } => plain_text(spans).chars().count().div_ceil(CHARS_PER_LINE).max(1),
+            Item::Code(text) => text.lines().count().max(1),
+            Item::Table { rows, .. } => rows.len() + 1,
+            Item::Image { .. } => MAX_SLIDE_LINES / 2,
+        }
+    }
+}
+
+struct Slide {
+    kind: SlideKind,
+    title: Vec<Span>,
+    subtitle: Vec<Span>,
+    items: Vec<Item>,
+    notes: Vec<String>,
+}
+
+impl Slide {
+    fn new(kind: SlideKind, title: Vec<Span>) -> Self {
+        Self {
+            kind,
+            title,
+            subtitle: Vec::new(),
+            items: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.title.is_empty() && self.subtitle.is_empty() && self.items.is_empty() && self.notes.is_empty()
+    }
+
+    fn add_block(&mut self, block: &Block) {
+        match block {
+            Block::Paragraph { spans, marker, context } => {
+                let style = if context.quote_depth > 0 { TextStyle::Quote } else { TextStyle::Normal };
+                let mut spans = spans.clone();
+                if marker.is_none() && context.list_depth == 0 && plain_text(&spans).chars().count() > LONG_PARAGRAPH_CHARS {
+                    self.notes.push(plain_text(&spans));
+                    spans = first_sentence(&spans);
+                }
+                self.items.push(Item::Text {
+                    spans,
+                    level: context.list_depth.saturating_sub(1),
+                    marker: *marker,
+                    in_list: context.list_depth > 0,
+                    style,
+                });
+            }
+            Block::Heading { spans, .. } => self.items.push(Item::Text {
+                spans: spans.clone(),
+                level: 0,
+                marker: None,
+                in_list: false,
+                style: TextStyle::Heading,
+            }),
+            Block::Code { text, .. } => self.items.push(Item::Code(text.clone())),
+            Block::Table {
+                alignments,
+                rows,
+                header_rows,
+                ..
+            } => self.items.push(Item::Table {
+                alignments: alignments.clone(),
+                rows: rows.clone(),
+                header_rows: *header_rows,
+            }),
+            Block::Image { url, alt, .. } => self.items.push(Item::Image {
+                url: url.clone(),
+                alt: alt.clone(),
+            }),
+            Block::Rule | Block::Comment(_) => {}
+        }
+    }
+}
+
+// Convert markdown to a .pptx deck. Images are resolved relative to `base_dir`.
+pub fn markdown_to_pptx(
+    markdown: &str,
+    base_dir: &Path,
+    title: &str,
+    strategy: SlideBreakStrategy,
+) -> Result<Vec<u8>, String> {
+    let document = document::parse(markdown);
+    let mut slides = split_slides(&document, strategy);
+    if slides.is_empty() {
+        slides.push(Slide::new(
+            SlideKind::Title,
+            vec![Span {
+                text: title.to_string(),
+                ..Span::default()
+            }],
+        ));
+    }
+
+    let mut writer = DeckWriter::new(base_dir);
+    for slide in &slides {
+        writer.add_slide(slide);
+    }
+    writer.finish(title)
+}
+
+fn split_slides(document: &Document, strategy: SlideBreakStrategy) -> Vec<Slide> {
+    let by_headings = strategy != SlideBreakStrategy::Rules;
+    let by_rules = strategy != SlideBreakStrategy::Headings;
+
+    let mut slides: Vec<Slide> = Vec::new();
+    let mut current: Option<Slide> = None;
+    let push = |slides: &mut Vec<Slide>, slide: Option<Slide>| {
+        if let Some(slide) = slide.filter(|s| !s.is_empty()) {
+            slides.push(slide);
+        }
+    };
+
+    for block in &document.blocks {
+        match block {
+            // H1 opens the deck or a new section, H2 a new slide
+            Block::Heading { level, spans } if by_headings && *level <= 2 => {
+                push(&mut slides, current.take());
+                let kind = match level {
+                    1 if slides.is_empty() => SlideKind::Title,
+                    1 => SlideKind::Section,
+                    _ => SlideKind::Content,
+                };
+                current = Some(Slide::new(kind, spans.clone()));
+            }
+            Block::Heading { level, spans } => {
+                // Title and section slides hold only a title and subtitle
+                if current.as_ref().is_some_and(|s| s.kind != SlideKind::Content) {
+                    push(&mut slides, current.take());
+                }
+                let first = slides.is_empty();
+                let slide = current.get_or_insert_with(|| Slide::new(SlideKind::Content, Vec::new()));
+                if slide.title.is_empty() && slide.items.is_empty() {
+                    slide.title = spans.clone();
+                    if *level == 1 {
+                        slide.kind = if first { SlideKind::Title } else { SlideKind::Section };
+                    }
+                } else {
+                    slide.add_block(block);
+                }
+            }
+            Block::Rule => {
+                if by_rules {
+                    push(&mut slides, current.take());
+                }
+            }
+            Block::Comment(text) => {
+                let Some(note) = speaker_note(text).filter(|note| !note.is_empty()) else {
+                    continue;
+                };
+                match current.as_mut().or(slides.last_mut()) {
+                    Some(slide) => slide.notes.push(note),
+                    None => {
+                        let mut slide = Slide::new(SlideKind::Content, Vec::new());
+                        slide.notes.push(note);
+                        current = Some(slide);
+                    }
+                }
+            }
+            _ => {
+                let slide = current.get_or_insert_with(|| Slide::new(SlideKind::Content, Vec::new()));
+                if slide.kind != SlideKind::Content {
+                    // One short paragraph under a title or section heading is its
+                    // subtitle; anything more continues on a content slide
+                    if let Block::Paragraph {
+                        spans,
+                        marker: None,
+                        context,
+                    } = block
+                    {
+                        let short = plain_text(spans).chars().count() <= CHARS_PER_LINE * 2;
+                        if slide.subtitle.is_empty() && slide.items.is_empty() && context.quote_depth == 0 && short {
+                            slide.subtitle = spans.clone();
+                            continue;
+                        }
+                    }
+                    let title = slide.title.clone();
+                    push(&mut slides, current.take());
+                    current = Some(Slide::new(SlideKind::Content, title));
+                }
+                if let Some(slide) = current.as_mut() {
+                    slide.add_block(block);
+                }
+            }
+        }
+    }
+    push(&mut slides, current.take());
+
+    if !document.footnotes.is_empty() {
+        let mut slide = Slide::new(
+            SlideKind::Content,
+            vec![Span {
+                text: "Notes".to_string(),
+                ..Span::default()
+            }],
+        );
+        for footnote in &document.footnotes {
+            for block in &footnote.blocks {
+                if let Block::Paragraph { spans, .. } = block {
+                    slide.items.push(Item::Text {
+                        spans: spans.clone(),
+                        level: 0,
+                        marker: Some(Marker::Number(footnote.number as u64)),
+                        in_list: true,
+                        style: TextStyle::Normal,
+                    });
+                    break;
+                }
+            }
+        }
+        slides.push(slide);
+    }
+
+    slides.into_iter().flat_map(split_overfull).collect()
+}
+
+// Spread the items of an overfull slide over continuation slides
+fn split_overfull(slide: Slide) -> Vec<Slide> {
+    let total: usize = slide.items.iter().map(Item::weight).sum();
+    if slide.kind != SlideKind::Content || total <= MAX_SLIDE_LINES {
+        return vec![slide];
+    }
+
+    let mut continued_title = slide.title.clone();
+    if !continued_title.is_empty() {
+        continued_title.push(Span {
+            text: " (cont.)".to_string(),
+            ..Span::default()
+        });
+    }
+
+    let mut slides = Vec::new();
+    let mut page = Slide::new(SlideKind::Content, slide.title);
+    page.notes = slide.notes;
+    let mut lines = 0;
+    for item in slide.items {
+        let weight = item.weight();
+        if lines > 0 && lines + weight > MAX_SLIDE_LINES {
+            slides.push(page);
+            page = Slide::new(SlideKind::Content, continued_title.clone());
+            lines = 0;
+        }
+        lines += weight;
+        page.items.push(item);
+    }
+    slides.push(page);
+    slides
+}
+
+// Text of a comment starting with "notes" or "note" and then a colon,
+// whitespace or a line break, e.g. <!-- notes: Say this --> or
+// <!-- notes\nSay this -->. Other comments are not notes.
+fn speaker_note(comment: &str) -> Option<String> {
+    let text = comment.trim();
+    ["notes", "note"].iter().find_map(|keyword| {
+        let rest = text
+            .get(keyword.len()..)
+            .filter(|_| text[..keyword.len()].eq_ignore_ascii_case(keyword))?;
+        if !(rest.is_empty() || rest.starts_with(|c: char| c == ':' || c.is_whitespace())) {
+            return None;
+        }
+        let rest = rest.trim_start();
+        Some(rest.strip_prefix(':').unwrap_or(rest).trim().to_string())
+    })
+}
+
+// The spans up to the end of the first sentence, or the first ~160
+// characters when there is no sentence break
+fn first_sentence(spans: &[Span]) -> Vec<Span> {
+    let text = plain_text(spans);
+    let end = text
+        .match_indices(['.', '?', '!'])
+        .map(|(i, _)| i + 1)
+        .find(|&i| text[i..].starts_with(char::is_whitespace));
+
+    let (cut, ellipsis) = match end {
+        Some(end) if end <= LONG_PARAGRAPH_CHARS => (end, false),
+        _ => {
+            let limit = text.char_indices().nth(160).map(|(i, _)| i).unwrap_or(text.len());
+            (text[..limit].rfind(' ').unwrap_or(limit), true)
+        }
+    };
+
+    let mut out = Vec::new();
+    let mut offset = 0;
+    for span in spans {
+        if offset >= cut {
+            break;
+        }
+        let take = (cut - offset).min(span.text.len());
+        out.push(Span {
+            text: span.text[..take].to_string(),
+            ..span.clone()
+        });
+        offset += span.text.len();
+    }
+    if ellipsis {
+        if let Some(last) = out.last_mut() {
+            last.text = format!("{}\u{2026}", last.text.trim_end());
+        }
+    }
+    out
+}
+
+struct SlideRelationship {
+    id: String,
+    kind: &'static str,
+    target: String,
+    external: bool,
+}
+
+struct DeckWriter<'a> {
+    base_dir: &'a Path,
+    slides: Vec<(String, Vec<SlideRelationship>)>,
+    notes: Vec<(usize, String)>,
+    media: Vec<(String, ImageFormat, Vec<u8>)>,
+    media_names: HashMap<String, String>,
+
+    // State of the slide being written
+    relationships: Vec<SlideRelationship>,
+    shape_id: u32,
+}
+
+impl<'a> DeckWriter<'a> {
+    fn new(base_dir: &'a Path) -> Self {
+        Self {
+            base_dir,
+            slides: Vec::new(),
+            notes: Vec::new(),
+            media: Vec::new(),
+            media_names: HashMap::new(),
+            relationships: Vec::new(),
+            shape_id: 1,
+        }
+    }
+
+    fn add_slide(&mut self, slide: &Slide) {
+        let number = self.slides.len() + 1;
+        self.shape_id = 1;
+        let layout = match slide.kind {
+            SlideKind::Title => TITLE_LAYOUT,
+            SlideKind::Section => SECTION_LAYOUT,
+            SlideKind::Content => CONTENT_LAYOUT,
+        };
+        self.relationships = vec![SlideRelationship {
+            id: "rId1".to_string(),
+            kind: "http://schemas.openxmlformats.org/officeDocument/2006/relationships/slideLayout",
+            target: format!("../slideLayouts/slideLayout{}.xml", layout),
+            external: false,
+        }];
+        if !slide.notes.is_empty() {
+            self.add_relationship(
+                "http://schemas.openxmlformats.org/officeDocument/2006/relationships/notesSlide",
+                format!("../notesSlides/notesSlide{}.xml", number),
+                false,
+            );
+            self.notes.push((number, notes_slide(&slide.notes)));
+        }
+
+        let mut shapes = String::new();
+        match slide.kind {
+            SlideKind::Title | SlideKind::Section => {
+                let (title_type, subtitle_type) = if slide.kind == SlideKind::Title {
+                    ("ctrTitle", "subTitle")
+                } else {
+                    ("title", "body")
+                };
+                let title = self.paragraph_xml(&slide.title, "", None);
+                shapes.push_str(&self.text_placeholder(title_type, None, &title));
+                if !slide.subtitle.is_empty() {
+                    let subtitle = self.paragraph_xml(&slide.subtitle, "", None);
+                    shapes.push_str(&self.text_placeholder(subtitle_type, Some(1), &subtitle));
+                }
+            }
+            SlideKind::Content => {
+                if !slide.title.is_empty() {
+                    let title = self.paragraph_xml(&slide.title, "", None);
+                    shapes.push_str(&self.text_placeholder("title", None, &title));
+                }
+                shapes.push_str(&self.content(&slide.items));
+            }
+        }
+
+        let xml = format!(
+            r#"{}<p:sld {}><p:cSld><p:spTree>{}{}</p:spTree></p:cSld><p:clrMapOvr><a:masterClrMapping/></p:clrMapOvr></p:sld>"#,
+            XML_HEADER, NAMESPACES, EMPTY_GROUP, shapes
+        );
+        let relationships = std::mem::take(&mut self.relationships);
+        self.slides.push((xml, relationships));
+    }
+
+    // Body text in the content placeholder; tables and images beside it,
+    // or filling the body area when the slide has no text
+    fn content(&mut self, items: &[Item]) -> String {
+        let (x, y, width, height) = BODY_FRAME;
+        let mut images = Vec::new();
+        let mut text_items = Vec::new();
+        for item in items {
+            if let Item::Image { url, alt } = item {
+                match self.embed_image(url) {
+                    Some(image) => images.push((image, alt.as_str())),
+                    None => text_items.push(Item::Text {
+                        spans: vec![Span {
+                            text: format!("[{}]", if alt.is_empty() { url } else { alt }),
+                            italic: true,
+                            ..Span::default()
+                        }],
+                        level: 0,
+                        marker: None,
+                        in_list: false,
+                        style: TextStyle::Normal,
+                    }),
+                }
+            }
+        }
+        let texts: Vec<&Item> = items.iter().filter(|i| !i.is_visual()).chain(text_items.iter()).collect();
+        let tables: Vec<&Item> = items.iter().filter(|i| matches!(i, Item::Table { .. })).collect();
+        let visual_count = images.len() + tables.len();
+
+        let mut out = String::new();
+        let visual_frame = if texts.is_empty() {
+            (x, y, width, height)
+        } else if visual_count == 0 {
+            out.push_str(&self.body(&texts, None));
+            return out;
+        } else {
+            let half = (width - COLUMN_GAP) / 2;
+            out.push_str(&self.body(&texts, Some((x, y, half, height))));
+            (x + half + COLUMN_GAP, y, half, height)
+        };
+
+        // Stack the visuals in their column
+        let (vx, vy, vw, vh) = visual_frame;
+        let slot = vh / visual_count as i64;
+        let mut top = vy;
+        for table in tables {
+            if let Item::Table {
+                alignments,
+                rows,
+                header_rows,
+            } = table
+            {
+                out.push_str(&self.table(alignments, rows, *header_rows, (vx, top, vw, slot)));
+            }
+            top += slot;
+        }
+        for ((rel_id, width_px, height_px), alt) in images {
+            out.push_str(&self.picture(&rel_id, alt, width_px, height_px, (vx, top, vw, slot)));
+            top += slot;
+        }
+        out
+    }
+
+    fn body(&mut self, items: &[&Item], frame: Option<(i64, i64, i64, i64)>) -> String {
+        let mut paragraphs = String::new();
+        for item in items {
+            match item {
+                Item::Text {
+                    spans,
+                    level,
+                    marker,
+                    in_list,
+                    style,
+                } => {
+                    let level = (*level).min(4);
+                    let ppr = match (marker, style) {
+                        (_, TextStyle::Heading) | (None, TextStyle::Quote) => {
+                            r#"<a:pPr marL="0" indent="0"><a:buNone/></a:pPr>"#.to_string()
+                        }
+                        (Some(Marker::Bullet), _) => format!(r#"<a:pPr lvl="{}"/>"#, level),
+                        (Some(Marker::Number(n)), _) => format!(
+                            r#"<a:pPr marL="{}" lvl="{}" indent="-342900"><a:buFont typeface="+mj-lt"/><a:buAutoNum type="arabicPeriod" startAt="{}"/></a:pPr>"#,
+                            342_900 + level as i64 * 457_200,
+                            level,
+                            n
+                        ),
+                        (Some(Marker::Task(checked)), _) => format!(
+                            r#"<a:pPr lvl="{}"><a:buFont typeface="Segoe UI Symbol"/><a:buChar char="{}"/></a:pPr>"#,
+                            level,
+                            if *checked { "\u{2611}" } else { "\u{2610}" }
+                        ),
+                        (None, _) if *in_list => format!(
+                            r#"<a:pPr marL="{}" lvl="{}" indent="0"><a:buNone/></a:pPr>"#,
+                            228_600 + level as i64 * 457_200,
+                            level
+                        ),
+                        (None, _) => r#"<a:pPr marL="0" indent="0"><a:buNone/></a:pPr>"#.to_string(),
+                    };
+                    let spans: Vec<Span> = match style {
+                        TextStyle::Heading => spans.iter().map(|s| Span { bold: true, ..s.clone() }).collect(),
+                        TextStyle::Quote => spans.iter().map(|s| Span { italic: true, ..s.clone() }).collect(),
+                        TextStyle::Normal => spans.clone(),
+                    };
+                    paragraphs.push_str(&self.paragraph_xml(&spans, &ppr, None));
+                }
+                Item::Code(code) => {
+                    for line in code.split('\n') {
+                        let span = Span {
+                            text: line.to_string(),
+                            code: true,
+                            ..Span::default()
+                        };
+                        paragraphs.push_str(&self.paragraph_xml(
+                            &[span],
+                            r#"<a:pPr marL="0" indent="0"><a:spcBef><a:spcPts val="0"/></a:spcBef><a:buNone/></a:pPr>"#,
+                            Some(1400),
+                        ));
+                    }
+                }
+                Item::Table { .. } | Item::Image { .. } => {}
+            }
+        }
+
+        self.shape_id += 1;
+        let xfrm = frame
+            .map(|(x, y, cx, cy)| {
+                format!(r#"<a:xfrm><a:off x="{}" y="{}"/><a:ext cx="{}" cy="{}"/></a:xfrm>"#, x, y, cx, cy)
+            })
+            .unwrap_or_default();
+        format!(
+            r#"<p:sp><p:nvSpPr><p:cNvPr id="{id}" name="Content Placeholder {id}"/><p:cNvSpPr><a:spLocks noGrp="1"/></p:cNvSpPr><p:nvPr><p:ph idx="1"/></p:nvPr></p:nvSpPr><p:spPr>{xfrm}</p:spPr><p:txBody><a:bodyPr><a:normAutofit/></a:bodyPr><a:lstStyle/>{paragraphs}</p:txBody></p:sp>"#,
+            id = self.shape_id,
+            xfrm = xfrm,
+            paragraphs = paragraphs
+        )
+    }
+
+    fn text_placeholder(&mut self, kind: &str, index: Option<u32>, paragraphs: &str) -> String {
+        self.shape_id += 1;
+        let index = index.map(|i| format!(r#" idx="{}""#, i)).unwrap_or_default();
+        format!(
+            r#"<p:sp><p:nvSpPr><p:cNvPr id="{id}" name="Placeholder {id}"/><p:cNvSpPr><a:spLocks noGrp="1"/></p:cNvSpPr><p:nvPr><p:ph type="{kind}"{index}/></p:nvPr></p:nvSpPr><p:spPr/><p:txBody><a:bodyPr/><a:lstStyle/>{paragraphs}</p:txBody></p:sp>"#,
+            id = self.shape_id,
+            kind = kind,
+            index = index,
+            paragraphs = paragraphs
+        )
+    }
+
+    fn table(
+        &mut self,
+        alignments: &[Alignment],
+        rows: &[Vec<Vec<Span>>],
+        header_rows: usize,
+        frame: (i64, i64, i64, i64),
+    ) -> String {
+        let columns = rows.iter().map(|r| r.len()).max().unwrap_or(0).max(alignments.len()).max(1);
+        let (x, y, width, height) = frame;
+        let column_width = width / columns as i64;
+        let table_height = (rows.len() as i64 * TABLE_ROW_HEIGHT).min(height);
+
+        let mut xml = String::new();
+        for row in rows {
+            xml.push_str(&format!(r#"<a:tr h="{}">"#, TABLE_ROW_HEIGHT));
+            // Every row needs a cell for each grid column
+            for column in 0..columns {
+                let ppr = match alignments.get(column) {
+                    Some(Alignment::Center) => r#"<a:pPr algn="ctr"/>"#,
+                    Some(Alignment::Right) => r#"<a:pPr algn="r"/>"#,
+                    _ => "",
+                };
+                let spans = row.get(column).map(|s| s.as_slice()).unwrap_or(&[]);
+                let paragraph = self.paragraph_xml(spans, ppr, Some(1400));
+                xml.push_str(&format!(
+                    r#"<a:tc><a:txBody><a:bodyPr/><a:lstStyle/>{}</a:txBody><a:tcPr/></a:tc>"#,
+                    paragraph
+                ));
+            }
+            xml.push_str("</a:tr>");
+        }
+
+        let grid: String = (0..columns)
+            .map(|_| format!(r#"<a:gridCol w="{}"/>"#, column_width))
+            .collect();
+        self.shape_id += 1;
+        format!(
+            r#"<p:graphicFrame><p:nvGraphicFramePr><p:cNvPr id="{id}" name="Table {id}"/><p:cNvGraphicFramePr><a:graphicFrameLocks noGrp="1"/></p:cNvGraphicFramePr><p:nvPr/></p:nvGraphicFramePr><p:xfrm><a:off x="{x}" y="{y}"/><a:ext cx="{cx}" cy="{cy}"/></p:xfrm><a:graphic><a:graphicData uri="http://schemas.openxmlformats.org/drawingml/2006/table"><a:tbl><a:tblPr firstRow="{first}" bandRow="1"><a:tableStyleId>{style}</a:tableStyleId></a:tblPr><a:tblGrid>{grid}</a:tblGrid>{rows}</a:tbl></a:graphicData></a:graphic></p:graphicFrame>"#,
+            id = self.shape_id,
+            x = x,
+            y = y,
+            cx = column_width * columns as i64,
+            cy = table_height,
+            first = if header_rows > 0 { 1 } else { 0 },
+            style = TABLE_STYLE_ID,
+            grid = grid,
+            rows = xml
+        )
+    }
+
+    // Picture scaled to fit the frame, keeping its aspect ratio, and centred
+    fn picture(&mut self, rel_id: &str, alt: &str, width_px: u32, height_px: u32, frame: (i64, i64, i64, i64)) -> String {
+        let (x, y, width, height) = frame;
+        let natural = (width_px as i64 * EMU_PER_PIXEL, height_px as i64 * EMU_PER_PIXEL);
+        let scale = (width as f64 / natural.0 as f64).min(height as f64 / natural.1 as f64).min(1.0);
+        let (cx, cy) = ((natural.0 as f64 * scale) as i64, (natural.1 as f64 * scale) as i64);
+        let (px, py) = (x + (width - cx) / 2, y + (height - cy) / 2);
+
+        self.shape_id += 1;
+        format!(
+            r#"<p:pic><p:nvPicPr><p:cNvPr id="{id}" name="Picture {id}" descr="{alt}"/><p:cNvPicPr><a:picLocks noChangeAspect="1"/></p:cNvPicPr><p:nvPr/></p:nvPicPr><p:blipFill><a:blip r:embed="{rel}"/><a:stretch><a:fillRect/></a:stretch></p:blipFill><p:spPr><a:xfrm><a:off x="{x}" y="{y}"/><a:ext cx="{cx}" cy="{cy}"/></a:xfrm><a:prstGeom prst="rect"><a:avLst/></a:prstGeom></p:spPr></p:pic>"#,
+            id = self.shape_id,
+            alt = xml_escape(alt),
+            rel = rel_id,
+            x = px,
+            y = py,
+            cx = cx,
+            cy = cy
+        )
+    }
+
+    // A paragraph of runs. Line breaks inside spans become <a:br/>.
+    fn paragraph_xml(&mut self, spans: &[Span], ppr: &str, size: Option<u32>) -> String {
+        let size_attr = size.map(|s| format!(r#" sz="{}""#, s)).unwrap_or_default();
+        let mut runs = String::new();
+        for span in spans {
+            let mut attrs = String::from(r#"lang="en-US""#);
+            if span.bold {
+                attrs.push_str(r#" b="1""#);
+            }
+            if span.italic {
+                attrs.push_str(r#" i="1""#);
+            }
+            if span.strike {
+                attrs.push_str(r#" strike="sngStrike""#);
+            }
+            attrs.push_str(&size_attr);
+
+            let mut children = String::new();
+            if span.code {
+                children.push_str(r#"<a:latin typeface="Consolas"/><a:cs typeface="Consolas"/>"#);
+            }
+            if let Some(link) = span.link.as_ref().filter(|l| !l.starts_with('#')) {
+                let id = self.add_relationship(
+                    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink",
+                    link.clone(),
+                    true,
+                );
+                children.push_str(&format!(r#"<a:hlinkClick r:id="{}"/>"#, id));
+            }
+            let rpr = if children.is_empty() {
+                format!("<a:rPr {}/>", attrs)
+            } else {
+                format!("<a:rPr {}>{}</a:rPr>", attrs, children)
+            };
+
+            for (i, line) in span.text.split('\n').enumerate() {
+                if i > 0 {
+                    runs.push_str(&format!("<a:br>{}</a:br>", rpr));
+                }
+                if !line.is_empty() {
+                    runs.push_str(&format!("<a:r>{}<a:t>{}</a:t></a:r>", rpr, xml_escape(line)));
+                }
+            }
+        }
+        format!(
+            r#"<a:p>{}{}<a:endParaRPr lang="en-US"{}/></a:p>"#,
+            ppr, runs, size_attr
+        )
+    }
+
+    // Add an image to ppt/media (once per file) and relate it to the current
+    // slide. Returns the relationship id and pixel size.
+    fn embed_image(&mut self, url: &str) -> Option<(String, u32, u32)> {
+        let image = media::load_image(self.base_dir, url)?;
+        // PowerPoint cannot display WebP or SVG without a raster fallback
+        if matches!(image.format, ImageFormat::Webp | ImageFormat::Svg) {
+            return None;
+        }
+        let (width, height) = image.size.unwrap_or((800, 600));
+
+        let key = image.path.to_string_lossy().to_string();
+        let name = match self.media_names.get(&key) {
+            Some(name) => name.clone(),
+            None => {
+                let name = format!("image{}.{}", self.media.len() + 1, image.format.extension());
+                self.media.push((name.clone(), image.format, image.data));
+                self.media_names.insert(key, name.clone());
+                name
+            }
+        };
+        let rel_id = self.add_relationship(
+            "http://schemas.openxmlformats.org/officeDocument/2006/relationships/image",
+            format!("../media/{}", name),
+            false,
+        );
+        Some((rel_id, width, height))
+    }
+
+    fn add_relationship(&mut self, kind: &'static str, target: String, external: bool) -> String {
+        let id = format!("rId{}", self.relationships.len() + 1);
+        self.relationships.push(SlideRelationship {
+            id: id.clone(),
+            kind,
+            target,
+            external,
+        });
+        id
+    }
+
+    fn finish(self, title: &str) -> Result<Vec<u8>, String> {
+        let mut package = Package::new();
+        package.add("[Content_Types].xml", self.content_types().as_bytes())?;
+        package.add("_rels/.rels", ROOT_RELS.as_bytes())?;
+        package.add("docProps/core.xml", ooxml::core_properties(title).as_bytes())?;
+        package.add("ppt/presentation.xml", self.presentation().as_bytes())?;
+        package.add("ppt/_rels/presentation.xml.rels", self.presentation_rels().as_bytes())?;
+        package.add("ppt/presProps.xml", template::presentation_properties().as_bytes())?;
+        package.add("ppt/viewProps.xml", template::view_properties().as_bytes())?;
+        package.add("ppt/tableStyles.xml", template::table_styles().as_bytes())?;
+        package.add("ppt/theme/theme1.xml", template::theme().as_bytes())?;
+        package.add("ppt/theme/theme2.xml", template::theme().as_bytes())?;
+        package.add("ppt/slideMasters/slideMaster1.xml", template::slide_master().as_bytes())?;
+        package.add(
+            "ppt/slideMasters/_rels/slideMaster1.xml.rels",
+            template::slide_master_rels().as_bytes(),
+        )?;
+        for (index, layout) in template::slide_layouts().iter().enumerate() {
+            package.add(&format!("ppt/slideLayouts/slideLayout{}.xml", index + 1), layout.as_bytes())?;
+            package.add(
+                &format!("ppt/slideLayouts/_rels/slideLayout{}.xml.rels", index + 1),
+                template::slide_layout_rels().as_bytes(),
+            )?;
+        }
+        package.add("ppt/notesMasters/notesMaster1.xml", template::notes_master().as_bytes())?;
+        package.add(
+            "ppt/notesMasters/_rels/notesMaster1.xml.rels",
+            template::notes_master_rels().as_bytes(),
+        )?;
+
+        for (index, (xml, relationships)) in self.slides.iter().enumerate() {
+            package.add(&format!("ppt/slides/slide{}.xml", index + 1), xml.as_bytes())?;
+            package.add(
+                &format!("ppt/slides/_rels/slide{}.xml.rels", index + 1),
+                relationships_xml(relationships).as_bytes(),
+            )?;
+        }
+        for (number, xml) in &self.notes {
+            package.add(&format!("ppt/notesSlides/notesSlide{}.xml", number), xml.as_bytes())?;
+            let relationships = [
+                SlideRelationship {
+                    id: "rId1".to_string(),
+                    kind: "http://schemas.openxmlformats.org/officeDocument/2006/relationships/notesMaster",
+                    target: "../notesMasters/notesMaster1.xml".to_string(),
+                    external: false,
+                },
+                SlideRelationship {
+                    id: "rId2".to_string(),
+                    kind: "http://schemas.openxmlformats.org/officeDocument/2006/relationships/slide",
+                    target: format!("../slides/slide{}.xml", number),
+                    external: false,
+                },
+            ];
+            package.add(
+                &format!("ppt/notesSlides/_rels/notesSlide{}.xml.rels", number),
+                relationships_xml(&relationships).as_bytes(),
+            )?;
+        }
+        for (name, _, data) in &self.media {
+            package.add(&format!("ppt/media/{}", name), data)?;
+        }
+        package.finish()
+    }
+
+    fn presentation(&self) -> String {
+        let slide_ids: String = (0..self.slides.len())
+            .map(|i| format!(r#"<p:sldId id="{}" r:id="rId{}"/>"#, 256 + i, FIRST_SLIDE_REL + i))
+            .collect();
+        format!(
+            r#"{}<p:presentation {} saveSubsetFonts="1"><p:sldMasterIdLst><p:sldMasterId id="2147483648" r:id="rId1"/></p:sldMasterIdLst><p:notesMasterIdLst><p:notesMasterId r:id="rId2"/></p:notesMasterIdLst><p:sldIdLst>{}</p:sldIdLst><p:sldSz cx="{}" cy="{}"/><p:notesSz cx="6858000" cy="9144000"/></p:presentation>"#,
+            XML_HEADER, NAMESPACES, slide_ids, SLIDE_WIDTH, SLIDE_HEIGHT
+        )
+    }
+
+    fn presentation_rels(&self) -> String {
+        let base = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+        let mut rels = vec![
+            ("slideMaster", "slideMasters/slideMaster1.xml".to_string()),
+            ("notesMaster", "notesMasters/notesMaster1.xml".to_string()),
+            ("theme", "theme/theme1.xml".to_string()),
+            ("presProps", "presProps.xml".to_string()),
+            ("viewProps", "viewProps.xml".to_string()),
+            ("tableStyles", "tableStyles.xml".to_string()),
+        ];
+        for i in 0..self.slides.len() {
+            rels.push(("slide", format!("slides/slide{}.xml", i + 1)));
+        }
+
+        let mut xml = format!(
+            r#"{}<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+            XML_HEADER
+        );
+        for (index, (kind, target)) in rels.iter().enumerate() {
+            xml.push_str(&format!(
+                r#"<Relationship Id="rId{}" Type="{}/{}" Target="{}"/>"#,
+                index + 1,
+                base,
+                kind,
+                target
+            ));
+        }
+        xml.push_str("</Relationships>");
+        xml
+    }
+
+    fn content_types(&self) -> String {
+        let mut defaults = String::new();
+        let mut seen: Vec<ImageFormat> = Vec::new();
+        for (_, format, _) in &self.media {
+            if !seen.contains(format) {
+                seen.push(*format);
+                defaults.push_str(&format!(
+                    r#"<Default Extension="{}" ContentType="{}"/>"#,
+                    format.extension(),
+                    format.mime_type()
+                ));
+            }
+        }
+
+        let presentationml = "application/vnd.openxmlformats-officedocument.presentationml";
+        let mut overrides = vec![
+            ("/ppt/presentation.xml".to_string(), format!("{}.presentation.main+xml", presentationml)),
+            ("/ppt/presProps.xml".to_string(), format!("{}.presProps+xml", presentationml)),
+            ("/ppt/viewProps.xml".to_string(), format!("{}.viewProps+xml", presentationml)),
+            ("/ppt/tableStyles.xml".to_string(), format!("{}.tableStyles+xml", presentationml)),
+            (
+                "/ppt/slideMasters/slideMaster1.xml".to_string(),
+                format!("{}.slideMaster+xml", presentationml),
+            ),
+            (
+                "/ppt/notesMasters/notesMaster1.xml".to_string(),
+                format!("{}.notesMaster+xml", presentationml),
+            ),
+            (
+                "/ppt/theme/theme1.xml".to_string(),
+                "application/vnd.openxmlformats-officedocument.theme+xml".to_string(),
+            ),
+            (
+                "/ppt/theme/theme2.xml".to_string(),
+                "application/vnd.openxmlformats-officedocument.theme+xml".to_string(),
+            ),
+            (
+                "/docProps/core.xml".to_string(),
+                "application/vnd.openxmlformats-package.core-properties+xml".to_string(),
+            ),
+        ];
+        for index in 1..=3 {
+            overrides.push((
+                format!("/ppt/slideLayouts/slideLayout{}.xml", index),
+                format!("{}.slideLayout+xml", presentationml),
+            ));
+        }
+        for index in 1..=self.slides.len() {
+            overrides.push((
+                format!("/ppt/slides/slide{}.xml", index),
+                format!("{}.slide+xml", presentationml),
+            ));
+        }
+        for (number, _) in &self.notes {
+            overrides.push((
+                format!("/ppt/notesSlides/notesSlide{}.xml", number),
+                format!("{}.notesSlide+xml", presentationml),
+            ));
+        }
+
+        let overrides: String = overrides
+            .iter()
+            .map(|(part, kind)| format!(r#"<Override PartName="{}" ContentType="{}"/>"#, part, kind))
+            .collect();
+        format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                "\n",
+                r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">"#,
+                r#"<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>"#,
+                r#"<Default Extension="xml" ContentType="application/xml"/>{}{}</Types>"#
+            ),
+            defaults, overrides
+        )
+    }
+}
+
+// Slides are related from presentation.xml after the six fixed parts
+const FIRST_SLIDE_REL: usize = 7;
+
+fn notes_slide(notes: &[String]) -> String {
+    let paragraphs: String = notes
+        .iter()
+        .flat_map(|note| note.split('\n'))
+        .map(|line| {
+            format!(
+                r#"<a:p><a:r><a:rPr lang="en-US"/><a:t>{}</a:t></a:r></a:p>"#,
+                xml_escape(line)
+            )
+        })
+        .collect();
+    format!(
+        r#"{}<p:notes {}><p:cSld><p:spTree>{}<p:sp><p:nvSpPr><p:cNvPr id="2" name="Slide Image Placeholder 1"/><p:cNvSpPr><a:spLocks noGrp="1" noRot="1" noChangeAspect="1"/></p:cNvSpPr><p:nvPr><p:ph type="sldImg"/></p:nvPr></p:nvSpPr><p:spPr/></p:sp><p:sp><p:nvSpPr><p:cNvPr id="3" name="Notes Placeholder 2"/><p:cNvSpPr><a:spLocks noGrp="1"/></p:cNvSpPr><p:nvPr><p:ph type="body" idx="1"/></p:nvPr></p:nvSpPr><p:spPr/><p:txBody><a:bodyPr/><a:lstStyle/>{}</p:txBody></p:sp></p:spTree></p:cSld><p:clrMapOvr><a:masterClrMapping/></p:clrMapOvr></p:notes>"#,
+        XML_HEADER, NAMESPACES, EMPTY_GROUP, paragraphs
+    )
+}
+
+fn relationships_xml(relationships: &[SlideRelationship]) -> String {
+    let mut xml = format!(
+        r#"{}<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+        XML_HEADER
+    );
+    for rel in relationships {
+        xml.push_str(&format!(
+            r#"<Relationship Id="{}" Type="{}" Target="{}"{}/>"#,
+            rel.id,
+            rel.kind,
+            xml_escape(&rel.target),
+            if rel.external { r#" TargetMode="External""# } else { "" }
+        ));
+    }
+    xml.push_str("</Relationships>");
+    xml
+}
+
+const ROOT_RELS: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    "\n",
+    r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+    r#"<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="ppt/presentation.xml"/>"#,
+    r#"<Relationship Id="rId2" Type="http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties" Target="docProps/core.xml"/>"#,
+    "</Relationships>"
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slides(markdown: &str, strategy: SlideBreakStrategy) -> Vec<Slide> {
+        split_slides(&document::parse(markdown), strategy)
+    }
+
+    fn titles(slides: &[Slide]) -> Vec<String> {
+        slides.iter().map(|s| plain_text(&s.title)).collect()
+    }
+
+    fn texts(slide: &Slide) -> Vec<String> {
+        slide
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Text { spans, .. } => Some(plain_text(spans)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    const DECK: &str = "# Deck\n\nBy the team\n\n## One\n\n- a\n- b\n\n---\n\nAfter the rule\n\n# Part two\n\n## Two\n\nText\n";
+
+    #[test]
+    fn splits_on_headings() {
+        let slides = slides(DECK, SlideBreakStrategy::Headings);
+        assert_eq!(titles(&slides), ["Deck", "One", "Part two", "Two"]);
+        let kinds: Vec<SlideKind> = slides.iter().map(|s| s.kind).collect();
+        assert_eq!(
+            kinds,
+            [SlideKind::Title, SlideKind::Content, SlideKind::Section, SlideKind::Content]
+        );
+        assert_eq!(plain_text(&slides[0].subtitle), "By the team");
+        // The rule does not break the slide
+        assert_eq!(texts(&slides[1]), ["a", "b", "After the rule"]);
+    }
+
+    #[test]
+    fn splits_on_rules() {
+        let slides = slides(DECK, SlideBreakStrategy::Rules);
+        // The title slide still takes only a title and subtitle
+        assert_eq!(titles(&slides), ["Deck", "One", ""]);
+        // Headings inside a slide are kept as text
+        assert_eq!(texts(&slides[2])[..2], ["After the rule", "Part two"]);
+    }
+
+    #[test]
+    fn splits_on_both() {
+        let slides = slides(DECK, SlideBreakStrategy::Both);
+        assert_eq!(titles(&slides), ["Deck", "One", "", "Part two", "Two"]);
+        assert_eq!(texts(&slides[2]), ["After the rule"]);
+    }
+
+    #[test]
+    fn notes_come_from_notes_comments() {
+        assert_eq!(speaker_note("notes: Say this").as_deref(), Some("Say this"));
+        assert_eq!(speaker_note(" Note: Say this ").as_deref(), Some("Say this"));
+        assert_eq!(speaker_note("notes\nSay this\nand that").as_deref(), Some("Say this\nand that"));
+        assert_eq!(speaker_note("NOTES Say this").as_deref(), Some("Say this"));
+        assert_eq!(speaker_note("notes : Say this").as_deref(), Some("Say this"));
+        assert_eq!(speaker_note("notes").as_deref(), Some(""));
+        assert_eq!(speaker_note("Notesworthy: not a note"), None);
+        assert_eq!(speaker_note("TODO fix the chart"), None);
+    }
+
+    #[test]
+    fn notes_attach_to_their_slide() {
+        let markdown = "## One\n\nText\n\n<!-- notes\nSay this -->\n\n<!-- a plain comment -->\n\n## Two\n\n<!-- note: Then this -->\n";
+        let slides = slides(markdown, SlideBreakStrategy::Headings);
+        assert_eq!(slides[0].notes, ["Say this"]);
+        assert_eq!(slides[1].notes, ["Then this"]);
+    }
+
+    #[test]
+    fn long_paragraphs_move_to_the_notes() {
+        let long = format!("This is the point. {}", "Supporting detail goes on. ".repeat(20));
+        let slides = slides(&format!("## Slide\n\n{}\n", long), SlideBreakStrategy::Headings);
+        assert_eq!(texts(&slides[0]), ["This is the point."]);
+        assert_eq!(slides[0].notes, [long.trim()]);
+    }
+
+    #[test]
+    fn first_sentence_falls_back_to_a_word_boundary() {
+        let span = |text: &str| Span {
+            text: text.to_string(),
+            ..Span::default()
+        };
+        let cut = first_sentence(&[span("First part. "), span("Second part.")]);
+        assert_eq!(plain_text(&cut), "First part.");
+
+        let words = "word ".repeat(100);
+        let cut = plain_text(&first_sentence(&[span(&words)]));
+        assert!(cut.ends_with("word\u{2026}"));
+        assert!(cut.chars().count() <= 161);
+    }
+
+    #[test]
+    fn overfull_slides_continue() {
+        let bullets: String = (1..=30).map(|i| format!("- point {}\n", i)).collect();
+        let markdown = format!("## Points\n\n<!-- notes: Keep it short -->\n\n{}", bullets);
+        let slides = slides(&markdown, SlideBreakStrategy::Headings);
+
+        assert_eq!(titles(&slides), ["Points", "Points (cont.)", "Points (cont.)"]);
+        assert!(slides.iter().all(|s| s.items.len() <= MAX_SLIDE_LINES));
+        assert_eq!(slides.iter().map(|s| s.items.len()).sum::<usize>(), 30);
+        assert_eq!(texts(&slides[1])[0], "point 13");
+        // Notes stay with the first slide
+        assert_eq!(slides[0].notes, ["Keep it short"]);
+        assert!(slides[1].notes.is_empty());
+    }
+}
@@ -0,0 +1,677 @@
+use chardetng::EncodingDetector;
+use chrono::{DateTime, Utc};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+use crate::error::AppError;
+
+// Leading bytes inspected to tell text from binary
+const SNIFF_LEN: usize = 8192;
+// Files above this size are not read whole unless asked to
+pub const DEFAULT_MAX_READ_SIZE: u64 = 10 * 1024 * 1024;
+// Block size for reading large files line by line
+const BLOCK_SIZE: usize = 64 * 1024;
+
+pub struct FileSettings {
+    pub max_read_size: u64,
+}
+
+impl FileSettings {
+    pub fn new() -> Self {
+        Self {
+            max_read_size: DEFAULT_MAX_READ_SIZE,
+        }
+    }
+}
+
+pub struct FileSettingsState(pub Arc<Mutex<FileSettings>>);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FileContent {
+    // Text decoded from its detected encoding (a WHATWG name such as
+    // "UTF-8", "UTF-16LE" or "windows-1252"). CRLF files are returned with
+    // "\n" line endings; write_file puts the CRLFs back. Files mixing both
+    // are returned and written back as they are.
+    Text {
+        content: String,
+        encoding: String,
+        bom: bool,
+        line_ending: LineEnding,
+    },
+    Binary {
+        mime: String,
+        size: u64,
+    },
+    // Over the read limit; page through it with read_file_range or
+    // read_file_lines, or read it anyway with `force`
+    TooLarge {
+        size: u64,
+        limit: u64,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileStat {
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+    pub readonly: bool,
+    pub binary: bool,
+    pub mime: Option<String>,
+    // Detected from the start of the file, for text files
+    pub encoding: Option<String>,
+    pub line_ending: Option<LineEnding>,
+    pub too_large: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileChunk {
+    pub content: String,
+    // Byte range actually decoded, moved to character boundaries
+    pub offset: u64,
+    pub end: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileLines {
+    // Lines without their line endings
+    pub lines: Vec<String>,
+    pub start_line: usize,
+    // Known once the end of the file was reached
+    pub total_lines: Option<usize>,
+}
+
+#[tauri::command]
+pub fn stat_file(path: String, settings: State<'_, FileSettingsState>) -> Result<FileStat, AppError> {
+    let file_path = Path::new(&path);
+    let metadata = file_metadata(file_path, &path)?;
+    let limit = settings.0.lock().map_err(|e| e.to_string())?.max_read_size;
+
+    let head = read_head(file_path)?;
+    let text = detect_encoding(&head, head.len() as u64 == metadata.len());
+    let line_ending = text.map(|(encoding, bom_len)| {
+        let (decoded, _) = encoding.decode_without_bom_handling(&head[bom_len..]);
+        detect_line_ending(&decoded)
+    });
+
+    Ok(FileStat {
+        size: metadata.len(),
+        modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+        readonly: metadata.permissions().readonly(),
+        binary: text.is_none(),
+        mime: text.is_none().then(|| mime_type(file_path, &head).to_string()),
+        encoding: text.map(|(encoding, _)| encoding.name().to_string()),
+        line_ending,
+        too_large: metadata.len() > limit,
+    })
+}
+
+#[tauri::command]
+pub fn set_max_read_size(bytes: u64, settings: State<'_, FileSettingsState>) -> Result<(), AppError> {
+    if bytes == 0 {
+        return Err(AppError::invalid("Read size limit must be at least 1 byte"));
+    }
+    settings.0.lock().map_err(|e| e.to_string())?.max_read_size = bytes;
+    Ok(())
+}
+
+// Decode `length` bytes from `offset`. The range is narrowed to whole
+// characters, so `end` is where the next page starts.
+#[tauri::command]
+pub fn read_file_range(path: String, offset: u64, length: u64) -> Result<FileChunk, AppError> {
+    let file_path = Path::new(&path);
+    let size = file_metadata(file_path, &path)?.len();
+    let head = read_head(file_path)?;
+    let (encoding, bom_len) = detect_encoding(&head, head.len() as u64 == size)
+        .ok_or_else(|| AppError::invalid(format!("Cannot read binary file as text: {}", path)))?;
+
+    let mut offset = offset.clamp(bom_len as u64, size);
+    let mut end = offset.saturating_add(length).min(size);
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        offset -= (offset - bom_len as u64) % 2;
+        end -= (end - offset) % 2;
+    }
+
+    let read_error = |e| AppError::io("Failed to read file", file_path, e);
+    let mut file = File::open(file_path).map_err(read_error)?;
+    file.seek(SeekFrom::Start(offset)).map_err(read_error)?;
+    let mut bytes = vec![0; (end - offset) as usize];
+    file.read_exact(&mut bytes).map_err(read_error)?;
+
+    // A character cut at the end is left for the next page, unless the
+    // file itself ends there
+    let at_end = end == size;
+    let mut range = 0..bytes.len();
+    let content = if encoding == UTF_8 {
+        // Skip the tail of a character cut at the start
+        range.start = bytes.iter().take(3).take_while(|b| (**b & 0xC0) == 0x80).count();
+        if let Err(e) = std::str::from_utf8(&bytes[range.clone()]) {
+            if e.error_len().is_none() && !at_end {
+                range.end = range.start + e.valid_up_to();
+            }
+        }
+        encoding.decode_without_bom_handling(&bytes[range.clone()]).0.into_owned()
+    } else if encoding == UTF_16LE || encoding == UTF_16BE {
+        let unit = |at: usize| {
+            let pair = [bytes[at], bytes[at + 1]];
+            if encoding == UTF_16LE {
+                u16::from_le_bytes(pair)
+            } else {
+                u16::from_be_bytes(pair)
+            }
+        };
+        // Surrogate pairs cut at either end
+        if offset > bom_len as u64 && range.len() >= 2 && (0xDC00..0xE000).contains(&unit(0)) {
+            range.start = 2;
+        }
+        if !at_end && range.len() >= 2 && (0xD800..0xDC00).contains(&unit(range.end - 2)) {
+            range.end -= 2;
+        }
+        encoding.decode_without_bom_handling(&bytes[range.clone()]).0.into_owned()
+    } else {
+        // Multibyte legacy encodings: feed the decoder a byte at a time and
+        // end after the last byte that completed a character
+        let mut decoder = encoding.new_decoder_without_bom_handling();
+        let mut content = String::with_capacity(bytes.len());
+        range.end = 0;
+        for (index, byte) in bytes.iter().enumerate() {
+            let last = at_end && index + 1 == bytes.len();
+            content.reserve(decoder.max_utf8_buffer_length(1).unwrap_or(16));
+            let before = content.len();
+            let _ = decoder.decode_to_string(std::slice::from_ref(byte), &mut content, last);
+            if content.len() > before || last {
+                range.end = index + 1;
+            }
+        }
+        content
+    };
+
+    Ok(FileChunk {
+        content,
+        offset: offset + range.start as u64,
+        end: offset + range.end as u64,
+        size,
+    })
+}
+
+// Lines [start_line, start_line + count), counted from 0. The file is
+// decoded block by block, so only the lines up to the range are read.
+#[tauri::command]
+pub fn read_file_lines(path: String, start_line: usize, count: usize) -> Result<FileLines, AppError> {
+    let file_path = Path::new(&path);
+    let size = file_metadata(file_path, &path)?.len();
+    let head = read_head(file_path)?;
+    let (encoding, _) = detect_encoding(&head, head.len() as u64 == size)
+        .ok_or_else(|| AppError::invalid(format!("Cannot read binary file as text: {}", path)))?;
+
+    let read_error = |e| AppError::io("Failed to read file", file_path, e);
+    let mut file = File::open(file_path).map_err(read_error)?;
+    let mut decoder = encoding.new_decoder_with_bom_removal();
+    let mut block = vec![0; BLOCK_SIZE];
+    let mut pending = String::new();
+    let mut lines = Vec::new();
+    let mut line_number = 0;
+    let end_line = start_line.saturating_add(count);
+
+    loop {
+        let read = file.read(&mut block).map_err(read_error)?;
+        let last = read == 0;
+        pending.reserve(decoder.max_utf8_buffer_length(read).unwrap_or(read * 3));
+        let _ = decoder.decode_to_string(&block[..read], &mut pending, last);
+
+        // Every complete line in the decoded text, and the final one at the end
+        let mut consumed = 0;
+        while let Some(newline) = pending[consumed..].find('\n') {
+            let line = &pending[consumed..consumed + newline];
+            if line_number >= start_line && line_number < end_line {
+                lines.push(line.strip_suffix('\r').unwrap_or(line).to_string());
+            }
+            line_number += 1;
+            consumed += newline + 1;
+        }
+        pending.drain(..consumed);
+
+        if last {
+            if !pending.is_empty() {
+                if line_number >= start_line && line_number < end_line {
+                    lines.push(std::mem::take(&mut pending));
+                }
+                line_number += 1;
+            }
+            return Ok(FileLines {
+                lines,
+                start_line,
+                total_lines: Some(line_number),
+            });
+        }
+        if line_number >= end_line {
+            return Ok(FileLines {
+                lines,
+                start_line,
+                total_lines: None,
+            });
+        }
+    }
+}
+
+fn file_metadata(file_path: &Path, path: &str) -> Result<fs::Metadata, AppError> {
+    if !file_path.exists() {
+        return Err(AppError::NotFound { path: path.to_string() });
+    }
+    if !file_path.is_file() {
+        return Err(AppError::NotAFile { path: path.to_string() });
+    }
+    fs::metadata(file_path).map_err(|e| AppError::io("Failed to read file", file_path, e))
+}
+
+fn read_head(file_path: &Path) -> Result<Vec<u8>, AppError> {
+    let read_error = |e| AppError::io("Failed to read file", file_path, e);
+    let file = File::open(file_path).map_err(read_error)?;
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    file.take(SNIFF_LEN as u64).read_to_end(&mut head).map_err(read_error)?;
+    Ok(head)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    // Both LF and CRLF, left alone so saving does not rewrite every line
+    Mixed,
+}
+
+// How a text file is stored, so it can be written back the same way
+#[derive(Debug, Clone, Copy)]
+pub struct TextFormat {
+    pub encoding: &'static Encoding,
+    pub bom: bool,
+    pub line_ending: LineEnding,
+}
+
+impl Default for TextFormat {
+    fn default() -> Self {
+        Self {
+            encoding: UTF_8,
+            bom: false,
+            line_ending: LineEnding::Lf,
+        }
+    }
+}
+
+pub fn decode(path: &Path, bytes: &[u8]) -> FileContent {
+    let Some((text, format)) = decode_text(bytes) else {
+        return FileContent::Binary {
+            mime: mime_type(path, bytes).to_string(),
+            size: bytes.len() as u64,
+        };
+    };
+    let content = match format.line_ending {
+        LineEnding::Crlf => text.replace("\r\n", "\n"),
+        LineEnding::Lf | LineEnding::Mixed => text.into_owned(),
+    };
+    FileContent::Text {
+        content,
+        encoding: format.encoding.name().to_string(),
+        bom: format.bom,
+        line_ending: format.line_ending,
+    }
+}
+
+// Format of existing file content, or None if it is not text
+pub fn text_format(bytes: &[u8]) -> Option<TextFormat> {
+    decode_text(bytes).map(|(_, format)| format)
+}
+
+// Encode text for writing. Characters the encoding cannot represent are an
+// error rather than being replaced, so saving never loses text silently.
+pub fn encode(content: &str, format: &TextFormat) -> Result<Vec<u8>, String> {
+    let text = match format.line_ending {
+        LineEnding::Crlf => Cow::Owned(content.replace("\r\n", "\n").replace('\n', "\r\n")),
+        LineEnding::Lf | LineEnding::Mixed => Cow::Borrowed(content),
+    };
+
+    let mut bytes = Vec::with_capacity(text.len() + 3);
+    // encoding_rs only decodes UTF-16, so it is encoded here
+    if format.encoding == UTF_16LE || format.encoding == UTF_16BE {
+        let little_endian = format.encoding == UTF_16LE;
+        if format.bom {
+            bytes.extend_from_slice(if little_endian { &[0xFF, 0xFE] } else { &[0xFE, 0xFF] });
+        }
+        for unit in text.encode_utf16() {
+            bytes.extend_from_slice(&if little_endian { unit.to_le_bytes() } else { unit.to_be_bytes() });
+        }
+        return Ok(bytes);
+    }
+
+    if format.bom && format.encoding == UTF_8 {
+        bytes.extend_from_slice(&[0xEF, 0xBB, 0xBF]);
+    }
+    let (encoded, _, unmappable) = format.encoding.encode(&text);
+    if unmappable {
+        return Err(format!(
+            "Failed to write file: the text contains characters that {} cannot represent",
+            format.encoding.name()
+        ));
+    }
+    bytes.extend_from_slice(&encoded);
+    Ok(bytes)
+}
+
+// Decoding fails rather than substituting characters, so whatever decodes
+// encodes back to the same bytes
+fn decode_text(bytes: &[u8]) -> Option<(Cow<'_, str>, TextFormat)> {
+    let (encoding, bom_len) = detect_encoding(bytes, true)?;
+    let text = encoding.decode_without_bom_handling_and_without_replacement(&bytes[bom_len..])?;
+    let line_ending = detect_line_ending(&text);
+    Some((
+        text,
+        TextFormat {
+            encoding,
+            bom: bom_len > 0,
+            line_ending,
+        },
+    ))
+}
+
+// Encoding and BOM length, or None for binary content. When `bytes` is
+// only the start of a file, a character cut off at the end is allowed.
+fn detect_encoding(bytes: &[u8], complete: bool) -> Option<(&'static Encoding, usize)> {
+    if let Some(found) = Encoding::for_bom(bytes) {
+        return Some(found);
+    }
+    let head = &bytes[..bytes.len().min(SNIFF_LEN)];
+    if let Some(encoding) = utf16_without_bom(head) {
+        return Some((encoding, 0));
+    }
+    if head.contains(&0) {
+        return None;
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(_) => return Some((UTF_8, 0)),
+        Err(e) if !complete && e.error_len().is_none() => return Some((UTF_8, 0)),
+        Err(_) => {}
+    }
+
+    // Legacy single and multi-byte encodings, e.g. Latin-1 or Shift_JIS
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    Some((detector.guess(None, true), 0))
+}
+
+// UTF-16 without a BOM shows as a zero byte in most code units, always on
+// the same side
+fn utf16_without_bom(head: &[u8]) -> Option<&'static Encoding> {
+    let units = head.len() / 2;
+    if units < 2 {
+        return None;
+    }
+    let (mut high_first, mut high_second) = (0, 0);
+    for unit in head.chunks_exact(2) {
+        high_first += (unit[0] == 0) as usize;
+        high_second += (unit[1] == 0) as usize;
+    }
+    if high_first == 0 && high_second * 10 >= units * 7 {
+        Some(UTF_16LE)
+    } else if high_second == 0 && high_first * 10 >= units * 7 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+// CRLF or LF when every line break is one, otherwise mixed
+fn detect_line_ending(text: &str) -> LineEnding {
+    let breaks = text.matches('\n').count();
+    let crlf = text.matches("\r\n").count();
+    if crlf == 0 {
+        LineEnding::Lf
+    } else if crlf == breaks {
+        LineEnding::Crlf
+    } else {
+        LineEnding::Mixed
+    }
+}
+
+const SIGNATURES: [(&[u8], &str); 21] = [
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"BM", "image/bmp"),
+    (b"II*\x00", "image/tiff"),
+    (b"MM\x00*", "image/tiff"),
+    (b"\x00\x00\x01\x00", "image/x-icon"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"Rar!", "application/vnd.rar"),
+    (b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1", "application/x-ole-storage"),
+    (b"SQLite format 3\x00", "application/vnd.sqlite3"),
+    (b"ID3", "audio/mpeg"),
+    (b"OggS", "audio/ogg"),
+    (b"fLaC", "audio/flac"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+    (b"\x7fELF", "application/x-executable"),
+];
+
+// Mime type from the file's signature, refined or replaced by its extension
+fn mime_type(path: &Path, bytes: &[u8]) -> &'static str {
+    let by_extension = path
+        .extension()
+        .and_then(|e| extension_mime_type(&e.to_string_lossy().to_lowercase()));
+    let by_signature = if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" {
+        match &bytes[8..12] {
+            b"WEBP" => Some("image/webp"),
+            b"WAVE" => Some("audio/wav"),
+            b"AVI " => Some("video/x-msvideo"),
+            _ => None,
+        }
+    } else if bytes.len() >= 8 && &bytes[4..8] == b"ftyp" {
+        Some("video/mp4")
+    } else {
+        SIGNATURES
+            .iter()
+            .find(|(signature, _)| bytes.starts_with(signature))
+            .map(|(_, mime)| *mime)
+    };
+
+    match by_signature {
+        // Office documents, EPUBs and the like are zip packages
+        Some("application/zip") | None => by_extension.or(by_signature).unwrap_or("application/octet-stream"),
+        Some(mime) => mime,
+    }
+}
+
+fn extension_mime_type(extension: &str) -> Option<&'static str> {
+    Some(match extension {
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "doc" => "application/msword",
+        "xls" => "application/vnd.ms-excel",
+        "ppt" => "application/vnd.ms-powerpoint",
+        "odt" => "application/vnd.oasis.opendocument.text",
+        "epub" => "application/epub+zip",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "wasm" => "application/wasm",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Decode as read_file does, then encode as write_file does with the
+    // format detected from the same bytes
+    fn round_trip(bytes: &[u8]) -> (String, LineEnding, Vec<u8>) {
+        let FileContent::Text {
+            content, line_ending, ..
+        } = decode(Path::new("test.txt"), bytes)
+        else {
+            panic!("not decoded as text");
+        };
+        let format = text_format(bytes).unwrap();
+        let encoded = encode(&content, &format).unwrap();
+        (content, line_ending, encoded)
+    }
+
+    #[test]
+    fn utf8_round_trips() {
+        for bytes in [
+            "plain\ntext\n".as_bytes(),
+            "caf\u{e9} \u{2014} \u{1F600}\n".as_bytes(),
+            b"\xEF\xBB\xBFwith a BOM\n",
+        ] {
+            let (_, line_ending, encoded) = round_trip(bytes);
+            assert_eq!(line_ending, LineEnding::Lf);
+            assert_eq!(encoded, bytes);
+        }
+    }
+
+    #[test]
+    fn crlf_is_returned_as_lf_and_written_back() {
+        let bytes = b"one\r\ntwo\r\nthree\r\n";
+        let (content, line_ending, encoded) = round_trip(bytes);
+        assert_eq!(content, "one\ntwo\nthree\n");
+        assert_eq!(line_ending, LineEnding::Crlf);
+        assert_eq!(encoded, bytes);
+    }
+
+    #[test]
+    fn mixed_line_endings_are_kept() {
+        let bytes = b"one\r\ntwo\nthree\r\n";
+        let (content, line_ending, encoded) = round_trip(bytes);
+        assert_eq!(content, "one\r\ntwo\nthree\r\n");
+        assert_eq!(line_ending, LineEnding::Mixed);
+        assert_eq!(encoded, bytes);
+    }
+
+    #[test]
+    fn utf16_round_trips() {
+        let text = "h\u{e9}llo\r\nw\u{f6}rld\r\n";
+        let mut little = vec![0xFF, 0xFE];
+        let mut big = vec![0xFE, 0xFF];
+        for unit in text.encode_utf16() {
+            little.extend_from_slice(&unit.to_le_bytes());
+            big.extend_from_slice(&unit.to_be_bytes());
+        }
+        for bytes in [little, big] {
+            let (content, _, encoded) = round_trip(&bytes);
+            assert_eq!(content, "h\u{e9}llo\nw\u{f6}rld\n");
+            assert_eq!(encoded, bytes);
+        }
+    }
+
+    #[test]
+    fn legacy_encoding_round_trips() {
+        let (bytes, _, _) = encoding_rs::WINDOWS_1252
+            .encode("Le caf\u{e9} \u{e9}tait d\u{e9}j\u{e0} ferm\u{e9}, h\u{e9}las. D\u{e9}sol\u{e9} pour l'\u{e9}t\u{e9}.\n");
+        let (content, _, encoded) = round_trip(&bytes);
+        assert!(content.starts_with("Le caf\u{e9}"));
+        assert_eq!(encoded, bytes.as_ref());
+    }
+
+    #[test]
+    fn binary_is_not_decoded() {
+        let bytes = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR\x00\x00";
+        assert!(matches!(
+            decode(Path::new("image.png"), bytes),
+            FileContent::Binary { ref mime, .. } if mime == "image/png"
+        ));
+    }
+
+    #[test]
+    fn unmappable_characters_are_an_error() {
+        let format = TextFormat {
+            encoding: encoding_rs::WINDOWS_1252,
+            ..TextFormat::default()
+        };
+        assert!(encode("snowman \u{2603}", &format).is_err());
+    }
+
+    // Read a file page by page, checking that every page ends where the
+    // next one starts
+    fn pages(name: &str, bytes: &[u8], length: u64) -> Vec<String> {
+        let path = std::env::temp_dir().join(format!("clause-range-{}-{}", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        let mut pages = Vec::new();
+        let mut offset = 0;
+        loop {
+            let chunk = read_file_range(path.to_string_lossy().to_string(), offset, length).unwrap();
+            if chunk.offset == chunk.size {
+                break;
+            }
+            assert!(offset == 0 || chunk.offset == offset);
+            assert!(chunk.end > chunk.offset);
+            pages.push(chunk.content);
+            offset = chunk.end;
+        }
+        fs::remove_file(&path).unwrap();
+        pages
+    }
+
+    #[test]
+    fn utf8_ranges_keep_whole_characters() {
+        let text = "caf\u{e9} \u{1F600} na\u{ef}ve \u{4e16}\u{754c}\n".repeat(3);
+        for length in [4, 5, 7] {
+            let pages = pages("utf8", text.as_bytes(), length);
+            assert!(pages.iter().all(|page| !page.contains('\u{FFFD}')));
+            assert_eq!(pages.concat(), text);
+        }
+    }
+
+    #[test]
+    fn utf16_ranges_keep_surrogate_pairs() {
+        let text = "a\u{1F600}\u{1F680}b\u{e9}\u{1F600}\n".repeat(3);
+        let mut little = vec![0xFF, 0xFE];
+        let mut big = vec![0xFE, 0xFF];
+        for unit in text.encode_utf16() {
+            little.extend_from_slice(&unit.to_le_bytes());
+            big.extend_from_slice(&unit.to_be_bytes());
+        }
+        for (name, bytes) in [("utf16le", little), ("utf16be", big)] {
+            for length in [4, 5, 6] {
+                let pages = pages(name, &bytes, length);
+                assert!(pages.iter().all(|page| !page.contains('\u{FFFD}')));
+                assert_eq!(pages.concat(), text);
+            }
+        }
+    }
+
+    #[test]
+    fn legacy_ranges_keep_multibyte_characters() {
+        let text = "\u{65e5}\u{672c}\u{8a9e}\u{306e}\u{6587}\u{7ae0}\u{3067}\u{3059}\u{3002}\u{3053}\u{308c}\u{306f}\u{30c6}\u{30b9}\u{30c8}\u{3067}\u{3059}\u{3002}abc\n".repeat(20);
+        let (bytes, _, _) = encoding_rs::SHIFT_JIS.encode(&text);
+        assert_eq!(text_format(&bytes).unwrap().encoding, encoding_rs::SHIFT_JIS);
+        for length in [3, 5, 64] {
+            let pages = pages("sjis", &bytes, length);
+            assert!(pages.iter().all(|page| !page.contains('\u{FFFD}')));
+            assert_eq!(pages.concat(), text);
+        }
+    }
+
+    #[test]
+    fn a_character_cut_by_the_end_of_the_file_is_still_read() {
+        // UTF-16LE "ab" and the first half of a surrogate pair
+        let bytes = b"\xFF\xFEa\x00b\x00\x3D\xD8";
+        assert_eq!(pages("utf16-cut", bytes, 4).concat(), "ab\u{FFFD}");
+    }
+}
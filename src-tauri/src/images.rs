@@ -0,0 +1,174 @@
+use base64::Engine;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use serde::Serialize;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use crate::error::AppError;
+use crate::export::media::{self, ImageFormat};
+
+// Longest side Claude looks at; larger images are scaled down to it
+const MAX_EDGE: u32 = 1568;
+// Claude takes images up to 5 MB once base64 encoded, which adds a third
+const MAX_BYTES: usize = 3_750_000;
+// Smaller sizes tried for images still too large at MAX_EDGE
+const SHRINK_ATTEMPTS: usize = 4;
+const JPEG_QUALITY: u8 = 85;
+
+// An image ready to be sent as a base64 content block
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageData {
+    pub media_type: String,
+    pub width: u32,
+    pub height: u32,
+    // Size of the file on disk, when it was scaled down to fit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_size: Option<(u32, u32)>,
+    #[serde(skip)]
+    pub data: String,
+}
+
+// Read a PNG, JPEG, GIF or WebP file. Images within the limits are sent as
+// they are; others are scaled down and re-encoded, JPEGs as JPEG and the
+// rest as PNG so transparency is kept.
+pub fn load(path: &Path) -> Result<ImageData, AppError> {
+    let bytes = fs::read(path).map_err(|e| AppError::io("Failed to read image", path, e))?;
+    let format = media::detect_format(&bytes)
+        .filter(|f| matches!(f, ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::Webp))
+        .ok_or_else(|| {
+            AppError::invalid(format!("Not a PNG, JPEG, GIF or WebP image: {}", path.display()))
+        })?;
+
+    if let Some((width, height)) = media::dimensions(&bytes, format) {
+        if width.max(height) <= MAX_EDGE && bytes.len() <= MAX_BYTES {
+            return Ok(ImageData {
+                media_type: format.mime_type().to_string(),
+                width,
+                height,
+                original_size: None,
+                data: base64::engine::general_purpose::STANDARD.encode(&bytes),
+            });
+        }
+    }
+
+    let decoded = image::load_from_memory_with_format(&bytes, codec_format(format))
+        .map_err(|e| AppError::invalid(format!("Failed to decode image {}: {}", path.display(), e)))?;
+    let original_size = (decoded.width(), decoded.height());
+
+    let mut edge = original_size.0.max(original_size.1).min(MAX_EDGE);
+    for _ in 0..SHRINK_ATTEMPTS {
+        let resized = if original_size.0.max(original_size.1) > edge {
+            decoded.resize(edge, edge, FilterType::Lanczos3)
+        } else {
+            decoded.clone()
+        };
+        let (media_type, encoded) = encode(&resized, format)
+            .map_err(|e| format!("Failed to scale down image {}: {}", path.display(), e))?;
+        if encoded.len() <= MAX_BYTES {
+            let size = (resized.width(), resized.height());
+            return Ok(ImageData {
+                media_type: media_type.to_string(),
+                width: size.0,
+                height: size.1,
+                // Re-encoding alone keeps the size
+                original_size: (size != original_size).then_some(original_size),
+                data: base64::engine::general_purpose::STANDARD.encode(&encoded),
+            });
+        }
+        edge = edge * 3 / 4;
+    }
+
+    Err(AppError::invalid(format!(
+        "Image is too large to send even when scaled down: {}",
+        path.display()
+    )))
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat) -> Result<(&'static str, Vec<u8>), image::ImageError> {
+    let mut encoded = Vec::new();
+    if format == ImageFormat::Jpeg {
+        JpegEncoder::new_with_quality(&mut encoded, JPEG_QUALITY).encode_image(&image.to_rgb8())?;
+        Ok(("image/jpeg", encoded))
+    } else {
+        image.write_to(&mut Cursor::new(&mut encoded), image::ImageFormat::Png)?;
+        Ok(("image/png", encoded))
+    }
+}
+
+fn codec_format(format: ImageFormat) -> image::ImageFormat {
+    match format {
+        ImageFormat::Jpeg => image::ImageFormat::Jpeg,
+        ImageFormat::Gif => image::ImageFormat::Gif,
+        ImageFormat::Webp => image::ImageFormat::WebP,
+        _ => image::ImageFormat::Png,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn write_png(name: &str, image: &RgbImage) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("clause-image-{}-{}.png", std::process::id(), name));
+        image.save(&path).unwrap();
+        path
+    }
+
+    fn load_png(name: &str, image: &RgbImage) -> Result<ImageData, AppError> {
+        let path = write_png(name, image);
+        let result = load(&path);
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn small_images_are_sent_as_they_are() {
+        let path = write_png("small", &RgbImage::from_pixel(40, 30, Rgb([200, 10, 10])));
+        let bytes = fs::read(&path).unwrap();
+        let image = load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(image.media_type, "image/png");
+        assert_eq!((image.width, image.height), (40, 30));
+        assert_eq!(image.original_size, None);
+        assert_eq!(image.data, base64::engine::general_purpose::STANDARD.encode(bytes));
+    }
+
+    #[test]
+    fn large_images_are_scaled_to_the_max_edge() {
+        let image = load_png("wide", &RgbImage::from_pixel(3000, 1200, Rgb([0, 120, 255]))).unwrap();
+        assert_eq!((image.width, image.height), (MAX_EDGE, 627));
+        assert_eq!(image.original_size, Some((3000, 1200)));
+    }
+
+    #[test]
+    fn images_over_the_byte_limit_shrink_until_they_fit() {
+        // Noise does not compress, so this is over MAX_BYTES at full size
+        let mut seed: u32 = 1;
+        let noise = RgbImage::from_fn(MAX_EDGE, MAX_EDGE, |_, _| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let [a, b, c, _] = seed.to_le_bytes();
+            Rgb([a, b, c])
+        });
+        let image = load_png("noise", &noise).unwrap();
+
+        // 1568 and 1176 are still too large
+        assert_eq!((image.width, image.height), (882, 882));
+        assert_eq!(image.original_size, Some((MAX_EDGE, MAX_EDGE)));
+        let decoded = base64::engine::general_purpose::STANDARD.decode(&image.data).unwrap();
+        assert!(decoded.len() <= MAX_BYTES);
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        let path = std::env::temp_dir().join(format!("clause-image-{}-text.png", std::process::id()));
+        fs::write(&path, "not an image").unwrap();
+        let result = load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
@@ -1,12 +1,37 @@
 use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher, EventKind};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::fs;
 use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
-use std::process::{Child, ChildStdin, Command, Stdio};
-use std::sync::{Arc, Mutex};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
-use tauri::{AppHandle, Emitter, State};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+mod attachments;
+mod budget;
+mod claude_cli;
+mod claude_errors;
+mod documents;
+mod error;
+mod export;
+mod files;
+mod images;
+mod references;
+mod research;
+mod tool_results;
+
+use attachments::{Attachment, ContentBlock};
+use budget::{ContextEstimate, Fit};
+use claude_cli::{ClaudeCli, ClaudeCliState};
+use claude_errors::ErrorInfo;
+use error::AppError;
+use files::{FileContent, FileSettings, FileSettingsState, LineEnding};
+use research::{ResearchManager, ResearchState};
+use tool_results::{ToolCall, ToolResultDetails};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileEntry {
@@ -22,11 +47,14 @@ pub struct FileChangeEvent {
     pub kind: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ClaudeEvent {
     #[serde(rename = "type")]
     pub event_type: String,
     pub session_id: String,
+    // The message this event answers or reports on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub text: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -37,8 +65,64 @@ pub struct ClaudeEvent {
     pub tool_input: Option<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_result: Option<String>,
+    // Tool results carry is_error, summary, truncation and the files they touched
+    #[serde(flatten)]
+    pub tool_details: Option<ToolResultDetails>,
+    // Complete events carry stop_reason and usage
+    #[serde(flatten)]
+    pub turn_info: Option<TurnInfo>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    // Known failures carry error_kind, hint and reset_at
+    #[serde(flatten)]
+    pub error_info: Option<ErrorInfo>,
+}
+
+impl ClaudeEvent {
+    // An event with only its type and session set; literals fill in the rest
+    // with `..ClaudeEvent::new(..)`
+    pub fn new(event_type: &str, session_id: &str) -> Self {
+        Self {
+            event_type: event_type.to_string(),
+            session_id: session_id.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+// How a turn ended: why Claude stopped, and the tokens it used
+#[derive(Debug, Clone, Serialize)]
+pub struct TurnInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<serde_json::Value>,
+}
+
+// What the stdout reader remembers between lines of one process's output
+#[derive(Default)]
+struct StreamState {
+    // Tool calls waiting for their result, so results can name their tool
+    tool_calls: HashMap<String, ToolCall>,
+    // The last assistant message of the turn, for its stop_reason and usage
+    stop_reason: Option<String>,
+    usage: Option<serde_json::Value>,
+}
+
+// Numbers every ClaudeEvent in the order it is emitted, so the frontend can
+// put events back in order and notice ones it missed
+static EVENT_SEQ: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Clone, Serialize)]
+struct SequencedEvent {
+    seq: u64,
+    #[serde(flatten)]
+    event: ClaudeEvent,
+}
+
+fn emit_claude_event(app_handle: &AppHandle, event: ClaudeEvent) {
+    let seq = EVENT_SEQ.fetch_add(1, Ordering::SeqCst);
+    let _ = app_handle.emit("claude-event", SequencedEvent { seq, event });
 }
 
 // Persistent Claude session state
@@ -47,6 +131,28 @@ pub struct ClaudeSession {
     child: Option<Child>,
     session_id: Option<String>,
     working_dir: String,
+    // Whether Claude's .docx edits are written as tracked changes
+    track_changes: bool,
+    // Messages in the order they were sent. Only the first is ever being
+    // answered; the next is written to Claude when its result arrives.
+    queue: VecDeque<QueuedMessage>,
+    // Bumped for every process so the reader of a replaced one can tell
+    generation: u64,
+    // Crash restarts since the last answered message
+    restarts: u32,
+    // A crashed process is about to be restarted; messages wait for it
+    restarting: bool,
+    // A process is being started in the background; messages wait for it
+    starting: bool,
+    // Why the process was given up on after crashing, reported to the next
+    // message sent to the same folder
+    crashed: Option<String>,
+    // Characters of each tool result passed on to the frontend
+    tool_result_limit: usize,
+    // Model named when the process started, and the tokens the
+    // conversation takes up as of Claude's last message
+    model: Option<String>,
+    context_used: usize,
 }
 
 impl ClaudeSession {
@@ -56,22 +162,75 @@ impl ClaudeSession {
             child: None,
             session_id: None,
             working_dir: String::new(),
+            track_changes: false,
+            queue: VecDeque::new(),
+            generation: 0,
+            restarts: 0,
+            restarting: false,
+            starting: false,
+            crashed: None,
+            tool_result_limit: tool_results::DEFAULT_RESULT_LIMIT,
+            model: None,
+            context_used: 0,
         }
     }
 }
 
+impl ClaudeSession {
+    // Model and tokens used by the conversation a message for `working_dir`
+    // would join; another folder starts a new one
+    fn context_for(&self, working_dir: &str) -> (Option<String>, usize) {
+        let context_used = if self.working_dir == working_dir { self.context_used } else { 0 };
+        (self.model.clone(), context_used)
+    }
+
+    // Request id of the message Claude is answering
+    fn current_request(&self) -> Option<String> {
+        self.queue
+            .front()
+            .filter(|message| message.state == MessageState::Started)
+            .map(|message| message.request_id.clone())
+    }
+}
+
 pub struct ClaudeSessionState(pub Arc<Mutex<ClaudeSession>>);
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageState {
+    Queued,
+    // Written to Claude, which is answering it
+    Started,
+}
+
+// A user message waiting for or getting an answer. Answered messages leave
+// the queue with a finished event, removed ones with a removed event.
+#[derive(Debug, Clone, Serialize)]
+pub struct QueuedMessage {
+    pub request_id: String,
+    pub message: String,
+    pub state: MessageState,
+    // The stream-json line for Claude's stdin
+    #[serde(skip)]
+    line: String,
+}
+
+// Restarts after a crash before giving up, waiting 1s, 2s, 4s between them
+const MAX_RESTARTS: u32 = 3;
+const RESTART_BACKOFF: Duration = Duration::from_secs(1);
+// Lines of stderr sent with a session_crashed event
+const STDERR_TAIL_LINES: usize = 20;
+
 #[tauri::command]
-fn list_directory(path: String) -> Result<Vec<FileEntry>, String> {
+fn list_directory(path: String) -> Result<Vec<FileEntry>, AppError> {
     let dir_path = Path::new(&path);
 
     if !dir_path.exists() {
-        return Err(format!("Directory does not exist: {}", path));
+        return Err(AppError::NotFound { path });
     }
 
     if !dir_path.is_dir() {
-        return Err(format!("Path is not a directory: {}", path));
+        return Err(AppError::NotADirectory { path });
     }
 
     let mut entries: Vec<FileEntry> = Vec::new();
@@ -99,7 +258,7 @@ fn list_directory(path: String) -> Result<Vec<FileEntry>, String> {
                 }
             }
         }
-        Err(e) => return Err(format!("Failed to read directory: {}", e)),
+        Err(e) => return Err(AppError::io("Failed to read directory", dir_path, e)),
     }
 
     // Sort: directories first, then files, alphabetically
@@ -114,255 +273,616 @@ fn list_directory(path: String) -> Result<Vec<FileEntry>, String> {
     Ok(entries)
 }
 
+// Text comes back decoded from whatever encoding the file uses; binary
+// files are reported with their mime type instead of being decoded.
+// Files over the read limit are only read with `force`.
 #[tauri::command]
-fn read_file(path: String) -> Result<String, String> {
+fn read_file(
+    path: String,
+    force: Option<bool>,
+    settings: State<'_, FileSettingsState>,
+) -> Result<FileContent, AppError> {
     let file_path = Path::new(&path);
 
     if !file_path.exists() {
-        return Err(format!("File does not exist: {}", path));
+        return Err(AppError::NotFound { path });
     }
 
     if !file_path.is_file() {
-        return Err(format!("Path is not a file: {}", path));
+        return Err(AppError::NotAFile { path });
     }
 
-    match fs::read_to_string(file_path) {
-        Ok(content) => Ok(content),
-        Err(e) => Err(format!("Failed to read file: {}", e)),
+    let size = fs::metadata(file_path)
+        .map_err(|e| AppError::io("Failed to read file", file_path, e))?
+        .len();
+    let limit = settings.0.lock().map_err(|e| e.to_string())?.max_read_size;
+    if size > limit && !force.unwrap_or(false) {
+        return Ok(FileContent::TooLarge { size, limit });
+    }
+
+    match fs::read(file_path) {
+        Ok(bytes) => Ok(files::decode(file_path, &bytes)),
+        Err(e) => Err(AppError::io("Failed to read file", file_path, e)),
     }
 }
 
+// An existing file keeps its encoding, BOM and line endings unless they
+// are given; new files are UTF-8 with LF line endings.
 #[tauri::command]
-fn write_file(path: String, content: String) -> Result<(), String> {
+fn write_file(
+    path: String,
+    content: String,
+    encoding: Option<String>,
+    line_ending: Option<LineEnding>,
+    bom: Option<bool>,
+) -> Result<(), AppError> {
     let file_path = Path::new(&path);
 
     // Ensure parent directory exists
     if let Some(parent) = file_path.parent() {
         if !parent.exists() {
             if let Err(e) = fs::create_dir_all(parent) {
-                return Err(format!("Failed to create parent directories: {}", e));
+                return Err(AppError::io("Failed to create parent directories", parent, e));
             }
         }
     }
 
-    match fs::write(file_path, content) {
+    let mut format = fs::read(file_path)
+        .ok()
+        .and_then(|bytes| files::text_format(&bytes))
+        .unwrap_or_default();
+    if let Some(label) = encoding {
+        format.encoding = encoding_rs::Encoding::for_label(label.as_bytes())
+            .ok_or_else(|| AppError::invalid(format!("Unknown encoding: {}", label)))?;
+    }
+    if let Some(line_ending) = line_ending {
+        format.line_ending = line_ending;
+    }
+    if let Some(bom) = bom {
+        format.bom = bom;
+    }
+    let bytes = files::encode(&content, &format).map_err(AppError::invalid)?;
+
+    match fs::write(file_path, bytes) {
         Ok(()) => Ok(()),
-        Err(e) => Err(format!("Failed to write file: {}", e)),
+        Err(e) => Err(AppError::io("Failed to write file", file_path, e)),
     }
 }
 
+// Whether the Claude CLI was found and is recent enough; get_claude_status
+// has the details
 #[tauri::command]
-fn check_claude_available() -> Result<bool, String> {
-    match Command::new("claude")
-        .arg("--version")
-        .output()
-    {
-        Ok(output) => Ok(output.status.success()),
-        Err(_) => Ok(false),
-    }
+async fn check_claude_available(cli: State<'_, ClaudeCliState>) -> Result<bool, AppError> {
+    claude_cli::is_available(&cli.0)
 }
 
-// Start or get the persistent Claude session
-fn ensure_claude_session(
-    session_state: &Arc<Mutex<ClaudeSession>>,
+// Make the session fit the working dir and tracking mode, stopping a
+// process started for others. Returns whether a new one must be started.
+fn prepare_session(
+    session: &mut ClaudeSession,
     working_dir: &str,
+    track_changes: bool,
     app_handle: &AppHandle,
-) -> Result<(), String> {
-    let mut session = session_state.lock().map_err(|e| e.to_string())?;
+) -> bool {
+    // A crashed session restarts by itself and then sends queued messages,
+    // so it counts as running
+    let same_session = session.working_dir == working_dir && session.track_changes == track_changes;
+    if same_session && (session.stdin.is_some() || session.restarting || session.starting) {
+        return false;
+    }
 
-    // Check if we need a new session (different working dir or no session)
-    let needs_new_session = session.stdin.is_none() || session.working_dir != working_dir;
+    // Kill existing process if any
+    if let Some(mut child) = session.child.take() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    // The old reader must not take this for a crash
+    session.generation += 1;
+    session.stdin = None;
+    session.session_id = None;
+    session.context_used = 0;
+    for message in session.queue.drain(..) {
+        emit_request_event("removed", &message.request_id, app_handle);
+    }
+    session.restarts = 0;
+    session.restarting = false;
+    session.crashed = None;
+    session.starting = true;
+    session.working_dir = working_dir.to_string();
+    session.track_changes = track_changes;
+    true
+}
+
+// Start Claude for a prepared session and send it the queued messages.
+// Finding the binary can take seconds the first time, so this runs on its
+// own thread with the session unlocked until the process is spawned.
+fn start_session(session_state: Arc<Mutex<ClaudeSession>>, generation: u64, app_handle: AppHandle) {
+    thread::spawn(move || {
+        let cmd = claude_cli::command(&app_handle.state::<ClaudeCliState>().0);
 
-    if needs_new_session {
-        // Kill existing process if any
-        if let Some(mut child) = session.child.take() {
-            let _ = child.kill();
+        let Ok(mut session) = session_state.lock() else { return };
+        // Replaced by a session for another folder while looking
+        if session.generation != generation {
+            return;
         }
-        session.stdin = None;
-        session.session_id = None;
+        session.starting = false;
+
+        match cmd.and_then(|cmd| spawn_claude(&mut session, &session_state, cmd, None, &app_handle)) {
+            Ok(()) => send_next(&mut session, &app_handle),
+            Err(e) => {
+                // Every waiting message fails with the reason
+                let error_info = claude_errors::classify(&e.to_string());
+                for message in session.queue.drain(..) {
+                    let event = ClaudeEvent {
+                        request_id: Some(message.request_id.clone()),
+                        error: Some(e.to_string()),
+                        error_info: error_info.clone(),
+                        ..ClaudeEvent::new("error", "")
+                    };
+                    emit_claude_event(&app_handle, event);
+                    emit_request_event("removed", &message.request_id, &app_handle);
+                }
+            }
+        }
+    });
+}
 
-        // Start new persistent Claude process with focused system prompt
-        let system_prompt = r#"You are a fast markdown editing assistant in Clause editor.
+// Start a Claude process for the session's working dir and tracking mode,
+// resuming an earlier conversation when given its session id
+fn spawn_claude(
+    session: &mut ClaudeSession,
+    session_state: &Arc<Mutex<ClaudeSession>>,
+    mut cmd: Command,
+    resume: Option<&str>,
+    app_handle: &AppHandle,
+) -> Result<(), AppError> {
+    // Start new persistent Claude process with focused system prompt
+    let system_prompt = r#"You are a fast markdown editing assistant in Clause editor.
 RULES:
 1. Use Edit tool IMMEDIATELY - no exploration, no questions
 2. Make minimal, targeted edits
 3. Reply with ONE sentence summary after editing
 4. Never explain what you'll do - just do it
+5. For .docx files use the docx_* tools, never Write; call docx_read first for paragraph numbers
 Be fast. Be direct. Edit now."#;
 
-        let mut cmd = Command::new("claude");
-        cmd.arg("--print")
-            .arg("--verbose") // Required for stream-json output
-            .arg("--output-format")
-            .arg("stream-json")
-            .arg("--input-format")
-            .arg("stream-json")
-            .arg("--permission-mode")
-            .arg("acceptEdits")
-            .arg("--append-system-prompt")
-            .arg(system_prompt)
-            .arg("--allowedTools")
-            .arg("Edit,Read,Write")
-            .current_dir(working_dir)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-
-        let mut child = cmd.spawn().map_err(|e| format!("Failed to spawn claude: {}", e))?;
-
-        let stdin = child.stdin.take().ok_or("Failed to get stdin")?;
-        let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
-        let stderr = child.stderr.take().ok_or("Failed to get stderr")?;
-
-        session.stdin = Some(stdin);
-        session.child = Some(child);
-        session.working_dir = working_dir.to_string();
-
-        // Spawn thread to read stderr and emit error events
-        let app_handle_stderr = app_handle.clone();
-        thread::spawn(move || {
-            let reader = BufReader::new(stderr);
-            let mut error_buffer = String::new();
-
-            for line in reader.lines() {
-                match line {
-                    Ok(line) => {
-                        if line.trim().is_empty() {
-                            continue;
-                        }
-                        // Accumulate stderr output
-                        if !error_buffer.is_empty() {
-                            error_buffer.push('\n');
-                        }
-                        error_buffer.push_str(&line);
+    cmd.arg("--print")
+        .arg("--verbose") // Required for stream-json output
+        .arg("--output-format")
+        .arg("stream-json")
+        .arg("--input-format")
+        .arg("stream-json")
+        .arg("--permission-mode")
+        .arg("acceptEdits")
+        .arg("--append-system-prompt")
+        .arg(system_prompt)
+        .current_dir(&session.working_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    // Word documents are edited through our own tools server
+    let mut allowed_tools = String::from("Edit,Read,Write");
+    if let Some(config) = documents::mcp_config(session.track_changes) {
+        cmd.arg("--mcp-config").arg(config);
+        allowed_tools.push_str(&format!(",{}", documents::DOCX_TOOLS));
+    }
+    cmd.arg("--allowedTools").arg(allowed_tools);
 
-                        // Emit error event immediately for critical errors
-                        if line.contains("Error:") || line.contains("error:") {
-                            let event = ClaudeEvent {
-                                event_type: "error".to_string(),
-                                session_id: String::new(),
-                                text: None,
-                                tool_id: None,
-                                tool_name: None,
-                                tool_input: None,
-                                tool_result: None,
-                                error: Some(line.clone()),
-                            };
-                            let _ = app_handle_stderr.emit("claude-event", event);
+    if let Some(session_id) = resume {
+        cmd.arg("--resume").arg(session_id);
+    }
+
+    let mut child = cmd.spawn().map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => AppError::ClaudeNotInstalled,
+        _ => AppError::from(format!("Failed to spawn claude: {}", e)),
+    })?;
+
+    let stdin = child.stdin.take().ok_or("Failed to get stdin")?;
+    let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
+    let stderr = child.stderr.take().ok_or("Failed to get stderr")?;
+
+    session.stdin = Some(stdin);
+    session.child = Some(child);
+    session.generation += 1;
+    let generation = session.generation;
+
+    // Spawn thread to read stderr and emit error events. What was written
+    // goes to the stdout reader when stderr closes, to report the exit.
+    let app_handle_stderr = app_handle.clone();
+    let session_state_stderr = Arc::clone(session_state);
+    let (stderr_tx, stderr_rx) = mpsc::channel::<String>();
+    thread::spawn(move || {
+        let reader = BufReader::new(stderr);
+        let mut error_buffer = String::new();
+        let mut reported = Vec::new();
+
+        for line in reader.lines() {
+            match line {
+                Ok(line) => {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    // Accumulate stderr output
+                    if !error_buffer.is_empty() {
+                        error_buffer.push('\n');
+                    }
+                    error_buffer.push_str(&line);
+
+                    // Emit error event immediately for known failures, once
+                    // per kind, and other critical errors
+                    let error_info = claude_errors::classify(&line);
+                    if let Some(info) = &error_info {
+                        if reported.contains(&info.error_kind) {
+                            continue;
                         }
+                        reported.push(info.error_kind);
+                    }
+                    if error_info.is_some() || line.contains("Error:") || line.contains("error:") {
+                        let request_id = session_state_stderr
+                            .lock()
+                            .ok()
+                            .filter(|session| session.generation == generation)
+                            .and_then(|session| session.current_request());
+                        let event = ClaudeEvent {
+                            request_id,
+                            error: Some(line.clone()),
+                            error_info,
+                            ..ClaudeEvent::new("error", "")
+                        };
+                        emit_claude_event(&app_handle_stderr, event);
                     }
-                    Err(_) => break,
                 }
+                Err(_) => break,
             }
+        }
 
-            // Emit any remaining buffered errors when stderr closes
-            if !error_buffer.is_empty() && !error_buffer.contains("Error:") {
-                let event = ClaudeEvent {
-                    event_type: "error".to_string(),
-                    session_id: String::new(),
-                    text: None,
-                    tool_id: None,
-                    tool_name: None,
-                    tool_input: None,
-                    tool_result: None,
-                    error: Some(format!("Claude stderr: {}", error_buffer)),
-                };
-                let _ = app_handle_stderr.emit("claude-event", event);
-            }
-        });
-
-        // Spawn thread to read stdout and emit events
-        let app_handle_clone = app_handle.clone();
-        let session_state_clone = Arc::clone(session_state);
+        let _ = stderr_tx.send(error_buffer);
+    });
 
-        thread::spawn(move || {
-            let reader = BufReader::new(stdout);
+    // Spawn thread to read stdout and emit events
+    let app_handle_clone = app_handle.clone();
+    let session_state_clone = Arc::clone(session_state);
 
-            for line in reader.lines() {
-                match line {
-                    Ok(line) => {
-                        if line.trim().is_empty() {
-                            continue;
-                        }
+    thread::spawn(move || {
+        let reader = BufReader::new(stdout);
+        let mut stream = StreamState::default();
 
-                        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&line) {
-                            let msg_session_id = json.get("session_id")
-                                .and_then(|v| v.as_str())
-                                .unwrap_or("")
-                                .to_string();
+        for line in reader.lines() {
+            match line {
+                Ok(line) => {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
 
-                            // Store session_id for future use
-                            if !msg_session_id.is_empty() {
-                                if let Ok(mut session) = session_state_clone.lock() {
+                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(&line) {
+                        let msg_session_id = json.get("session_id")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("")
+                            .to_string();
+                        let event_type = json.get("type").and_then(|v| v.as_str());
+
+                        let mut request_id = None;
+                        let mut result_limit = tool_results::DEFAULT_RESULT_LIMIT;
+                        if let Ok(mut session) = session_state_clone.lock() {
+                            result_limit = session.tool_result_limit;
+                            // Output from a replaced process no longer touches the session
+                            if session.generation == generation {
+                                // Store session_id for future use
+                                if !msg_session_id.is_empty() {
                                     session.session_id = Some(msg_session_id.clone());
                                 }
+                                if let Some(model) = json.get("model").and_then(|v| v.as_str()) {
+                                    session.model = Some(model.to_string());
+                                }
+                                // Everything Claude read and wrote so far is in the next request
+                                if let Some(usage) = json.get("message").and_then(|m| m.get("usage")) {
+                                    let used: u64 = ["input_tokens", "cache_creation_input_tokens", "cache_read_input_tokens", "output_tokens"]
+                                        .iter()
+                                        .filter_map(|key| usage.get(key).and_then(|v| v.as_u64()))
+                                        .sum();
+                                    if used > 0 {
+                                        session.context_used = used as usize;
+                                    }
+                                }
+                                request_id = session.current_request();
                             }
+                        }
+
+                        if let Some(event_type) = event_type {
+                            process_claude_event(
+                                event_type,
+                                &json,
+                                &msg_session_id,
+                                request_id.as_deref(),
+                                &mut stream,
+                                result_limit,
+                                &app_handle_clone,
+                            );
+                        }
 
-                            if let Some(event_type) = json.get("type").and_then(|v| v.as_str()) {
-                                process_claude_event(event_type, &json, &msg_session_id, &app_handle_clone);
+                        // A result finishes the message being answered, and
+                        // shows a restarted process is working again
+                        if event_type == Some("result") {
+                            if let Ok(mut session) = session_state_clone.lock() {
+                                if session.generation == generation && session.current_request().is_some() {
+                                    session.restarts = 0;
+                                    if let Some(message) = session.queue.pop_front() {
+                                        emit_request_event("finished", &message.request_id, &app_handle_clone);
+                                    }
+                                    send_next(&mut session, &app_handle_clone);
+                                }
                             }
                         }
                     }
-                    Err(e) => {
-                        let event = ClaudeEvent {
-                            event_type: "error".to_string(),
-                            session_id: String::new(),
-                            text: None,
-                            tool_id: None,
-                            tool_name: None,
-                            tool_input: None,
-                            tool_result: None,
-                            error: Some(format!("Read error: {}", e)),
-                        };
-                        let _ = app_handle_clone.emit("claude-event", event);
-                        break;
-                    }
+                }
+                Err(e) => {
+                    let request_id = session_state_clone
+                        .lock()
+                        .ok()
+                        .filter(|session| session.generation == generation)
+                        .and_then(|session| session.current_request());
+                    let event = ClaudeEvent {
+                        request_id,
+                        error: Some(format!("Read error: {}", e)),
+                        ..ClaudeEvent::new("error", "")
+                    };
+                    emit_claude_event(&app_handle_clone, event);
+                    break;
                 }
             }
+        }
+
+        handle_claude_exit(&session_state_clone, generation, stderr_rx, &app_handle_clone);
+    });
+
+    Ok(())
+}
+
+// Called when a Claude process's stdout closes. A process that was replaced
+// is left alone. One that failed, was killed or left a message unanswered
+// has crashed: it is restarted on its last session id with a growing delay
+// and the message it was answering is sent again, up to MAX_RESTARTS times.
+fn handle_claude_exit(
+    session_state: &Arc<Mutex<ClaudeSession>>,
+    generation: u64,
+    stderr_rx: mpsc::Receiver<String>,
+    app_handle: &AppHandle,
+) {
+    // Hold new messages while the exit is looked at
+    let child = {
+        let Ok(mut session) = session_state.lock() else { return };
+        if session.generation != generation {
+            return;
+        }
+        session.stdin = None;
+        session.restarting = true;
+        session.child.take()
+    };
+
+    let status = child.and_then(|mut child| child.wait().ok());
+    // Processes started by Claude can keep stderr open, so don't wait long
+    let stderr = stderr_rx.recv_timeout(Duration::from_secs(1)).unwrap_or_default();
+
+    let Ok(mut session) = session_state.lock() else { return };
+    if session.generation != generation {
+        return;
+    }
+
+    let crashed = !status.is_some_and(|s| s.success()) || !session.queue.is_empty();
+    if !crashed {
+        session.restarting = false;
+        session.session_id = None;
+        drop(session);
 
-            // Emit a complete event when stdout closes (process ended)
-            // This ensures the frontend stops showing "Thinking..."
+        // Emit any buffered errors, then a complete event so the frontend
+        // stops showing "Thinking..."
+        if !stderr.is_empty() && !stderr.contains("Error:") && claude_errors::classify(&stderr).is_none() {
             let event = ClaudeEvent {
-                event_type: "complete".to_string(),
-                session_id: String::new(),
-                text: None,
-                tool_id: None,
-                tool_name: None,
-                tool_input: None,
-                tool_result: None,
-                error: None,
+                error: Some(format!("Claude stderr: {}", stderr)),
+                ..ClaudeEvent::new("error", "")
             };
-            let _ = app_handle_clone.emit("claude-event", event);
+            emit_claude_event(app_handle, event);
+        }
+        let event = ClaudeEvent::new("complete", "");
+        emit_claude_event(app_handle, event);
+        return;
+    }
 
-            // Clear the session state since the process ended
-            if let Ok(mut session) = session_state_clone.lock() {
-                session.stdin = None;
-                session.child = None;
-                session.session_id = None;
-            }
-        });
+    let reason = describe_exit(status);
+    let session_id = session.session_id.clone().unwrap_or_default();
+    let request_id = session.current_request();
+    let lines: Vec<&str> = stderr.lines().collect();
+    let stderr_tail = lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..].join("\n");
+
+    // Restarting cannot fix some failures, such as being logged out
+    let error_info = claude_errors::classify(&stderr);
+    let fatal = error_info.as_ref().is_some_and(|info| !info.error_kind.retryable());
+
+    session.restarts += 1;
+    let attempt = session.restarts;
+    let give_up = fatal || attempt > MAX_RESTARTS;
+    drop(session);
+
+    let delay = RESTART_BACKOFF * 2u32.pow(attempt.min(MAX_RESTARTS) - 1);
+    let event = ClaudeEvent {
+        request_id: request_id.clone(),
+        text: Some(stderr_tail).filter(|tail| !tail.is_empty()),
+        error: Some(if fatal {
+            format!("{}; not restarting", reason)
+        } else if give_up {
+            format!("{}; gave up after {} restarts", reason, MAX_RESTARTS)
+        } else {
+            format!(
+                "{}; restarting in {}s (attempt {} of {})",
+                reason,
+                delay.as_secs(),
+                attempt,
+                MAX_RESTARTS
+            )
+        }),
+        error_info: error_info.clone(),
+        ..ClaudeEvent::new("session_crashed", &session_id)
+    };
+    emit_claude_event(app_handle, event);
+
+    if give_up {
+        let message = if fatal {
+            format!("Claude stopped: {}", reason)
+        } else {
+            format!("Claude could not be restarted: {}", reason)
+        };
+        give_up_session(session_state, generation, AppError::ClaudeCrashed { message }, error_info, app_handle);
+        return;
+    }
+
+    // This is the old process's reader thread, so waiting here blocks nothing
+    thread::sleep(delay);
+    if let Err(e) = restart_claude(session_state, generation, app_handle) {
+        give_up_session(session_state, generation, e, None, app_handle);
+    }
+}
+
+// Stop restarting a crashed session. Its messages fail with the reason,
+// which is kept so the next message to the folder gets ClaudeCrashed.
+fn give_up_session(
+    session_state: &Arc<Mutex<ClaudeSession>>,
+    generation: u64,
+    error: AppError,
+    error_info: Option<ErrorInfo>,
+    app_handle: &AppHandle,
+) {
+    let Ok(mut session) = session_state.lock() else { return };
+    if session.generation != generation {
+        return;
+    }
+    let request_id = session.current_request();
+    let session_id = session.session_id.take().unwrap_or_default();
+    let dropped: Vec<String> = session.queue.drain(..).map(|message| message.request_id).collect();
+    session.restarting = false;
+    session.restarts = 0;
+    session.crashed = Some(error.to_string());
+    drop(session);
+
+    let event = ClaudeEvent {
+        request_id,
+        error: Some(error.to_string()),
+        error_info,
+        ..ClaudeEvent::new("error", &session_id)
+    };
+    emit_claude_event(app_handle, event);
+    for request_id in dropped {
+        emit_request_event("removed", &request_id, app_handle);
+    }
+}
+
+// Start a new process for a crashed session, resuming its conversation.
+// Fails with ClaudeCrashed when the process cannot be started again.
+fn restart_claude(
+    session_state: &Arc<Mutex<ClaudeSession>>,
+    generation: u64,
+    app_handle: &AppHandle,
+) -> Result<(), AppError> {
+    let cmd = claude_cli::command(&app_handle.state::<ClaudeCliState>().0);
+
+    let mut session = session_state.lock().map_err(|e| e.to_string())?;
+    // A new session was started while waiting
+    if session.generation != generation || !session.restarting {
+        return Ok(());
     }
 
+    let resume = session.session_id.clone();
+    cmd.and_then(|cmd| spawn_claude(&mut session, session_state, cmd, resume.as_deref(), app_handle))
+        .map_err(|e| AppError::ClaudeCrashed {
+            message: format!("Failed to restart Claude: {}", e),
+        })?;
+
+    // Send the message the crashed process never answered again, then
+    // carry on with the queue
+    session.restarting = false;
+    if let Some(message) = session.queue.front_mut() {
+        message.state = MessageState::Queued;
+    }
+    send_next(&mut session, app_handle);
     Ok(())
 }
 
-fn process_claude_event(event_type: &str, json: &serde_json::Value, session_id: &str, app_handle: &AppHandle) {
+// Write the first queued message to Claude unless it is busy answering one
+fn send_next(session: &mut ClaudeSession, app_handle: &AppHandle) {
+    if session.restarting {
+        return;
+    }
+    let Some(message) = session.queue.front_mut() else {
+        return;
+    };
+    if message.state == MessageState::Started {
+        return;
+    }
+    let Some(stdin) = session.stdin.as_mut() else {
+        return;
+    };
+
+    // A closed pipe means the process has exited; its stdout reader
+    // restarts it and sends the message again
+    let _ = writeln!(stdin, "{}", message.line).and_then(|_| stdin.flush());
+    message.state = MessageState::Started;
+    emit_request_event("started", &message.request_id, app_handle);
+}
+
+// queued, started, finished or removed for a message
+fn emit_request_event(event_type: &str, request_id: &str, app_handle: &AppHandle) {
+    let event = ClaudeEvent {
+        request_id: Some(request_id.to_string()),
+        ..ClaudeEvent::new(event_type, "")
+    };
+    emit_claude_event(app_handle, event);
+}
+
+fn describe_exit(status: Option<ExitStatus>) -> String {
+    let Some(status) = status else {
+        return "Claude exited unexpectedly".to_string();
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("Claude was killed by signal {}", signal);
+        }
+    }
+
+    match status.code() {
+        Some(0) => "Claude exited before answering".to_string(),
+        Some(code) => format!("Claude exited with status {}", code),
+        None => "Claude exited unexpectedly".to_string(),
+    }
+}
+
+fn process_claude_event(
+    event_type: &str,
+    json: &serde_json::Value,
+    session_id: &str,
+    request_id: Option<&str>,
+    stream: &mut StreamState,
+    result_limit: usize,
+    app_handle: &AppHandle,
+) {
     match event_type {
         "system" => {
             let event = ClaudeEvent {
-                event_type: "init".to_string(),
-                session_id: session_id.to_string(),
-                text: None,
-                tool_id: None,
-                tool_name: None,
-                tool_input: None,
-                tool_result: None,
-                error: None,
+                request_id: request_id.map(str::to_string),
+                ..ClaudeEvent::new("init", session_id)
             };
-            let _ = app_handle.emit("claude-event", event);
+            emit_claude_event(app_handle, event);
         }
         "assistant" => {
             if let Some(message) = json.get("message") {
+                if let Some(stop_reason) = message.get("stop_reason").and_then(|v| v.as_str()) {
+                    stream.stop_reason = Some(stop_reason.to_string());
+                }
+                if let Some(usage) = message.get("usage").filter(|u| u.is_object()) {
+                    stream.usage = Some(usage.clone());
+                }
                 if let Some(content) = message.get("content").and_then(|c| c.as_array()) {
                     for block in content {
                         if let Some(block_type) = block.get("type").and_then(|t| t.as_str()) {
@@ -370,34 +890,43 @@ fn process_claude_event(event_type: &str, json: &serde_json::Value, session_id:
                                 "text" => {
                                     if let Some(text) = block.get("text").and_then(|t| t.as_str()) {
                                         let event = ClaudeEvent {
-                                            event_type: "text".to_string(),
-                                            session_id: session_id.to_string(),
+                                            request_id: request_id.map(str::to_string),
                                             text: Some(text.to_string()),
-                                            tool_id: None,
-                                            tool_name: None,
-                                            tool_input: None,
-                                            tool_result: None,
-                                            error: None,
+                                            ..ClaudeEvent::new("text", session_id)
                                         };
-                                        let _ = app_handle.emit("claude-event", event);
+                                        emit_claude_event(app_handle, event);
                                     }
                                 }
+                                // Extended thinking; redacted blocks are sent without text
+                                "thinking" | "redacted_thinking" => {
+                                    let text = block.get("thinking").and_then(|t| t.as_str()).map(str::to_string);
+                                    let event = ClaudeEvent {
+                                        request_id: request_id.map(str::to_string),
+                                        text,
+                                        ..ClaudeEvent::new("thinking", session_id)
+                                    };
+                                    emit_claude_event(app_handle, event);
+                                }
                                 "tool_use" => {
                                     let tool_id = block.get("id").and_then(|v| v.as_str()).unwrap_or("").to_string();
                                     let tool_name = block.get("name").and_then(|v| v.as_str()).unwrap_or("").to_string();
                                     let tool_input = block.get("input").cloned();
+                                    stream.tool_calls.insert(
+                                        tool_id.clone(),
+                                        ToolCall {
+                                            name: tool_name.clone(),
+                                            input: tool_input.clone().unwrap_or_default(),
+                                        },
+                                    );
 
                                     let event = ClaudeEvent {
-                                        event_type: "tool_use".to_string(),
-                                        session_id: session_id.to_string(),
-                                        text: None,
+                                        request_id: request_id.map(str::to_string),
                                         tool_id: Some(tool_id),
                                         tool_name: Some(tool_name),
                                         tool_input,
-                                        tool_result: None,
-                                        error: None,
+                                        ..ClaudeEvent::new("tool_use", session_id)
                                     };
-                                    let _ = app_handle.emit("claude-event", event);
+                                    emit_claude_event(app_handle, event);
                                 }
                                 _ => {}
                             }
@@ -413,121 +942,208 @@ fn process_claude_event(event_type: &str, json: &serde_json::Value, session_id:
                         if block.get("type").and_then(|t| t.as_str()) == Some("tool_result") {
                             let tool_id = block.get("tool_use_id").and_then(|v| v.as_str()).unwrap_or("").to_string();
 
-                            let tool_name = json.get("tool_use_result")
-                                .and_then(|r| r.get("file"))
-                                .and_then(|f| f.get("filePath"))
-                                .and_then(|p| p.as_str())
-                                .map(|p| p.split('/').last().unwrap_or(p).to_string());
-
-                            let result_summary = json.get("tool_use_result")
-                                .and_then(|r| r.get("file"))
-                                .and_then(|f| f.get("numLines"))
-                                .and_then(|n| n.as_i64())
-                                .map(|n| format!("Read {} lines", n))
-                                .unwrap_or_else(|| "Completed".to_string());
+                            let call = stream.tool_calls.remove(&tool_id);
+                            let (result, details) =
+                                tool_results::describe(block, json.get("tool_use_result"), call.as_ref(), result_limit);
 
                             let event = ClaudeEvent {
-                                event_type: "tool_result".to_string(),
-                                session_id: session_id.to_string(),
-                                text: None,
+                                request_id: request_id.map(str::to_string),
                                 tool_id: Some(tool_id),
-                                tool_name,
-                                tool_input: None,
-                                tool_result: Some(result_summary),
-                                error: None,
+                                tool_name: call.map(|call| call.name),
+                                tool_result: Some(result),
+                                tool_details: Some(details),
+                                ..ClaudeEvent::new("tool_result", session_id)
                             };
-                            let _ = app_handle.emit("claude-event", event);
+                            emit_claude_event(app_handle, event);
                         }
                     }
                 }
             }
         }
         "result" => {
+            // A failed turn, e.g. on a usage limit, still ends with a result
+            let is_error = json.get("is_error").and_then(|v| v.as_bool()).unwrap_or(false)
+                || json.get("subtype").and_then(|v| v.as_str()).is_some_and(|s| s.starts_with("error"));
+            if is_error {
+                let message = json.get("result")
+                    .and_then(|v| v.as_str())
+                    .or_else(|| json.get("subtype").and_then(|v| v.as_str()))
+                    .unwrap_or("Claude reported an error")
+                    .to_string();
+                let event = ClaudeEvent {
+                    request_id: request_id.map(str::to_string),
+                    error_info: claude_errors::classify(&message),
+                    error: Some(message),
+                    ..ClaudeEvent::new("error", session_id)
+                };
+                emit_claude_event(app_handle, event);
+            }
+
+            // The result's usage covers the whole turn; a message's only itself
+            let turn_info = TurnInfo {
+                stop_reason: stream.stop_reason.take(),
+                usage: json.get("usage").filter(|u| u.is_object()).cloned().or(stream.usage.take()),
+            };
             let event = ClaudeEvent {
-                event_type: "complete".to_string(),
-                session_id: session_id.to_string(),
-                text: None,
-                tool_id: None,
-                tool_name: None,
-                tool_input: None,
-                tool_result: None,
-                error: None,
+                request_id: request_id.map(str::to_string),
+                turn_info: Some(turn_info),
+                ..ClaudeEvent::new("complete", session_id)
             };
-            let _ = app_handle.emit("claude-event", event);
+            emit_claude_event(app_handle, event);
         }
         _ => {}
     }
 }
 
 // Inner message structure for stream-json format
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize)]
 struct StreamJsonInnerMessage {
     role: String,
-    content: String,
+    content: Vec<ContentBlock>,
 }
 
 // Outer message wrapper for stream-json format
-// Format: {"type":"user","message":{"role":"user","content":"..."}}
-#[derive(Debug, Serialize, Deserialize)]
+// Format: {"type":"user","message":{"role":"user","content":[{"type":"text","text":"..."}]}}
+#[derive(Debug, Serialize)]
 struct StreamJsonMessage {
     #[serde(rename = "type")]
     msg_type: String,
     message: StreamJsonInnerMessage,
 }
 
+// With `fit`, attachments too large for what is left of the context
+// window are cut down first, as estimate_context describes
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn send_to_claude(
     message: String,
     _session_id: Option<String>, // Kept for API compatibility but not used
     working_dir: String,
-    context: Option<String>,
+    attachments: Option<Vec<Attachment>>,
+    fit: Option<Fit>,
+    track_changes: Option<bool>,
     session_state: State<'_, ClaudeSessionState>,
     app_handle: AppHandle,
-) -> Result<String, String> {
-    // Build the full message with context if provided
-    let full_message = if let Some(ctx) = context {
-        format!("{}\n\n---\nContext:\n{}", message, ctx)
-    } else {
-        message
-    };
-
-    let session_arc = Arc::clone(&session_state.0);
-
-    // Ensure we have a running session
-    ensure_claude_session(&session_arc, &working_dir, &app_handle)?;
+) -> Result<String, AppError> {
+    // Attachments are read now, so a missing file fails the send rather than the answer
+    let max_read_size = app_handle.state::<FileSettingsState>().0.lock().map_err(|e| e.to_string())?.max_read_size;
+    let mut resolved = attachments::resolve(&attachments.unwrap_or_default(), &working_dir, max_read_size)?;
+    if fit.is_some() {
+        let (model, context_used) = session_state.0.lock().map_err(|e| e.to_string())?.context_for(&working_dir);
+        budget::estimate(&message, &mut resolved, model.as_deref(), context_used, fit);
+    }
+    let content = attachments::render(&message, &resolved);
 
     // Send the message as stream-json format
-    // Format: {"type":"user","message":{"role":"user","content":"..."}}
+    // Format: {"type":"user","message":{"role":"user","content":[{"type":"text","text":"..."}]}}
     let stream_msg = StreamJsonMessage {
         msg_type: "user".to_string(),
         message: StreamJsonInnerMessage {
             role: "user".to_string(),
-            content: full_message,
+            content,
         },
     };
 
     let json_msg = serde_json::to_string(&stream_msg)
         .map_err(|e| format!("Failed to serialize message: {}", e))?;
 
-    {
-        let mut session = session_arc.lock().map_err(|e| e.to_string())?;
-        if let Some(ref mut stdin) = session.stdin {
-            writeln!(stdin, "{}", json_msg)
-                .map_err(|e| format!("Failed to write to stdin: {}", e))?;
-            stdin.flush()
-                .map_err(|e| format!("Failed to flush stdin: {}", e))?;
-        } else {
-            return Err("No stdin available".to_string());
+    // Messages wait their turn, so each answer belongs to one request.
+    // The id goes back at once; a session that has to be started first
+    // is started in the background and reports failures as events.
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let mut session = session_state.0.lock().map_err(|e| e.to_string())?;
+    // A session given up on is reported once; sending again starts a new one
+    if session.working_dir == working_dir {
+        if let Some(message) = session.crashed.take() {
+            return Err(AppError::ClaudeCrashed { message });
         }
     }
+    let needs_start = prepare_session(&mut session, &working_dir, track_changes.unwrap_or(false), &app_handle);
+    session.queue.push_back(QueuedMessage {
+        request_id: request_id.clone(),
+        message,
+        state: MessageState::Queued,
+        line: json_msg,
+    });
+    emit_request_event("queued", &request_id, &app_handle);
+    send_next(&mut session, &app_handle);
+    let generation = session.generation;
+    drop(session);
+
+    if needs_start {
+        start_session(Arc::clone(&session_state.0), generation, app_handle);
+    }
+    Ok(request_id)
+}
+
+// Approximate tokens a message and its attachments would take, against
+// what is left of the model's context window. With `fit`, the estimate is
+// for the attachments as send_to_claude would cut them down.
+#[tauri::command]
+async fn estimate_context(
+    message: String,
+    working_dir: String,
+    attachments: Option<Vec<Attachment>>,
+    fit: Option<Fit>,
+    session_state: State<'_, ClaudeSessionState>,
+    app_handle: AppHandle,
+) -> Result<ContextEstimate, AppError> {
+    let max_read_size = app_handle.state::<FileSettingsState>().0.lock().map_err(|e| e.to_string())?.max_read_size;
+    let mut resolved = attachments::resolve(&attachments.unwrap_or_default(), &working_dir, max_read_size)?;
+    let (model, context_used) = session_state.0.lock().map_err(|e| e.to_string())?.context_for(&working_dir);
+    Ok(budget::estimate(&message, &mut resolved, model.as_deref(), context_used, fit))
+}
+
+// Messages Claude has not finished answering, in the order they are sent
+#[tauri::command]
+fn list_message_queue(session_state: State<'_, ClaudeSessionState>) -> Result<Vec<QueuedMessage>, AppError> {
+    let session = session_state.0.lock().map_err(|e| e.to_string())?;
+    Ok(session.queue.iter().cloned().collect())
+}
+
+// Set how many characters of each tool result are sent with tool_result events
+#[tauri::command]
+fn set_tool_result_limit(limit: usize, session_state: State<'_, ClaudeSessionState>) -> Result<(), AppError> {
+    if limit == 0 {
+        return Err(AppError::invalid("Tool result limit must be at least 1"));
+    }
+    let mut session = session_state.0.lock().map_err(|e| e.to_string())?;
+    session.tool_result_limit = limit;
+    Ok(())
+}
 
-    // Return the session_id if we have one
-    let session = session_arc.lock().map_err(|e| e.to_string())?;
-    Ok(session.session_id.clone().unwrap_or_else(|| "pending".to_string()))
+// Take back a message before it is sent to Claude
+#[tauri::command]
+fn remove_queued_message(
+    request_id: String,
+    session_state: State<'_, ClaudeSessionState>,
+    app_handle: AppHandle,
+) -> Result<(), AppError> {
+    let mut session = session_state.0.lock().map_err(|e| e.to_string())?;
+    let index = session
+        .queue
+        .iter()
+        .position(|message| message.request_id == request_id)
+        .ok_or_else(|| AppError::invalid(format!("No queued message with id {}", request_id)))?;
+    if session.queue[index].state == MessageState::Started {
+        return Err(AppError::conflict("Claude is already answering this message"));
+    }
+
+    session.queue.remove(index);
+    emit_request_event("removed", &request_id, &app_handle);
+    Ok(())
 }
 
 #[tauri::command]
-fn watch_directory(path: String, app_handle: AppHandle) -> Result<(), String> {
+fn watch_directory(path: String, app_handle: AppHandle) -> Result<(), AppError> {
+    let dir_path = Path::new(&path);
+    if !dir_path.exists() {
+        return Err(AppError::NotFound { path });
+    }
+    if !dir_path.is_dir() {
+        return Err(AppError::NotADirectory { path });
+    }
+
     let watch_path = path.clone();
 
     thread::spawn(move || {
@@ -573,19 +1189,57 @@ fn watch_directory(path: String, app_handle: AppHandle) -> Result<(), String> {
     Ok(())
 }
 
+pub use documents::DOCX_TOOLS_FLAG;
+
+// Serve the .docx editing tools to a Claude session over stdin/stdout
+pub fn run_docx_tools() {
+    let track_changes = std::env::args().any(|arg| arg == documents::TRACK_CHANGES_FLAG);
+    documents::serve_tools(track_changes);
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .manage(ClaudeSessionState(Arc::new(Mutex::new(ClaudeSession::new()))))
+        .manage(ResearchState(Arc::new(Mutex::new(ResearchManager::new()))))
+        .manage(FileSettingsState(Arc::new(Mutex::new(FileSettings::new()))))
+        .manage(ClaudeCliState(Arc::new(Mutex::new(ClaudeCli::new()))))
+        .setup(|app| {
+            let app_data_dir = app.path().app_data_dir()?;
+            claude_cli::load_settings(&app.state::<ClaudeCliState>().0, &app_data_dir);
+            let research_state = app.state::<ResearchState>();
+            research::restore_queue(&research_state.0, &app_data_dir, app.handle());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             list_directory,
             read_file,
             write_file,
+            files::stat_file,
+            files::read_file_range,
+            files::read_file_lines,
+            files::set_max_read_size,
+            references::resolve_reference,
             watch_directory,
             check_claude_available,
-            send_to_claude
+            claude_cli::get_claude_status,
+            claude_cli::set_claude_path,
+            send_to_claude,
+            estimate_context,
+            list_message_queue,
+            set_tool_result_limit,
+            remove_queued_message,
+            research::start_research,
+            research::list_research_tasks,
+            research::get_research_status,
+            research::cancel_research,
+            research::retry_research,
+            research::set_research_concurrency,
+            export::export_document,
+            documents::read_document,
+            documents::edit_docx
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
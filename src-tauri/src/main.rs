@@ -2,5 +2,10 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
-    clause_lib::run()
+    // Claude sessions start the app with this flag to use the .docx tools
+    if std::env::args().any(|arg| arg == clause_lib::DOCX_TOOLS_FLAG) {
+        clause_lib::run_docx_tools()
+    } else {
+        clause_lib::run()
+    }
 }
@@ -0,0 +1,307 @@
+use serde::Serialize;
+use std::path::Path;
+use tauri::State;
+
+use crate::attachments;
+use crate::documents::{self, DocxParagraph};
+use crate::error::AppError;
+use crate::export::slug;
+use crate::files::FileSettingsState;
+
+// A span of a file named by an @-mention, e.g. "notes.md:15-30",
+// "notes.md#Methods" or "proposal.docx#paragraph-12"
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedReference {
+    // The reference in its canonical form, which still names the same text
+    // when written again: headings by their slug, e.g. "notes.md#related-work"
+    pub anchor: String,
+    pub path: String,
+    pub span: Span,
+    // Heading the span starts under
+    pub heading: Option<String>,
+    pub text: String,
+}
+
+// Lines and paragraphs are numbered from 1, both ends included
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Span {
+    Whole,
+    Lines { start: usize, end: usize },
+    // Paragraphs of a .docx, as read_document numbers them
+    Paragraphs { start: usize, end: usize },
+}
+
+enum Target<'a> {
+    Whole,
+    // "file:15-30"; paragraphs for a .docx
+    Range(usize, usize),
+    // "file#paragraph-12" or "file#paragraph-12-15"
+    Paragraphs(usize, usize),
+    // "file#Heading Name" or "file#heading-name"
+    Heading(&'a str),
+}
+
+// Resolve a reference for a preview in the @-mention picker
+#[tauri::command]
+pub async fn resolve_reference(
+    reference: String,
+    working_dir: String,
+    settings: State<'_, FileSettingsState>,
+) -> Result<ResolvedReference, AppError> {
+    let max_read_size = settings.0.lock().map_err(|e| e.to_string())?.max_read_size;
+    resolve(&reference, Path::new(&working_dir), max_read_size)
+}
+
+pub fn resolve(reference: &str, working_dir: &Path, max_read_size: u64) -> Result<ResolvedReference, AppError> {
+    let (path, target) = parse(reference, working_dir)?;
+    let file_path = attachments::absolute(working_dir, path)?;
+    let is_docx = file_path
+        .extension()
+        .is_some_and(|e| e.to_string_lossy().eq_ignore_ascii_case("docx"));
+
+    let (span, heading, text) = if is_docx && !matches!(target, Target::Whole) {
+        if !file_path.is_file() {
+            return Err(AppError::NotFound { path: path.to_string() });
+        }
+        let paragraphs = documents::read_docx(&file_path)?.paragraphs;
+        resolve_paragraphs(&paragraphs, &target, path)?
+    } else {
+        let text = attachments::read_text(&file_path, max_read_size)?;
+        resolve_lines(&text, &target, path)?
+    };
+
+    let anchor = match (&target, span) {
+        (Target::Heading(name), _) => format!("{}#{}", path, slug(name)),
+        (_, Span::Whole) => path.to_string(),
+        (_, Span::Lines { start, end }) if start == end => format!("{}:{}", path, start),
+        (_, Span::Lines { start, end }) => format!("{}:{}-{}", path, start, end),
+        (_, Span::Paragraphs { start, end }) if start == end => format!("{}#paragraph-{}", path, start),
+        (_, Span::Paragraphs { start, end }) => format!("{}#paragraph-{}-{}", path, start, end),
+    };
+    Ok(ResolvedReference {
+        anchor,
+        path: file_path.display().to_string(),
+        span,
+        heading,
+        text,
+    })
+}
+
+fn parse<'a>(reference: &'a str, working_dir: &Path) -> Result<(&'a str, Target<'a>), AppError> {
+    let reference = reference.trim();
+    let reference = reference.strip_prefix('@').unwrap_or(reference);
+    // A file with '#' or ':' in its name is taken whole
+    if attachments::absolute(working_dir, reference).is_ok_and(|p| p.is_file()) {
+        return Ok((reference, Target::Whole));
+    }
+
+    let name_start = reference.rfind(['/', '\\']).map_or(0, |i| i + 1);
+    if let Some(hash) = reference[name_start..].find('#').map(|i| i + name_start) {
+        let (path, fragment) = (&reference[..hash], &reference[hash + 1..]);
+        if let Some(range) = fragment.strip_prefix("paragraph-") {
+            let (start, end) = parse_range(range)
+                .ok_or_else(|| AppError::invalid(format!("Not a paragraph number: {}", fragment)))?;
+            return Ok((path, Target::Paragraphs(start, end)));
+        }
+        if fragment.trim().is_empty() {
+            return Err(AppError::invalid(format!("No heading after '#' in {}", reference)));
+        }
+        return Ok((path, Target::Heading(fragment)));
+    }
+
+    // "C:\notes.md" has a colon too, but no number after it
+    if let Some(colon) = reference[name_start..].rfind(':').map(|i| i + name_start) {
+        if let Some((start, end)) = parse_range(&reference[colon + 1..]) {
+            return Ok((&reference[..colon], Target::Range(start, end)));
+        }
+    }
+    Ok((reference, Target::Whole))
+}
+
+// "15" or "15-30"
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let (start, end) = text.split_once('-').unwrap_or((text, text));
+    Some((start.trim().parse().ok()?, end.trim().parse().ok()?))
+}
+
+fn check_range(start: usize, end: usize, count: usize, unit: &str, path: &str) -> Result<(), AppError> {
+    if start == 0 || start > end || end > count {
+        return Err(AppError::invalid(format!(
+            "{} {}-{} are not in {}, which has {}",
+            unit, start, end, path, count
+        )));
+    }
+    Ok(())
+}
+
+fn resolve_lines(text: &str, target: &Target, path: &str) -> Result<(Span, Option<String>, String), AppError> {
+    let lines: Vec<&str> = text.lines().collect();
+    let headings = markdown_headings(&lines);
+    let (start, end) = match *target {
+        Target::Whole => return Ok((Span::Whole, None, text.to_string())),
+        Target::Range(start, end) => {
+            check_range(start, end, lines.len(), "Lines", path)?;
+            (start, end)
+        }
+        Target::Paragraphs(..) => {
+            return Err(AppError::invalid(format!(
+                "Paragraph references are for .docx files; use {}:<line> instead",
+                path
+            )))
+        }
+        Target::Heading(name) => {
+            let index = headings
+                .iter()
+                .position(|(_, _, text)| slug(text) == slug(name))
+                .ok_or_else(|| AppError::invalid(format!("No heading \"{}\" in {}", name, path)))?;
+            let (line, level, _) = headings[index];
+            let end = headings[index + 1..]
+                .iter()
+                .find(|(_, other, _)| *other <= level)
+                .map_or(lines.len(), |(next, _, _)| next - 1);
+            (line, end)
+        }
+    };
+
+    let heading = headings
+        .iter()
+        .rev()
+        .find(|(line, _, _)| *line <= start)
+        .map(|(_, _, text)| text.to_string());
+    Ok((Span::Lines { start, end }, heading, lines[start - 1..end].join("\n")))
+}
+
+fn resolve_paragraphs(
+    paragraphs: &[DocxParagraph],
+    target: &Target,
+    path: &str,
+) -> Result<(Span, Option<String>, String), AppError> {
+    let (start, end) = match *target {
+        Target::Range(start, end) | Target::Paragraphs(start, end) => {
+            check_range(start, end, paragraphs.len(), "Paragraphs", path)?;
+            (start, end)
+        }
+        Target::Heading(name) => {
+            let heading = paragraphs
+                .iter()
+                .find(|p| p.heading_level.is_some() && slug(&p.text) == slug(name))
+                .ok_or_else(|| AppError::invalid(format!("No heading \"{}\" in {}", name, path)))?;
+            let level = heading.heading_level.unwrap_or_default();
+            let end = paragraphs[heading.index..]
+                .iter()
+                .find(|p| p.heading_level.is_some_and(|other| other <= level))
+                .map_or(paragraphs.len(), |next| next.index - 1);
+            (heading.index, end)
+        }
+        Target::Whole => unreachable!("whole documents are read as markdown"),
+    };
+
+    let heading = paragraphs[..start]
+        .iter()
+        .rev()
+        .find(|p| p.heading_level.is_some())
+        .map(|p| p.text.clone());
+    let text = paragraphs[start - 1..end]
+        .iter()
+        .map(|p| p.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    Ok((Span::Paragraphs { start, end }, heading, text))
+}
+
+// (line, level, text) of each "#" heading, skipping fenced code blocks
+fn markdown_headings<'a>(lines: &[&'a str]) -> Vec<(usize, usize, &'a str)> {
+    let mut headings = Vec::new();
+    let mut in_code = false;
+    for (index, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        let level = line.chars().take_while(|c| *c == '#').count();
+        if !in_code && (1..=6).contains(&level) && line[level..].starts_with(' ') {
+            headings.push((index + 1, level, line[level..].trim().trim_end_matches('#').trim()));
+        }
+    }
+    headings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The parsed reference as "path|target", with no file on disk
+    fn parsed(reference: &str) -> String {
+        let (path, target) = parse(reference, Path::new("/nonexistent")).unwrap();
+        let target = match target {
+            Target::Whole => "whole".to_string(),
+            Target::Range(start, end) => format!("range {}-{}", start, end),
+            Target::Paragraphs(start, end) => format!("paragraphs {}-{}", start, end),
+            Target::Heading(name) => format!("heading {}", name),
+        };
+        format!("{}|{}", path, target)
+    }
+
+    #[test]
+    fn parses_ranges_and_headings() {
+        assert_eq!(parsed("notes.md"), "notes.md|whole");
+        assert_eq!(parsed("@notes.md:15"), "notes.md|range 15-15");
+        assert_eq!(parsed("notes.md:15-30"), "notes.md|range 15-30");
+        assert_eq!(parsed("notes.md#Related Work"), "notes.md|heading Related Work");
+        assert_eq!(parsed("docs/proposal.docx#paragraph-12"), "docs/proposal.docx|paragraphs 12-12");
+        assert_eq!(parsed("proposal.docx#paragraph-3-5"), "proposal.docx|paragraphs 3-5");
+    }
+
+    #[test]
+    fn parses_paths_with_colons_and_hashes() {
+        assert_eq!(parsed(r"C:\notes.md"), r"C:\notes.md|whole");
+        assert_eq!(parsed(r"C:\notes.md:4"), r"C:\notes.md|range 4-4");
+        assert_eq!(parsed("time 10:30.md"), "time 10:30.md|whole");
+        // A '#' in a folder name is not a heading
+        assert_eq!(parsed("c#/notes.md:2"), "c#/notes.md|range 2-2");
+    }
+
+    #[test]
+    fn rejects_bad_fragments() {
+        assert!(parse("notes.md#", Path::new("/nonexistent")).is_err());
+        assert!(parse("proposal.docx#paragraph-x", Path::new("/nonexistent")).is_err());
+    }
+
+    const NOTES: &str = "# Notes\nintro\n## Methods\nstep one\n```\n# not a heading\n```\n## Related Work\nother\n# Appendix\nend";
+
+    #[test]
+    fn resolves_line_ranges_with_their_heading() {
+        let (span, heading, text) = resolve_lines(NOTES, &Target::Range(4, 5), "notes.md").unwrap();
+        assert_eq!(span, Span::Lines { start: 4, end: 5 });
+        assert_eq!(heading.as_deref(), Some("Methods"));
+        assert_eq!(text, "step one\n```");
+
+        assert!(resolve_lines(NOTES, &Target::Range(0, 2), "notes.md").is_err());
+        assert!(resolve_lines(NOTES, &Target::Range(5, 99), "notes.md").is_err());
+        assert!(resolve_lines(NOTES, &Target::Paragraphs(1, 1), "notes.md").is_err());
+    }
+
+    #[test]
+    fn resolves_a_heading_to_its_section() {
+        let (span, heading, _) = resolve_lines(NOTES, &Target::Heading("methods"), "notes.md").unwrap();
+        // The fenced "# not a heading" does not end the section
+        assert_eq!(span, Span::Lines { start: 3, end: 7 });
+        assert_eq!(heading.as_deref(), Some("Methods"));
+
+        let (span, _, _) = resolve_lines(NOTES, &Target::Heading("Notes"), "notes.md").unwrap();
+        assert_eq!(span, Span::Lines { start: 1, end: 9 });
+
+        assert!(resolve_lines(NOTES, &Target::Heading("Missing"), "notes.md").is_err());
+    }
+
+    #[test]
+    fn slugs_headings_as_github_does() {
+        assert_eq!(slug("Related Work"), "related-work");
+        assert_eq!(slug("  What's new?  "), "whats-new");
+        assert_eq!(slug("A -- B"), "a----b");
+        assert_eq!(slug("snake_case name"), "snake_case-name");
+        assert_eq!(slug("Étude 2"), "étude-2");
+    }
+}
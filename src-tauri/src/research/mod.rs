@@ -0,0 +1,559 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::claude_cli::{self, ClaudeCliState};
+use crate::error::AppError;
+
+mod output;
+mod store;
+
+use output::ResearchReport;
+
+// How much of the research process's stderr to keep for error reporting
+const STDERR_TAIL_BYTES: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResearchStatus {
+    Pending,
+    Running,
+    Complete,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResearchTask {
+    pub id: String,
+    pub topic: String,
+    pub working_dir: String,
+    pub status: ResearchStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+    // Set for tasks interrupted by an app restart; the next run continues
+    // the recorded Claude session instead of starting over
+    #[serde(default)]
+    pub resumable: bool,
+    // Incremented on every (re)start so stale reader threads can tell
+    // their run has been cancelled or superseded
+    #[serde(skip)]
+    attempt: u32,
+}
+
+// Background research tasks. Each task runs its own `claude` process,
+// independent of the interactive editing session in ClaudeSessionState.
+pub struct ResearchManager {
+    tasks: Vec<ResearchTask>,
+    children: HashMap<String, Child>,
+    max_concurrent: usize,
+    // Where the queue is persisted; unset until the app data dir is known
+    store_path: Option<PathBuf>,
+}
+
+impl ResearchManager {
+    pub fn new() -> Self {
+        Self {
+            tasks: Vec::new(),
+            children: HashMap::new(),
+            max_concurrent: store::DEFAULT_MAX_CONCURRENT,
+            store_path: None,
+        }
+    }
+
+    fn task_mut(&mut self, task_id: &str) -> Result<&mut ResearchTask, String> {
+        self.tasks
+            .iter_mut()
+            .find(|t| t.id == task_id)
+            .ok_or_else(|| format!("Research task not found: {}", task_id))
+    }
+
+    // The still-running task for this attempt, or None if it was cancelled
+    // or restarted in the meantime
+    fn current_task(&mut self, task_id: &str, attempt: u32) -> Option<&mut ResearchTask> {
+        self.task_mut(task_id)
+            .ok()
+            .filter(|t| t.attempt == attempt && t.status == ResearchStatus::Running)
+    }
+
+    fn save(&self) {
+        if let Some(path) = &self.store_path {
+            if let Err(e) = store::save(path, self.max_concurrent, &self.tasks) {
+                eprintln!("{}", e);
+            }
+        }
+    }
+
+    // Persist the queue and tell the frontend that a task changed
+    fn notify(&self, app_handle: &AppHandle, task_id: &str) {
+        self.save();
+        if let Some(task) = self.tasks.iter().find(|t| t.id == task_id) {
+            let _ = app_handle.emit("research-progress", task.clone());
+        }
+    }
+}
+
+pub struct ResearchState(pub Arc<Mutex<ResearchManager>>);
+
+// Load the queue saved by the previous launch and resume interrupted tasks
+pub fn restore_queue(state: &Arc<Mutex<ResearchManager>>, app_data_dir: &Path, app_handle: &AppHandle) {
+    let path = app_data_dir.join(store::QUEUE_FILE);
+
+    {
+        let Ok(mut manager) = state.lock() else { return };
+        match store::load(&path) {
+            Ok(Some(queue)) => {
+                manager.max_concurrent = queue.max_concurrent.max(1);
+                manager.tasks = queue.tasks;
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("{}", e),
+        }
+        manager.store_path = Some(path);
+    }
+
+    schedule(state, app_handle);
+}
+
+fn research_prompt(topic: &str) -> String {
+    format!(
+        "Research the following topic thoroughly: {}\n\n\
+         Reply with the finished report only, as markdown: a `# ` title, \
+         a `## Key Findings` section of concise bullet points, any further \
+         `## ` sections you need, and a final `## Sources` section listing \
+         every source as a numbered markdown link. Do not write any files.",
+        topic
+    )
+}
+
+fn resume_prompt(topic: &str) -> String {
+    format!(
+        "You were interrupted while researching: {}\n\n\
+         Continue where you left off, then reply with the finished report \
+         in the format requested earlier.",
+        topic
+    )
+}
+
+// Start as many pending tasks as the concurrency limit allows
+fn schedule(state: &Arc<Mutex<ResearchManager>>, app_handle: &AppHandle) {
+    let Ok(mut manager) = state.lock() else { return };
+
+    loop {
+        let running = manager
+            .tasks
+            .iter()
+            .filter(|t| t.status == ResearchStatus::Running)
+            .count();
+        if running >= manager.max_concurrent {
+            break;
+        }
+
+        let Some(task_id) = manager
+            .tasks
+            .iter()
+            .find(|t| t.status == ResearchStatus::Pending)
+            .map(|t| t.id.clone())
+        else {
+            break;
+        };
+
+        // A spawn failure is recorded on the task, so just move on to the next one
+        let _ = spawn_research(&mut manager, state, &task_id, app_handle);
+    }
+}
+
+// What the stdout reader learned about a finished run
+#[derive(Default)]
+struct RunOutcome {
+    model: Option<String>,
+    cost_usd: Option<f64>,
+    report: Option<String>,
+    error: Option<String>,
+    got_result: bool,
+}
+
+// Record why a run could not start and save it, so the task does not stay
+// Running in the saved queue
+fn fail_to_start(
+    manager: &mut ResearchManager,
+    task_id: &str,
+    error: String,
+    app_handle: &AppHandle,
+) -> Result<(), String> {
+    if let Ok(task) = manager.task_mut(task_id) {
+        task.status = ResearchStatus::Failed;
+        task.error = Some(error.clone());
+    }
+    manager.notify(app_handle, task_id);
+    Err(error)
+}
+
+// Start the claude process for a pending task
+fn spawn_research(
+    manager: &mut ResearchManager,
+    state: &Arc<Mutex<ResearchManager>>,
+    task_id: &str,
+    app_handle: &AppHandle,
+) -> Result<(), String> {
+    let task = manager.task_mut(task_id)?;
+
+    let resume_session = task.session_id.clone().filter(|_| task.resumable);
+    task.attempt += 1;
+    task.status = ResearchStatus::Running;
+    task.resumable = false;
+    task.output_path = None;
+    task.error = None;
+    task.finished_at = None;
+    if resume_session.is_none() {
+        task.session_id = None;
+        task.model = None;
+        task.cost_usd = None;
+        task.started_at = Some(Utc::now());
+    }
+
+    let attempt = task.attempt;
+
+    let system_prompt = r#"You are a research assistant running in the background for the Clause editor.
+Work autonomously: do not ask questions, nobody will answer them.
+Use web search to find current, authoritative sources and cite every claim."#;
+
+    let mut cmd = match claude_cli::command(&app_handle.state::<ClaudeCliState>().0) {
+        Ok(cmd) => cmd,
+        Err(e) => return fail_to_start(manager, task_id, e.to_string(), app_handle),
+    };
+    cmd.arg("--print")
+        .arg("--verbose") // Required for stream-json output
+        .arg("--output-format")
+        .arg("stream-json")
+        .arg("--append-system-prompt")
+        .arg(system_prompt)
+        .arg("--allowedTools")
+        .arg("WebSearch,WebFetch,Read");
+    match &resume_session {
+        Some(session_id) => {
+            cmd.arg("--resume").arg(session_id).arg(resume_prompt(&task.topic));
+        }
+        None => {
+            cmd.arg(research_prompt(&task.topic));
+        }
+    }
+    cmd.current_dir(&task.working_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => return fail_to_start(manager, task_id, format!("Failed to spawn claude: {}", e), app_handle),
+    };
+
+    let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        let _ = child.kill();
+        let _ = child.wait();
+        return fail_to_start(manager, task_id, "Failed to read claude's output".to_string(), app_handle);
+    };
+    manager.children.insert(task_id.to_string(), child);
+    manager.notify(app_handle, task_id);
+
+    // Collect stderr so a failed run can report why
+    let stderr_handle = thread::spawn(move || {
+        let mut buffer = String::new();
+        let _ = BufReader::new(stderr).read_to_string(&mut buffer);
+        buffer
+    });
+
+    let state_clone = Arc::clone(state);
+    let app_handle_clone = app_handle.clone();
+    let task_id = task_id.to_string();
+
+    thread::spawn(move || {
+        let reader = BufReader::new(stdout);
+        let mut outcome = RunOutcome::default();
+
+        for line in reader.lines() {
+            let Ok(line) = line else { break };
+            let Ok(json) = serde_json::from_str::<serde_json::Value>(&line) else {
+                continue;
+            };
+
+            // Record the session id as soon as it is known, so an
+            // interrupted run can be resumed after a restart
+            if let Some(session_id) = json.get("session_id").and_then(|v| v.as_str()) {
+                if let Ok(mut manager) = state_clone.lock() {
+                    if let Some(task) = manager.current_task(&task_id, attempt) {
+                        if task.session_id.as_deref() != Some(session_id) {
+                            task.session_id = Some(session_id.to_string());
+                            manager.save();
+                        }
+                    }
+                }
+            }
+
+            match json.get("type").and_then(|v| v.as_str()) {
+                Some("system") => {
+                    if let Some(model) = json.get("model").and_then(|v| v.as_str()) {
+                        outcome.model = Some(model.to_string());
+                    }
+                }
+                Some("result") => {
+                    outcome.got_result = true;
+                    outcome.cost_usd = json.get("total_cost_usd").and_then(|v| v.as_f64());
+                    let text = json.get("result").and_then(|v| v.as_str());
+                    if json.get("is_error").and_then(|v| v.as_bool()).unwrap_or(false) {
+                        let message = text
+                            .or_else(|| json.get("subtype").and_then(|v| v.as_str()))
+                            .unwrap_or("Research failed");
+                        outcome.error = Some(message.to_string());
+                    } else {
+                        outcome.report = text.map(|t| t.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let stderr_output = stderr_handle.join().unwrap_or_default();
+        finish_research(&state_clone, &task_id, attempt, outcome, &stderr_output, &app_handle_clone);
+        schedule(&state_clone, &app_handle_clone);
+    });
+
+    Ok(())
+}
+
+// Reap the process and record the result of a run, saving the report on success
+fn finish_research(
+    state: &Arc<Mutex<ResearchManager>>,
+    task_id: &str,
+    attempt: u32,
+    outcome: RunOutcome,
+    stderr_output: &str,
+    app_handle: &AppHandle,
+) {
+    let (child, task) = {
+        let Ok(mut manager) = state.lock() else { return };
+        let Some(task) = manager.current_task(task_id, attempt).map(|t| t.clone()) else {
+            return;
+        };
+        (manager.children.remove(task_id), task)
+    };
+
+    let exit_ok = match child {
+        Some(mut child) => child.wait().map(|s| s.success()).unwrap_or(false),
+        None => false,
+    };
+    let finished_at = Utc::now();
+    let model = outcome.model.or(task.model.clone());
+    // A resumed run reports only its own cost, so add what was spent before
+    let cost_usd = match (task.cost_usd, outcome.cost_usd) {
+        (Some(before), Some(now)) => Some(before + now),
+        (before, now) => now.or(before),
+    };
+
+    let result: Result<String, String> = match (outcome.error, outcome.report) {
+        (Some(e), _) => Err(e),
+        (None, Some(report)) if outcome.got_result && exit_ok && !report.trim().is_empty() => {
+            let report = ResearchReport {
+                topic: &task.topic,
+                started_at: task.started_at.unwrap_or(finished_at),
+                finished_at,
+                model: model.as_deref(),
+                cost_usd,
+                session_id: task.session_id.as_deref(),
+                body: &report,
+            };
+            output::write_report(Path::new(&task.working_dir), &report)
+                .map(|path| path.to_string_lossy().to_string())
+        }
+        (None, Some(_)) if outcome.got_result && exit_ok => {
+            Err("Research finished without a report".to_string())
+        }
+        _ => {
+            let tail = stderr_tail(stderr_output);
+            Err(if tail.is_empty() {
+                "Research process exited unexpectedly".to_string()
+            } else {
+                tail
+            })
+        }
+    };
+
+    let Ok(mut manager) = state.lock() else { return };
+    let Some(task) = manager.current_task(task_id, attempt) else { return };
+
+    task.model = model;
+    task.cost_usd = cost_usd;
+    task.finished_at = Some(finished_at);
+    match result {
+        Ok(path) => {
+            task.status = ResearchStatus::Complete;
+            task.output_path = Some(path);
+        }
+        Err(e) => {
+            task.status = ResearchStatus::Failed;
+            task.error = Some(e);
+        }
+    }
+    manager.notify(app_handle, task_id);
+}
+
+fn stderr_tail(stderr: &str) -> String {
+    let trimmed = stderr.trim();
+    let mut start = trimmed.len().saturating_sub(STDERR_TAIL_BYTES);
+    while !trimmed.is_char_boundary(start) {
+        start += 1;
+    }
+    trimmed[start..].to_string()
+}
+
+#[tauri::command]
+pub fn start_research(
+    topic: String,
+    working_dir: String,
+    research_state: State<'_, ResearchState>,
+    app_handle: AppHandle,
+) -> Result<String, AppError> {
+    let topic = topic.trim().to_string();
+    if topic.is_empty() {
+        return Err(AppError::invalid("Research topic is empty"));
+    }
+    if !Path::new(&working_dir).is_dir() {
+        return Err(AppError::NotADirectory { path: working_dir });
+    }
+
+    let task = ResearchTask {
+        id: uuid::Uuid::new_v4().to_string(),
+        topic,
+        working_dir,
+        status: ResearchStatus::Pending,
+        session_id: None,
+        output_path: None,
+        error: None,
+        model: None,
+        cost_usd: None,
+        started_at: None,
+        finished_at: None,
+        resumable: false,
+        attempt: 0,
+    };
+    let task_id = task.id.clone();
+
+    {
+        let mut manager = research_state.0.lock().map_err(|e| e.to_string())?;
+        manager.tasks.push(task);
+        manager.notify(&app_handle, &task_id);
+    }
+
+    schedule(&research_state.0, &app_handle);
+
+    Ok(task_id)
+}
+
+#[tauri::command]
+pub fn list_research_tasks(research_state: State<'_, ResearchState>) -> Result<Vec<ResearchTask>, AppError> {
+    let manager = research_state.0.lock().map_err(|e| e.to_string())?;
+    Ok(manager.tasks.clone())
+}
+
+#[tauri::command]
+pub fn get_research_status(
+    task_id: String,
+    research_state: State<'_, ResearchState>,
+) -> Result<ResearchTask, AppError> {
+    let mut manager = research_state.0.lock().map_err(|e| e.to_string())?;
+    manager.task_mut(&task_id).map(|t| t.clone()).map_err(AppError::invalid)
+}
+
+#[tauri::command]
+pub fn cancel_research(
+    task_id: String,
+    research_state: State<'_, ResearchState>,
+    app_handle: AppHandle,
+) -> Result<(), AppError> {
+    {
+        let mut manager = research_state.0.lock().map_err(|e| e.to_string())?;
+
+        let task = manager.task_mut(&task_id).map_err(AppError::invalid)?;
+        if !matches!(task.status, ResearchStatus::Pending | ResearchStatus::Running) {
+            return Err(AppError::conflict(format!("Research task is not active: {}", task_id)));
+        }
+        task.status = ResearchStatus::Failed;
+        task.resumable = false;
+        task.error = Some("Cancelled".to_string());
+        manager.notify(&app_handle, &task_id);
+
+        if let Some(mut child) = manager.children.remove(&task_id) {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+
+    // The cancelled task may have freed a slot
+    schedule(&research_state.0, &app_handle);
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn retry_research(
+    task_id: String,
+    research_state: State<'_, ResearchState>,
+    app_handle: AppHandle,
+) -> Result<(), AppError> {
+    {
+        let mut manager = research_state.0.lock().map_err(|e| e.to_string())?;
+        let task = manager.task_mut(&task_id).map_err(AppError::invalid)?;
+        if task.status != ResearchStatus::Failed {
+            return Err(AppError::conflict(format!(
+                "Only failed research tasks can be retried: {}",
+                task_id
+            )));
+        }
+        task.status = ResearchStatus::Pending;
+        task.error = None;
+        manager.notify(&app_handle, &task_id);
+    }
+
+    schedule(&research_state.0, &app_handle);
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_research_concurrency(
+    limit: usize,
+    research_state: State<'_, ResearchState>,
+    app_handle: AppHandle,
+) -> Result<(), AppError> {
+    if limit == 0 {
+        return Err(AppError::invalid("Research concurrency limit must be at least 1"));
+    }
+
+    {
+        let mut manager = research_state.0.lock().map_err(|e| e.to_string())?;
+        manager.max_concurrent = limit;
+        manager.save();
+    }
+
+    // Raising the limit can let queued tasks start right away
+    schedule(&research_state.0, &app_handle);
+
+    Ok(())
+}
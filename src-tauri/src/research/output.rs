@@ -0,0 +1,312 @@
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+// Folder (relative to the working dir) where research results are saved
+pub const RESEARCH_DIR: &str = "deep-research";
+
+// Everything that goes into a saved research result
+pub struct ResearchReport<'a> {
+    pub topic: &'a str,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub model: Option<&'a str>,
+    pub cost_usd: Option<f64>,
+    pub session_id: Option<&'a str>,
+    pub body: &'a str,
+}
+
+// Turn a topic into a filename-friendly slug, e.g. "Korean chip market" -> "korean-chip-market"
+pub fn slugify(topic: &str) -> String {
+    let mut slug = String::new();
+    for c in topic.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.chars().count() >= 60 {
+            break;
+        }
+    }
+    let slug = slug.trim_end_matches('-').to_string();
+    if slug.is_empty() {
+        "research".to_string()
+    } else {
+        slug
+    }
+}
+
+// Write the report to deep-research/<topic-slug>.md, never overwriting an
+// earlier result: a taken name gets a numeric suffix (-2, -3, ...).
+pub fn write_report(working_dir: &Path, report: &ResearchReport) -> Result<PathBuf, String> {
+    let dir = working_dir.join(RESEARCH_DIR);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create research directory: {}", e))?;
+
+    let slug = slugify(report.topic);
+    let content = render_report(report);
+
+    for n in 1.. {
+        let file_name = if n == 1 {
+            format!("{}.md", slug)
+        } else {
+            format!("{}-{}.md", slug, n)
+        };
+        let path = dir.join(file_name);
+
+        // create_new makes claiming the name atomic, so two tasks finishing
+        // at once cannot pick the same file
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(content.as_bytes())
+                    .map_err(|e| format!("Failed to write research result: {}", e))?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("Failed to create research result: {}", e)),
+        }
+    }
+
+    unreachable!()
+}
+
+fn render_report(report: &ResearchReport) -> String {
+    let mut out = String::from("---\n");
+    out.push_str(&format!("topic: {}\n", yaml_string(report.topic)));
+    out.push_str(&format!(
+        "started: {}\n",
+        report.started_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+    ));
+    out.push_str(&format!(
+        "finished: {}\n",
+        report.finished_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+    ));
+    out.push_str(&format!(
+        "model: {}\n",
+        report.model.map(yaml_string).unwrap_or_else(|| "null".to_string())
+    ));
+    out.push_str(&format!(
+        "cost_usd: {}\n",
+        report
+            .cost_usd
+            .map(|c| format!("{:.4}", c))
+            .unwrap_or_else(|| "null".to_string())
+    ));
+    out.push_str(&format!(
+        "session_id: {}\n",
+        report.session_id.map(yaml_string).unwrap_or_else(|| "null".to_string())
+    ));
+    out.push_str("---\n\n");
+
+    let completed = report.finished_at.with_timezone(&Local).format("%Y-%m-%d").to_string();
+    out.push_str(&normalize_body(report.topic, report.body, &completed));
+    out
+}
+
+// Double-quoted YAML scalar, safe for any topic text
+fn yaml_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Bring Claude's answer into the layout the spec describes:
+// a single H1 title, a "Research completed" line, the findings, and a
+// trailing "## Sources" section.
+fn normalize_body(topic: &str, body: &str, completed: &str) -> String {
+    let body = strip_code_fence(body.trim());
+
+    let mut lines = body.lines().skip_while(|l| l.trim().is_empty()).peekable();
+    let title = match lines.peek() {
+        Some(first) if first.starts_with("# ") => {
+            let title = first[2..].trim().to_string();
+            lines.next();
+            title
+        }
+        _ => topic.to_string(),
+    };
+
+    let mut findings: Vec<&str> = Vec::new();
+    let mut sources: Vec<&str> = Vec::new();
+    let mut in_sources = false;
+
+    for line in lines {
+        if line.trim_start().starts_with("> Research completed:") {
+            continue;
+        }
+        if let Some(heading) = heading_text(line) {
+            in_sources = is_sources_heading(heading);
+            if in_sources {
+                continue;
+            }
+        }
+        if in_sources {
+            sources.push(line);
+        } else {
+            findings.push(line);
+        }
+    }
+
+    let findings = findings.join("\n").trim().to_string();
+    let mut sources = sources.join("\n").trim().to_string();
+
+    if sources.is_empty() {
+        sources = extract_links(&findings)
+            .iter()
+            .enumerate()
+            .map(|(i, (text, url))| format!("{}. [{}]({})", i + 1, text, url))
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+    if sources.is_empty() {
+        sources = "_No sources were cited._".to_string();
+    }
+
+    let mut out = format!("# {}\n\n> Research completed: {}\n\n", title, completed);
+    if !findings.is_empty() {
+        out.push_str(&findings);
+        out.push_str("\n\n");
+    }
+    out.push_str("## Sources\n\n");
+    out.push_str(&sources);
+    out.push('\n');
+    out
+}
+
+// Claude sometimes wraps the whole answer in ```markdown ... ```
+fn strip_code_fence(body: &str) -> &str {
+    if let Some(rest) = body.strip_prefix("```") {
+        if let (Some(start), true) = (rest.find('\n'), rest.ends_with("```")) {
+            let inner = &rest[start + 1..rest.len() - 3];
+            if !inner.contains("\n```") {
+                return inner.trim();
+            }
+        }
+    }
+    body
+}
+
+fn heading_text(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start_matches('#');
+    if trimmed.len() == line.len() || !trimmed.starts_with(' ') {
+        return None;
+    }
+    Some(trimmed.trim())
+}
+
+fn is_sources_heading(heading: &str) -> bool {
+    let heading = heading.trim_end_matches(':').to_lowercase();
+    matches!(heading.as_str(), "sources" | "references" | "bibliography" | "citations")
+}
+
+// Collect unique [text](http...) links in order of appearance
+fn extract_links(markdown: &str) -> Vec<(String, String)> {
+    let mut links: Vec<(String, String)> = Vec::new();
+    let mut rest = markdown;
+
+    while let Some(open) = rest.find('[') {
+        rest = &rest[open + 1..];
+        let Some(close) = rest.find("](") else { break };
+        let text = &rest[..close];
+        let after = &rest[close + 2..];
+        let Some(end) = after.find(')') else { break };
+        let url = &after[..end];
+
+        if !text.contains('[') && (url.starts_with("http://") || url.starts_with("https://")) {
+            if !links.iter().any(|(_, u)| u == url) {
+                links.push((text.to_string(), url.to_string()));
+            }
+            rest = &after[end + 1..];
+        }
+    }
+
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn report<'a>(topic: &'a str, body: &'a str) -> ResearchReport<'a> {
+        ResearchReport {
+            topic,
+            started_at: Utc.with_ymd_and_hms(2026, 3, 1, 9, 0, 0).unwrap(),
+            finished_at: Utc.with_ymd_and_hms(2026, 3, 1, 9, 30, 0).unwrap(),
+            model: Some("opus"),
+            cost_usd: Some(0.5),
+            session_id: None,
+            body,
+        }
+    }
+
+    #[test]
+    fn slugs_topics() {
+        assert_eq!(slugify("Korean chip market"), "korean-chip-market");
+        assert_eq!(slugify("  What's next?! (2026) "), "what-s-next-2026");
+        assert_eq!(slugify("Caf\u{e9} \u{4e16}\u{754c}"), "caf\u{e9}-\u{4e16}\u{754c}");
+        assert_eq!(slugify("???"), "research");
+        assert_eq!(slugify(&"word ".repeat(30)).chars().count(), 59);
+    }
+
+    #[test]
+    fn taken_names_get_a_suffix() {
+        let dir = std::env::temp_dir().join(format!("clause-research-{}", std::process::id()));
+        let paths: Vec<PathBuf> = (0..3)
+            .map(|_| write_report(&dir, &report("Chip market", "Findings")).unwrap())
+            .collect();
+        let names: Vec<String> = paths
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        let first = fs::read_to_string(&paths[0]).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(names, ["chip-market.md", "chip-market-2.md", "chip-market-3.md"]);
+        assert!(paths.iter().all(|p| p.starts_with(dir.join(RESEARCH_DIR))));
+        assert!(first.starts_with("---\ntopic: \"Chip market\"\n"));
+    }
+
+    #[test]
+    fn front_matter_records_the_run() {
+        let out = render_report(&report("Say \"hi\"\tnow", "Findings"));
+        let front_matter = out.split("---\n").nth(1).unwrap();
+        assert_eq!(
+            front_matter,
+            "topic: \"Say \\\"hi\\\"\\tnow\"\nstarted: 2026-03-01T09:00:00Z\nfinished: 2026-03-01T09:30:00Z\nmodel: \"opus\"\ncost_usd: 0.5000\nsession_id: null\n"
+        );
+    }
+
+    #[test]
+    fn body_gets_title_date_and_sources() {
+        let body = "```markdown\n# Chips in 2026\n\n> Research completed: yesterday\n\nDemand grew ([report](https://example.com/a)).\n\n### References:\n\n- [A](https://example.com/a)\n```";
+        assert_eq!(
+            normalize_body("chips", body, "2026-03-01"),
+            "# Chips in 2026\n\n> Research completed: 2026-03-01\n\nDemand grew ([report](https://example.com/a)).\n\n## Sources\n\n- [A](https://example.com/a)\n"
+        );
+    }
+
+    #[test]
+    fn sources_fall_back_to_the_links() {
+        let body = "See [one](https://a.example) and [two](http://b.example), [one again](https://a.example) and [local](notes.md).";
+        assert_eq!(
+            normalize_body("Topic", body, "2026-03-01"),
+            format!(
+                "# Topic\n\n> Research completed: 2026-03-01\n\n{}\n\n## Sources\n\n1. [one](https://a.example)\n2. [two](http://b.example)\n",
+                body
+            )
+        );
+        assert!(normalize_body("Topic", "No links.", "2026-03-01").ends_with("## Sources\n\n_No sources were cited._\n"));
+    }
+}
@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use super::{ResearchStatus, ResearchTask};
+
+// File in the app data dir that holds the research queue between launches
+pub const QUEUE_FILE: &str = "research-queue.json";
+
+// At most this many research processes run at once unless changed by the user
+pub const DEFAULT_MAX_CONCURRENT: usize = 2;
+
+#[derive(Serialize, Deserialize)]
+pub struct StoredQueue {
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+    #[serde(default)]
+    pub tasks: Vec<ResearchTask>,
+}
+
+fn default_max_concurrent() -> usize {
+    DEFAULT_MAX_CONCURRENT
+}
+
+pub fn load(path: &Path) -> Result<Option<StoredQueue>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read research queue: {}", e))?;
+    let mut queue: StoredQueue = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse research queue: {}", e))?;
+
+    // Anything still running when the app quit was interrupted. Runs that
+    // got far enough to record a session id pick up where they left off;
+    // the rest are marked failed so they can be retried.
+    for task in &mut queue.tasks {
+        task.resumable = false;
+        if task.status != ResearchStatus::Running {
+            continue;
+        }
+        if task.session_id.is_some() {
+            task.status = ResearchStatus::Pending;
+            task.resumable = true;
+        } else {
+            task.status = ResearchStatus::Failed;
+            task.error = Some("Interrupted before it started: the app was closed".to_string());
+        }
+    }
+
+    Ok(Some(queue))
+}
+
+pub fn save(path: &Path, max_concurrent: usize, tasks: &[ResearchTask]) -> Result<(), String> {
+    #[derive(Serialize)]
+    struct QueueRef<'a> {
+        max_concurrent: usize,
+        tasks: &'a [ResearchTask],
+    }
+
+    let json = serde_json::to_string_pretty(&QueueRef { max_concurrent, tasks })
+        .map_err(|e| format!("Failed to serialize research queue: {}", e))?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    }
+
+    // Write-then-rename so a crash mid-write never leaves a truncated queue
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, json).map_err(|e| format!("Failed to write research queue: {}", e))?;
+    fs::rename(&tmp_path, path).map_err(|e| format!("Failed to write research queue: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: &str, status: &str, session_id: Option<&str>) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "topic": "Topic",
+            "working_dir": "/tmp",
+            "status": status,
+            "session_id": session_id,
+        })
+    }
+
+    #[test]
+    fn interrupted_tasks_resume_or_fail() {
+        let path = std::env::temp_dir().join(format!("clause-{}-{}", std::process::id(), QUEUE_FILE));
+        let stored = serde_json::json!({
+            "tasks": [
+                task("resume", "running", Some("session-1")),
+                task("lost", "running", None),
+                task("queued", "pending", None),
+                task("done", "complete", Some("session-2")),
+            ]
+        });
+        fs::write(&path, stored.to_string()).unwrap();
+        let queue = load(&path).unwrap().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(queue.max_concurrent, DEFAULT_MAX_CONCURRENT);
+        let states: Vec<(&str, ResearchStatus, bool)> = queue
+            .tasks
+            .iter()
+            .map(|t| (t.id.as_str(), t.status, t.resumable))
+            .collect();
+        assert_eq!(
+            states,
+            [
+                ("resume", ResearchStatus::Pending, true),
+                ("lost", ResearchStatus::Failed, false),
+                ("queued", ResearchStatus::Pending, false),
+                ("done", ResearchStatus::Complete, false),
+            ]
+        );
+        assert!(queue.tasks[1].error.is_some());
+        assert_eq!(queue.tasks[0].session_id.as_deref(), Some("session-1"));
+    }
+
+    #[test]
+    fn saved_queues_load_back() {
+        let path = std::env::temp_dir().join(format!("clause-{}-saved-{}", std::process::id(), QUEUE_FILE));
+        assert!(load(&path).unwrap().is_none());
+
+        let tasks: Vec<ResearchTask> = serde_json::from_value(serde_json::json!([task("a", "failed", None)])).unwrap();
+        save(&path, 3, &tasks).unwrap();
+        let queue = load(&path).unwrap().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(queue.max_concurrent, 3);
+        assert_eq!(queue.tasks.len(), 1);
+        assert_eq!(queue.tasks[0].status, ResearchStatus::Failed);
+    }
+}
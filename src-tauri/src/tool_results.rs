@@ -0,0 +1,190 @@
+use serde::Serialize;
+use serde_json::Value;
+
+// Characters of a tool result sent to the frontend unless changed
+pub const DEFAULT_RESULT_LIMIT: usize = 20_000;
+
+// A tool_use seen in Claude's output, kept until its result arrives
+pub struct ToolCall {
+    pub name: String,
+    pub input: Value,
+}
+
+// Sent flattened into tool_result events next to the result text
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolResultDetails {
+    pub is_error: bool,
+    // One line for the chat, e.g. "Read 42 lines"
+    pub summary: String,
+    // Whether the result text was cut to the limit, and its full length in characters
+    pub truncated: bool,
+    pub full_length: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_path: Option<String>,
+    // What an Edit replaced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_string: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_string: Option<String>,
+}
+
+// Result text and details for a tool_result block. `tool_use_result` is the
+// CLI's structured result, sent beside the message that holds the block.
+pub fn describe(
+    block: &Value,
+    tool_use_result: Option<&Value>,
+    call: Option<&ToolCall>,
+    limit: usize,
+) -> (String, ToolResultDetails) {
+    let text = result_text(block);
+    let is_error = block.get("is_error").and_then(|v| v.as_bool()).unwrap_or(false);
+    let name = call.map(|c| c.name.as_str()).unwrap_or_default();
+    let input = call.map(|c| &c.input);
+    let structured = tool_use_result.filter(|r| r.is_object());
+
+    let summary = if is_error {
+        let line = text.lines().find(|l| !l.trim().is_empty()).unwrap_or("Failed");
+        line.chars().take(120).collect()
+    } else {
+        structured
+            .and_then(|r| r.get("file"))
+            .and_then(|f| f.get("numLines"))
+            .and_then(|n| n.as_i64())
+            .map(|n| format!("Read {} lines", n))
+            .unwrap_or_else(|| "Completed".to_string())
+    };
+
+    // The CLI's result has the final values; the call input is the fallback
+    let field = |result_path: &[&str], input_key: &str| {
+        structured
+            .and_then(|r| result_path.iter().try_fold(r, |v, key| v.get(key)))
+            .or_else(|| input.and_then(|i| i.get(input_key)))
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+    };
+    let file_path = field(&["filePath"], "file_path")
+        .or_else(|| field(&["file", "filePath"], "path"))
+        .or_else(|| field(&["notebook_path"], "notebook_path"));
+    let (old_string, new_string) = if name == "Edit" {
+        (field(&["oldString"], "old_string"), field(&["newString"], "new_string"))
+    } else {
+        (None, None)
+    };
+
+    let full_length = text.chars().count();
+    let truncated = full_length > limit;
+    let text = if truncated {
+        text.chars().take(limit).collect()
+    } else {
+        text
+    };
+
+    (
+        text,
+        ToolResultDetails {
+            is_error,
+            summary,
+            truncated,
+            full_length,
+            file_path,
+            old_string,
+            new_string,
+        },
+    )
+}
+
+// Content of a tool_result block is a string or a list of text and image blocks
+fn result_text(block: &Value) -> String {
+    match block.get("content") {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|part| match part.get("type").and_then(|t| t.as_str()) {
+                Some("text") => part.get("text").and_then(|t| t.as_str()).map(str::to_string),
+                Some("image") => Some("[image]".to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn call(name: &str, input: Value) -> ToolCall {
+        ToolCall {
+            name: name.to_string(),
+            input,
+        }
+    }
+
+    #[test]
+    fn long_results_are_cut_to_the_limit() {
+        let block = json!({ "type": "tool_result", "content": "\u{e9}".repeat(30) });
+        let (text, details) = describe(&block, None, None, 10);
+        assert_eq!(text, "\u{e9}".repeat(10));
+        assert!(details.truncated);
+        assert_eq!(details.full_length, 30);
+
+        let (text, details) = describe(&block, None, None, 30);
+        assert_eq!(text.chars().count(), 30);
+        assert!(!details.truncated);
+    }
+
+    #[test]
+    fn content_blocks_are_joined() {
+        let block = json!({
+            "content": [
+                { "type": "text", "text": "First" },
+                { "type": "image", "source": {} },
+                { "type": "text", "text": "Last" },
+            ]
+        });
+        let (text, details) = describe(&block, None, None, DEFAULT_RESULT_LIMIT);
+        assert_eq!(text, "First\n[image]\nLast");
+        assert_eq!(details.summary, "Completed");
+    }
+
+    #[test]
+    fn edits_report_what_they_replaced() {
+        let block = json!({ "content": "The file has been updated." });
+        let edit = call(
+            "Edit",
+            json!({ "file_path": "/w/a.md", "old_string": "from input", "new_string": "to input" }),
+        );
+
+        // The structured result wins over the call input
+        let result = json!({ "filePath": "/w/a.md", "oldString": "old", "newString": "new" });
+        let (_, details) = describe(&block, Some(&result), Some(&edit), DEFAULT_RESULT_LIMIT);
+        assert_eq!(details.file_path.as_deref(), Some("/w/a.md"));
+        assert_eq!(details.old_string.as_deref(), Some("old"));
+        assert_eq!(details.new_string.as_deref(), Some("new"));
+
+        let (_, details) = describe(&block, None, Some(&edit), DEFAULT_RESULT_LIMIT);
+        assert_eq!(details.old_string.as_deref(), Some("from input"));
+        assert_eq!(details.new_string.as_deref(), Some("to input"));
+
+        // Other tools have no replacement
+        let write = call("Write", json!({ "file_path": "/w/b.md", "content": "x" }));
+        let (_, details) = describe(&block, None, Some(&write), DEFAULT_RESULT_LIMIT);
+        assert_eq!(details.file_path.as_deref(), Some("/w/b.md"));
+        assert_eq!(details.old_string, None);
+    }
+
+    #[test]
+    fn summaries() {
+        let read = json!({ "type": "text", "file": { "filePath": "/w/a.md", "numLines": 42 } });
+        let (_, details) = describe(&json!({ "content": "..." }), Some(&read), None, DEFAULT_RESULT_LIMIT);
+        assert_eq!(details.summary, "Read 42 lines");
+        assert_eq!(details.file_path.as_deref(), Some("/w/a.md"));
+
+        let failed = json!({ "content": "\n\nFile does not exist.\nMore", "is_error": true });
+        let (_, details) = describe(&failed, None, None, DEFAULT_RESULT_LIMIT);
+        assert!(details.is_error);
+        assert_eq!(details.summary, "File does not exist.");
+    }
+}
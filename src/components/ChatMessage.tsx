@@ -1,3 +1,4 @@
+import { useState } from "react";
 import { Message, MessageContent } from "../types/claude";
 
 interface ChatMessageProps {
@@ -202,11 +203,23 @@ function ToolUseBlock({ content, darkMode }: { content: MessageContent & { type:
 
 function ToolResultBlock({ content, darkMode }: { content: MessageContent & { type: 'tool_result' }; darkMode: boolean }) {
   const textMuted = darkMode ? "text-gray-400" : "text-gray-500";
+  const textColor = content.isError ? "text-red-500" : textMuted;
+  const [expanded, setExpanded] = useState(false);
 
   return (
-    <div className={`flex items-center gap-1 ml-5 mb-2 text-sm ${textMuted}`}>
-      <span>└</span>
-      <span>{content.result}</span>
+    <div className={`ml-5 mb-2 text-sm ${textColor}`}>
+      <div
+        className={`flex items-center gap-1 ${content.result ? "cursor-pointer" : ""}`}
+        onClick={() => setExpanded(!expanded)}
+      >
+        <span>└</span>
+        <span>{content.summary}</span>
+      </div>
+      {expanded && content.result && (
+        <pre className="mt-1 ml-3 max-h-64 overflow-auto whitespace-pre-wrap break-words text-xs">
+          {content.result}
+        </pre>
+      )}
     </div>
   );
 }
@@ -222,6 +235,21 @@ function TextBlock({ text, darkMode }: { text: string; darkMode: boolean }) {
   );
 }
 
+function ThinkingBlock({ text, darkMode }: { text?: string; darkMode: boolean }) {
+  const textMuted = darkMode ? "text-gray-500" : "text-gray-400";
+  const [expanded, setExpanded] = useState(false);
+
+  return (
+    <div className={`my-2 text-sm italic ${textMuted}`}>
+      <div className={`flex items-center gap-2 ${text ? "cursor-pointer" : ""}`} onClick={() => setExpanded(!expanded)}>
+        <span>{expanded ? "▾" : "▸"}</span>
+        <span>{text ? "Thinking" : "Thinking (redacted)"}</span>
+      </div>
+      {expanded && text && <div className="mt-1 ml-5 whitespace-pre-wrap break-words">{text}</div>}
+    </div>
+  );
+}
+
 export function ChatMessage({ message, darkMode }: ChatMessageProps) {
   const isUser = message.role === "user";
   const border = darkMode ? "border-gray-700" : "border-gray-300";
@@ -249,6 +277,8 @@ export function ChatMessage({ message, darkMode }: ChatMessageProps) {
         switch (block.type) {
           case 'text':
             return <TextBlock key={index} text={block.text} darkMode={darkMode} />;
+          case 'thinking':
+            return <ThinkingBlock key={index} text={block.text} darkMode={darkMode} />;
           case 'tool_use':
             return <ToolUseBlock key={index} content={block} darkMode={darkMode} />;
           case 'tool_result':
@@ -257,6 +287,12 @@ export function ChatMessage({ message, darkMode }: ChatMessageProps) {
             return null;
         }
       })}
+      {message.stopReason && message.stopReason !== "end_turn" && (
+        <div className={`ml-5 text-xs ${darkMode ? "text-gray-500" : "text-gray-400"}`}>
+          {message.stopReason === "max_tokens" ? "Stopped at the output token limit" : `Stopped: ${message.stopReason}`}
+          {message.usage?.output_tokens !== undefined && ` · ${message.usage.output_tokens} tokens`}
+        </div>
+      )}
     </div>
   );
 }
@@ -8,6 +8,7 @@ import { TableCell } from "@tiptap/extension-table-cell";
 import { TableHeader } from "@tiptap/extension-table-header";
 import { Markdown } from "tiptap-markdown";
 import { invoke } from "@tauri-apps/api/core";
+import { errorMessage } from "../types/errors";
 import { listen } from "@tauri-apps/api/event";
 import * as Diff from "diff";
 
@@ -24,6 +25,11 @@ interface FileChangeEvent {
   kind: string;
 }
 
+type FileContent =
+  | { kind: "text"; content: string; encoding: string; bom: boolean; line_ending: "lf" | "crlf" | "mixed" }
+  | { kind: "binary"; mime: string; size: number }
+  | { kind: "too_large"; size: number; limit: number };
+
 interface DiffChange {
   id: string;
   added?: string;
@@ -143,7 +149,14 @@ export function Editor({ filePath, darkMode, onAddToContext }: EditorProps) {
     currentPathRef.current = filePath;
 
     try {
-      const newContent = await invoke<string>("read_file", { path: filePath });
+      const file = await invoke<FileContent>("read_file", { path: filePath });
+      if (file.kind === "binary") {
+        throw new Error(`Cannot open binary file (${file.mime})`);
+      }
+      if (file.kind === "too_large") {
+        throw new Error(`File is too large to open (${(file.size / 1048576).toFixed(1)} MB)`);
+      }
+      const newContent = file.content;
 
       if (showDiffs && lastSavedContentRef.current && lastSavedContentRef.current !== newContent) {
         // Store the pre-change content for potential revert
@@ -161,7 +174,7 @@ export function Editor({ filePath, darkMode, onAddToContext }: EditorProps) {
       lastSavedContentRef.current = newContent;
       isLoadingExternalRef.current = false;
     } catch (err) {
-      setError(String(err));
+      setError(errorMessage(err));
       editor.commands.setContent("");
     } finally {
       setLoading(false);
@@ -1,5 +1,6 @@
 import { useState, useEffect, useCallback } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { errorMessage } from "../types/errors";
 import { listen } from "@tauri-apps/api/event";
 
 interface FileEntry {
@@ -135,7 +136,7 @@ export function FileTree({ rootPath, activeFile, onSelectFile, darkMode }: FileT
       const result = await invoke<FileEntry[]>("list_directory", { path: rootPath });
       setEntries(result);
     } catch (err) {
-      setError(String(err));
+      setError(errorMessage(err));
       setEntries([]);
     } finally {
       setLoading(false);
@@ -3,7 +3,36 @@ import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import { ContextChip } from "../App";
 import { ChatMessage } from "./ChatMessage";
-import { Message, MessageContent, ClaudeEvent } from "../types/claude";
+import { Attachment, Message, MessageContent, ClaudeEvent } from "../types/claude";
+
+// Apply `update` to the assistant reply for a request, adding the reply if
+// this is the first event for it. Events without a request id go to the
+// last message when it is a reply.
+function updateReply(
+  prev: Message[],
+  requestId: string | undefined,
+  update: (content: MessageContent[]) => MessageContent[],
+): Message[] {
+  const index = requestId
+    ? prev.findIndex((m) => m.role === "assistant" && m.requestId === requestId)
+    : prev.length - 1;
+  const reply = index >= 0 ? prev[index] : undefined;
+  if (reply && reply.role === "assistant") {
+    const next = [...prev];
+    next[index] = { ...reply, content: update(reply.content) };
+    return next;
+  }
+  return [
+    ...prev,
+    {
+      id: `msg-${Date.now()}`,
+      role: "assistant",
+      requestId,
+      content: update([]),
+      timestamp: Date.now(),
+    },
+  ];
+}
 
 interface RightPanelProps {
   isOpen: boolean;
@@ -23,6 +52,11 @@ export function RightPanel({ isOpen, darkMode, contextChips, onRemoveChip, worki
   const messagesEndRef = useRef<HTMLDivElement>(null);
   const textareaRef = useRef<HTMLTextAreaElement>(null);
 
+  // Request id of the message last sent; status updates for older ones are ignored
+  const currentRequestRef = useRef<string | null>(null);
+  // Highest event seq applied to the loading state, so late events can't undo newer ones
+  const lastSeqRef = useRef(0);
+
   // Track pending tool uses to match with results
   const pendingToolsRef = useRef<Map<string, { name: string; input: Record<string, unknown> }>>(new Map());
 
@@ -49,48 +83,36 @@ export function RightPanel({ isOpen, darkMode, contextChips, onRemoveChip, worki
         setSessionId(e.session_id);
       }
 
+      // Only the newest event for the current request changes the status line
+      const isCurrent =
+        !e.request_id || !currentRequestRef.current || e.request_id === currentRequestRef.current;
+      const updatesStatus = isCurrent && e.seq > lastSeqRef.current;
+      if (updatesStatus) {
+        lastSeqRef.current = e.seq;
+      }
+      const setStatus = (text: string | null) => {
+        if (updatesStatus) setStatusText(text);
+      };
+
       switch (e.type) {
         case "init":
-          setStatusText("Thinking...");
+          setStatus("Thinking...");
           break;
 
         case "text":
           if (e.text) {
             const textContent = e.text; // Capture in variable for TypeScript
-            setStatusText(null);
-            setMessages((prev) => {
-              // Find the last assistant message to append to
-              const lastMsg = prev[prev.length - 1];
-              if (lastMsg && lastMsg.role === "assistant") {
-                // Check if last content block is text, append to it
-                const lastContent = lastMsg.content[lastMsg.content.length - 1];
+            setStatus(null);
+            setMessages((prev) =>
+              updateReply(prev, e.request_id, (content) => {
+                // Append to the last text block, or start one
+                const lastContent = content[content.length - 1];
                 if (lastContent && lastContent.type === "text") {
-                  const newContent: MessageContent[] = [...lastMsg.content];
-                  newContent[newContent.length - 1] = {
-                    type: "text" as const,
-                    text: lastContent.text + textContent,
-                  };
-                  return [...prev.slice(0, -1), { ...lastMsg, content: newContent }];
-                } else {
-                  // Add new text block
-                  return [
-                    ...prev.slice(0, -1),
-                    { ...lastMsg, content: [...lastMsg.content, { type: "text" as const, text: textContent }] },
-                  ];
+                  return [...content.slice(0, -1), { type: "text", text: lastContent.text + textContent }];
                 }
-              } else {
-                // Create new assistant message
-                return [
-                  ...prev,
-                  {
-                    id: `msg-${Date.now()}`,
-                    role: "assistant" as const,
-                    content: [{ type: "text" as const, text: textContent }],
-                    timestamp: Date.now(),
-                  },
-                ];
-              }
-            });
+                return [...content, { type: "text", text: textContent }];
+              }),
+            );
           }
           break;
 
@@ -119,84 +141,91 @@ export function RightPanel({ isOpen, darkMode, contextChips, onRemoveChip, worki
               status = `Finding files matching "${toolInput.pattern}"...`;
             }
 
-            setStatusText(status);
+            setStatus(status);
             // Store for later matching
             pendingToolsRef.current.set(e.tool_id, {
               name: e.tool_name,
               input: toolInput,
             });
 
-            setMessages((prev) => {
-              const lastMsg = prev[prev.length - 1];
-              const toolContent: MessageContent = {
-                type: "tool_use",
-                id: e.tool_id!,
-                name: e.tool_name!,
-                input: (e.tool_input as Record<string, unknown>) || {},
-              };
-
-              if (lastMsg && lastMsg.role === "assistant") {
-                return [
-                  ...prev.slice(0, -1),
-                  { ...lastMsg, content: [...lastMsg.content, toolContent] },
-                ];
-              } else {
-                return [
-                  ...prev,
-                  {
-                    id: `msg-${Date.now()}`,
-                    role: "assistant",
-                    content: [toolContent],
-                    timestamp: Date.now(),
-                  },
-                ];
-              }
-            });
+            const toolContent: MessageContent = {
+              type: "tool_use",
+              id: e.tool_id,
+              name: e.tool_name,
+              input: toolInput,
+            };
+            setMessages((prev) => updateReply(prev, e.request_id, (content) => [...content, toolContent]));
           }
           break;
 
         case "tool_result":
           if (e.tool_id) {
             const toolInfo = pendingToolsRef.current.get(e.tool_id);
-            setStatusText("Analyzing results...");
-
-            setMessages((prev) => {
-              const lastMsg = prev[prev.length - 1];
-              const resultContent: MessageContent = {
-                type: "tool_result",
-                id: e.tool_id!,
-                name: toolInfo?.name || e.tool_name || "Tool",
-                result: e.tool_result || "Completed",
-              };
-
-              if (lastMsg && lastMsg.role === "assistant") {
-                return [
-                  ...prev.slice(0, -1),
-                  { ...lastMsg, content: [...lastMsg.content, resultContent] },
-                ];
-              }
-              return prev;
-            });
+            setStatus("Analyzing results...");
+
+            const resultContent: MessageContent = {
+              type: "tool_result",
+              id: e.tool_id,
+              name: toolInfo?.name || e.tool_name || "Tool",
+              result: e.tool_result || "",
+              summary: e.summary || "Completed",
+              isError: e.is_error,
+            };
+            setMessages((prev) => updateReply(prev, e.request_id, (content) => [...content, resultContent]));
 
             pendingToolsRef.current.delete(e.tool_id);
           }
           break;
 
+        case "session_crashed":
+          // Claude is restarted and sent the message again; an error event follows if it gives up
+          setStatus(e.error || "Restarting Claude...");
+          break;
+
+        case "thinking":
+          setStatus("Thinking...");
+          setMessages((prev) => updateReply(prev, e.request_id, (content) => [...content, { type: "thinking", text: e.text }]));
+          break;
+
         case "complete":
-          setIsLoading(false);
-          setStatusText(null);
+          if (e.stop_reason || e.usage) {
+            setMessages((prev) =>
+              prev.map((m) =>
+                m.role === "assistant" && e.request_id && m.requestId === e.request_id
+                  ? { ...m, stopReason: e.stop_reason, usage: e.usage }
+                  : m,
+              ),
+            );
+          }
+          if (updatesStatus) {
+            setIsLoading(false);
+            setStatusText(null);
+          }
+          break;
+
+        // finished and removed also end a request whose complete event was missed
+        case "finished":
+        case "removed":
+          if (updatesStatus) {
+            setIsLoading(false);
+            setStatusText(null);
+          }
           break;
 
         case "error":
-          setIsLoading(false);
-          setStatusText(null);
+          if (updatesStatus) {
+            setIsLoading(false);
+            setStatusText(null);
+          }
           if (e.error) {
+            // Known failures come with a hint on what to do about them
+            const errorText = e.hint ? `Error: ${e.error}\n\n${e.hint}` : `Error: ${e.error}`;
             setMessages((prev) => [
               ...prev,
               {
                 id: `msg-${Date.now()}`,
                 role: "assistant",
-                content: [{ type: "text", text: `Error: ${e.error}` }],
+                content: [{ type: "text", text: errorText }],
                 timestamp: Date.now(),
               },
             ]);
@@ -230,18 +259,21 @@ export function RightPanel({ isOpen, darkMode, contextChips, onRemoveChip, worki
     setInputValue("");
     setIsLoading(true);
     setStatusText("Thinking...");
+    currentRequestRef.current = null;
 
-    // Build context from chips
-    const context = contextChips.length > 0
-      ? contextChips.map((chip) => `From ${chip.fileName} (lines ${chip.lineRange}):\n${chip.text}`).join("\n\n")
-      : undefined;
+    // Chips hold editor selections; the backend finds their lines in the file
+    const attachments: Attachment[] = contextChips.map((chip) => ({
+      type: "selection",
+      text: chip.text,
+      path: chip.filePath ?? undefined,
+    }));
 
     try {
-      await invoke<string>("send_to_claude", {
+      currentRequestRef.current = await invoke<string>("send_to_claude", {
         message: trimmedInput,
         sessionId,
         workingDir: workingDir || ".",
-        context,
+        attachments,
       });
     } catch (error) {
       console.error("Failed to send message:", error);
@@ -1,28 +1,96 @@
 export interface Message {
   id: string;
   role: 'user' | 'assistant';
+  // For replies, the request they answer
+  requestId?: string;
   content: MessageContent[];
   timestamp: number;
+  // For replies, how the turn ended
+  stopReason?: string;
+  usage?: TokenUsage;
+}
+
+export interface TokenUsage {
+  input_tokens?: number;
+  output_tokens?: number;
+  cache_creation_input_tokens?: number;
+  cache_read_input_tokens?: number;
 }
 
 export type MessageContent =
   | { type: 'text'; text: string }
+  // Redacted thinking has no text
+  | { type: 'thinking'; text?: string }
   | { type: 'tool_use'; id: string; name: string; input: Record<string, unknown> }
-  | { type: 'tool_result'; id: string; name: string; result: string };
+  | { type: 'tool_result'; id: string; name: string; result: string; summary: string; isError?: boolean };
+
+// Context sent with a message to send_to_claude. Relative paths are
+// resolved against the working dir; lines count from 1.
+export type Attachment =
+  | { type: 'file'; path: string; start_line?: number; end_line?: number }
+  | { type: 'directory'; path: string; depth?: number }
+  // Offsets into the file, as the editor counts them
+  | { type: 'selection'; text: string; path?: string; start?: number; end?: number }
+  | { type: 'image'; path: string }
+  | { type: 'snippet'; text: string; label?: string }
+  // An @-mention such as "notes.md:15-30", "notes.md#Methods" or "proposal.docx#paragraph-12"
+  | { type: 'reference'; reference: string };
+
+// From resolve_reference, for previews in the @-mention picker. Lines and
+// paragraphs count from 1, both ends included.
+export interface ResolvedReference {
+  // Canonical form of the reference, e.g. "notes.md#related-work"
+  anchor: string;
+  path: string;
+  span:
+    | { kind: 'whole' }
+    | { kind: 'lines'; start: number; end: number }
+    | { kind: 'paragraphs'; start: number; end: number };
+  heading: string | null;
+  text: string;
+}
 
 export interface ClaudeEvent {
-  type: 'init' | 'text' | 'tool_use' | 'tool_result' | 'complete' | 'error';
+  type:
+    | 'init' | 'text' | 'thinking' | 'tool_use' | 'tool_result' | 'complete' | 'error' | 'session_crashed'
+    // A message moving through the queue
+    | 'queued' | 'started' | 'finished' | 'removed';
   session_id: string;
-  // For text
+  // Increases with every event emitted, across requests
+  seq: number;
+  // Id returned by send_to_claude for the message this event belongs to
+  request_id?: string;
+  // For text and thinking, and the stderr tail for session_crashed
   text?: string;
   // For tool_use
   tool_id?: string;
   tool_name?: string;
   tool_input?: Record<string, unknown>;
-  // For tool_result
+  // For tool_result: the output, cut to the limit set with set_tool_result_limit
   tool_result?: string;
+  is_error?: boolean;
+  summary?: string;
+  truncated?: boolean;
+  full_length?: number;
+  file_path?: string;
+  // For Edit results
+  old_string?: string;
+  new_string?: string;
+  // For complete
+  stop_reason?: string;
+  usage?: TokenUsage;
   // For error
   error?: string;
+  error_kind?: 'not_logged_in' | 'rate_limited' | 'overloaded' | 'context_too_long' | 'network' | 'invalid_flag';
+  hint?: string;
+  // When a rate limit lifts
+  reset_at?: string;
+}
+
+export interface QueuedMessage {
+  request_id: string;
+  message: string;
+  state: 'queued' | 'started';
 }
 
 export interface ChatState {
@@ -31,3 +99,23 @@ export interface ChatState {
   isLoading: boolean;
   isConnected: boolean;
 }
+
+// How send_to_claude and estimate_context make oversized attachments fit
+export type Fit = 'truncate' | 'outline';
+
+// From estimate_context; token counts are approximate
+export interface ContextEstimate {
+  message_tokens: number;
+  attachments: {
+    label: string;
+    path: string | null;
+    tokens: number;
+    // Before `fit` cut the attachment down
+    original_tokens?: number;
+  }[];
+  total_tokens: number;
+  context_used: number;
+  context_window: number;
+  model: string | null;
+  fits: boolean;
+}
@@ -0,0 +1,28 @@
+// Error returned by every Tauri command
+export interface AppError {
+  code:
+    | 'not_found'
+    | 'not_a_directory'
+    | 'not_a_file'
+    | 'permission_denied'
+    | 'conflict'
+    | 'outside_workspace'
+    | 'claude_not_installed'
+    | 'claude_unsupported'
+    | 'claude_crashed'
+    | 'invalid_input'
+    | 'io'
+    | 'failed';
+  message: string;
+  path?: string;
+}
+
+export function isAppError(err: unknown): err is AppError {
+  return typeof err === 'object' && err !== null && 'code' in err && 'message' in err;
+}
+
+export function errorMessage(err: unknown): string {
+  if (isAppError(err)) return err.message;
+  if (err instanceof Error) return err.message;
+  return String(err);
+}